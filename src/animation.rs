@@ -0,0 +1,260 @@
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::board::BoardEvent;
+use crate::grid::{shade, Tile};
+
+/// How long a locked piece flashes.
+const LOCK_FLASH_TIME: Duration = Duration::from_millis(120);
+/// How long the name of a clear is shown.
+const ACTION_TEXT_TIME: Duration = Duration::from_millis(1500);
+/// How long each flash of the cleared lines lasts.
+const CLEAR_FLASH_TIME: Duration = Duration::from_millis(75);
+
+/// How cleared lines disappear during the line clear delay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ClearAnimation {
+    /// the lines blink white
+    #[default]
+    Flash,
+    /// the lines vanish from the middle outwards
+    Dissolve,
+}
+
+impl ClearAnimation {
+    pub const ALL: [ClearAnimation; 2] = [ClearAnimation::Flash, ClearAnimation::Dissolve];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClearAnimation::Flash => "flash",
+            ClearAnimation::Dissolve => "dissolve",
+        }
+    }
+}
+
+impl FromStr for ClearAnimation {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        ClearAnimation::ALL
+            .into_iter()
+            .find(|animation| animation.name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "clear animation must be one of {}",
+                    ClearAnimation::ALL
+                        .map(|animation| animation.name())
+                        .join(", ")
+                )
+            })
+    }
+}
+
+/// Effects drawn on top of the board, started by the events of the board.
+#[derive(Debug, Default)]
+pub struct Animations {
+    /// rows being cleared, and when they were
+    clear: Option<(Vec<usize>, Instant)>,
+    /// cells of the last locked piece, and when it locked
+    lock: Option<([(usize, usize); 4], Instant)>,
+    /// lines naming the last clear, and when it happened
+    text: Option<(Vec<String>, Instant)>,
+}
+
+impl Animations {
+    /// Starts the animations of something that happened on the board.
+    pub fn push(&mut self, event: &BoardEvent, now: Instant) {
+        match event {
+            BoardEvent::PieceLocked { cells, .. } => self.lock = Some((*cells, now)),
+            BoardEvent::LinesCleared {
+                rows,
+                count,
+                tspin,
+                b2b,
+                combo,
+            } => {
+                // the cleared lines have their own animation
+                self.lock = None;
+                self.clear = Some((rows.clone(), now));
+                let mut lines = vec![clear_name(*count, *tspin)];
+                if *b2b > 0 {
+                    lines.push(format!("B2B x{b2b}"));
+                }
+                if *combo > 0 {
+                    lines.push(format!("COMBO {combo}"));
+                }
+                self.text = Some((lines, now));
+            }
+            BoardEvent::PerfectClear => {
+                let (lines, _) = self.text.get_or_insert_with(|| (Vec::new(), now));
+                lines.push("PERFECT CLEAR".to_string());
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether anything is still moving, so the screen needs to be redrawn.
+    pub fn is_running(&self, now: Instant) -> bool {
+        let running = |since: Instant, time: Duration| now.duration_since(since) < time;
+        self.lock
+            .is_some_and(|(_, since)| running(since, LOCK_FLASH_TIME))
+            || self
+                .text
+                .as_ref()
+                .is_some_and(|(_, since)| running(*since, ACTION_TEXT_TIME))
+    }
+
+    /// Draws the lock flash, and the cleared lines while the board is
+    /// `clearing` them for `delay`, on the cells of the board.
+    pub fn apply(
+        &self,
+        cells: &mut [Vec<Option<Tile>>],
+        clearing: bool,
+        delay: Duration,
+        animation: ClearAnimation,
+        now: Instant,
+    ) {
+        if let Some((positions, since)) = self.lock {
+            let elapsed = now.duration_since(since);
+            if elapsed < LOCK_FLASH_TIME {
+                let amount = 0.6 * (1.0 - elapsed.as_secs_f32() / LOCK_FLASH_TIME.as_secs_f32());
+                for (x, y) in positions {
+                    if let Some(Some(tile)) = cells.get_mut(y).and_then(|row| row.get_mut(x + 1)) {
+                        tile.color = brighten(tile.color, amount);
+                    }
+                }
+            }
+        }
+
+        let Some((rows, since)) = self.clear.as_ref().filter(|_| clearing) else {
+            return;
+        };
+        let elapsed = now.duration_since(*since);
+        let progress = (elapsed.as_secs_f32() / delay.as_secs_f32().max(f32::EPSILON)).min(1.0);
+        for y in rows {
+            let Some(row) = cells.get_mut(*y) else {
+                continue;
+            };
+            // leave out the border on both sides
+            let width = row.len().saturating_sub(2);
+            for (x, cell) in row.iter_mut().enumerate().skip(1).take(width) {
+                let Some(tile) = cell else {
+                    continue;
+                };
+                match animation {
+                    ClearAnimation::Flash => {
+                        if (elapsed.as_millis() / CLEAR_FLASH_TIME.as_millis()).is_multiple_of(2) {
+                            tile.color = Color::White;
+                        }
+                    }
+                    ClearAnimation::Dissolve => {
+                        let from_middle =
+                            (2.0 * x as f32 - 1.0 - width as f32).abs() / width as f32;
+                        if from_middle < progress {
+                            *cell = None;
+                        } else {
+                            tile.color = brighten(tile.color, progress);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lines naming the last clear, and how far along they are from 0 to 1,
+    /// while they are shown.
+    pub fn text(&self, now: Instant) -> Option<(&[String], f32)> {
+        let (lines, since) = self.text.as_ref()?;
+        let elapsed = now.duration_since(*since);
+        (elapsed < ACTION_TEXT_TIME).then(|| {
+            (
+                lines.as_slice(),
+                elapsed.as_secs_f32() / ACTION_TEXT_TIME.as_secs_f32(),
+            )
+        })
+    }
+}
+
+/// Name of a clear of `count` lines.
+fn clear_name(count: u8, tspin: bool) -> String {
+    let name = match count {
+        1 => "SINGLE",
+        2 => "DOUBLE",
+        3 => "TRIPLE",
+        _ => "TETRIS",
+    };
+    if tspin {
+        format!("T-SPIN {name}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// A brighter color, named colors can't be mixed so they turn white.
+fn brighten(color: Color, amount: f32) -> Color {
+    match color {
+        Color::Rgb(..) => shade(color, amount),
+        _ if amount > 0.3 => Color::White,
+        _ => color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetromino::TetrominoShape;
+
+    #[test]
+    fn test_action_text() {
+        let now = Instant::now();
+        let mut animations = Animations::default();
+        animations.push(
+            &BoardEvent::LinesCleared {
+                rows: vec![20, 21],
+                count: 2,
+                tspin: true,
+                b2b: 3,
+                combo: 5,
+            },
+            now,
+        );
+        animations.push(&BoardEvent::PerfectClear, now);
+        let (lines, _) = animations.text(now).expect("no action text");
+        assert_eq!(
+            lines,
+            ["T-SPIN DOUBLE", "B2B x3", "COMBO 5", "PERFECT CLEAR"]
+        );
+        assert!(animations.text(now + ACTION_TEXT_TIME).is_none());
+    }
+
+    #[test]
+    fn test_dissolve() {
+        let now = Instant::now();
+        let mut animations = Animations::default();
+        animations.push(
+            &BoardEvent::LinesCleared {
+                rows: vec![0],
+                count: 1,
+                tspin: false,
+                b2b: 0,
+                combo: 0,
+            },
+            now,
+        );
+        let tile = Some(Tile::piece(Color::Rgb(0, 0, 255), TetrominoShape::J));
+        let mut cells = vec![vec![tile; 12]];
+        let delay = Duration::from_millis(300);
+        animations.apply(
+            &mut cells,
+            true,
+            delay,
+            ClearAnimation::Dissolve,
+            now + delay / 2,
+        );
+        // the middle is gone, the edges and the border are left
+        assert_eq!(cells[0].iter().filter(|cell| cell.is_none()).count(), 4);
+        assert!(cells[0][1].is_some() && cells[0][0] == tile);
+    }
+}
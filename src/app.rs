@@ -1,33 +1,1024 @@
-use crate::board::Board;
+use rand::{thread_rng, Rng};
+use std::time::{Duration, Instant};
+
+use crate::animation::Animations;
+use crate::board::{Action, Board, BoardSetup, FRAME};
+use crate::cli::PlayArgs;
+use crate::clock::Stopwatch;
+use crate::color::ColorDepth;
+use crate::config::{Config, ConfigSource};
+use crate::editor::Editor;
+use crate::fumen::{self, FumenPage};
+use crate::history::HistoryEntry;
+use crate::master::Master;
+use crate::menu::{cycle, Menu, MenuItem};
+use crate::mode::{GameMode, ULTRA_TIME};
+use crate::practice::{Practice, Snapshot};
+use crate::puzzle::{PuzzleMenu, PuzzlePack, PuzzleRun, SolvedPuzzles};
+use crate::replay::{Playback, RecordedAction, Replay};
+use crate::scores::{HighScores, ScoreEntry};
+use crate::settings::Settings;
+use crate::stats::{GameStats, GameSummary};
+use crate::tetromino::Direction;
+use crate::theme::Theme;
+use crate::tui::copy_to_clipboard;
+
+/// Options shown on the game over screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOverOption {
+    Retry,
+    Replay,
+    SaveReplay,
+    ExportStats,
+    Menu,
+    Quit,
+}
+impl GameOverOption {
+    pub const ALL: [GameOverOption; 6] = [
+        GameOverOption::Retry,
+        GameOverOption::Replay,
+        GameOverOption::SaveReplay,
+        GameOverOption::ExportStats,
+        GameOverOption::Menu,
+        GameOverOption::Quit,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GameOverOption::Retry => "retry",
+            GameOverOption::Replay => "view replay",
+            GameOverOption::SaveReplay => "save replay",
+            GameOverOption::ExportStats => "export stats",
+            GameOverOption::Menu => "main menu",
+            GameOverOption::Quit => "quit",
+        }
+    }
+}
+
+/// State of a finished game.
+#[derive(Debug)]
+pub struct GameOver {
+    /// index into [`GameOverOption::ALL`]
+    pub selected: usize,
+    /// was the goal of the mode reached, as opposed to topping out
+    pub finished: bool,
+    pub time: Duration,
+    pub personal_best: bool,
+    /// the personal best before this game
+    pub best: Option<ScoreEntry>,
+    /// has the top out animation finished
+    pub animation_done: bool,
+}
+
+/// What the application is currently showing.
+#[derive(Debug)]
+pub enum Screen {
+    Menu(Menu),
+    /// high scores of the given mode
+    HighScores(GameMode),
+    /// choosing a puzzle to play
+    Puzzles(PuzzleMenu),
+    Settings(Settings),
+    /// painting a board to play from
+    Editor(Editor),
+    Playing,
+    Paused,
+    GameOver(GameOver),
+    /// watching the replay of the game that ended with the given game over
+    Replay(Playback, GameOver),
+    Quit,
+}
+impl Default for Screen {
+    fn default() -> Self {
+        Screen::Menu(Menu::new())
+    }
+}
+
+/// How long a notice stays on screen.
+const NOTICE_TIME: Duration = Duration::from_secs(3);
 
 /// Application.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct App {
-    /// should the application exit?
-    pub should_quit: bool,
+    /// what is currently shown
+    pub screen: Screen,
+    /// configuration new games start with
+    pub config: Config,
+    /// colors of the config's theme
+    pub theme: Theme,
+    /// where the configuration is reloaded from
+    pub config_source: ConfigSource,
     /// game state
     pub board: Board,
-    /// is the game paused
-    pub paused: bool,
+    /// mode of the current game, or of the next one while in the menu
+    pub mode: GameMode,
+    pub starting_level: u8,
+    /// seed of every new game, random for each game if not set
+    pub seed: Option<u64>,
+    /// board every new game starts from instead of the mode's, if set
+    pub setup: Option<BoardSetup>,
+    /// in-game time of the current game
+    pub clock: Stopwatch,
+    /// actions of the current game
+    pub replay: Replay,
+    /// effects of what happened on the board
+    pub animations: Animations,
+    /// statistics of the current game
+    pub stats: GameStats,
+    /// puzzle of the current game, or of the last one played
+    pub puzzle: Option<PuzzleRun>,
+    /// placements that can be undone, in practice games
+    pub practice: Option<Practice>,
+    /// level, sections and grade, in master games
+    pub master: Option<Master>,
+    /// does the stats panel show every statistic
+    pub all_stats: bool,
+    /// high scores shown on the high scores screen, loaded when it's opened
+    pub high_scores: HighScores,
+    /// does the terminal report key releases, known from the first one. Held
+    /// directions auto shift only if it does, otherwise pieces move at the
+    /// terminal's key repeat rate
+    pub key_releases: bool,
+    /// in-game time of the last frame the game ran, see [`App::run_frames`]
+    frame_time: Duration,
+    /// frames the board of the current game has run, actions are recorded
+    /// with it so replays run the same frames between them
+    frames: u64,
+    /// short message shown at the bottom of the screen, and when it was set
+    notice: Option<(String, Instant)>,
+    /// colors the terminal supports, used unless the config sets them
+    detected_color_depth: ColorDepth,
 }
 
 impl App {
     /// Constructs a new instance of [`App`].
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(config_source: ConfigSource, config: Config) -> Self {
+        Self {
+            config_source,
+            screen: Screen::default(),
+            board: Board::new(&config, thread_rng().gen(), 1),
+            mode: GameMode::default(),
+            starting_level: 1,
+            seed: None,
+            setup: None,
+            clock: Stopwatch::default(),
+            replay: Replay::default(),
+            animations: Animations::default(),
+            stats: GameStats::default(),
+            puzzle: None,
+            practice: None,
+            master: None,
+            all_stats: false,
+            high_scores: HighScores::default(),
+            key_releases: false,
+            frame_time: Duration::ZERO,
+            frames: 0,
+            notice: None,
+            detected_color_depth: ColorDepth::detect(),
+            // the config was validated, which includes loading its theme
+            theme: Theme::from_config(&config).unwrap_or_default(),
+            config,
+        }
     }
 
-    /// Create a new instance of [`Board`].
+    /// Colors to draw with, from the config or detected from the terminal.
+    pub fn color_depth(&self) -> ColorDepth {
+        self.config.color_depth.unwrap_or(self.detected_color_depth)
+    }
+
+    /// Uses the mode, starting level, seed and starting board given on the command line.
+    pub fn apply_play_args(&mut self, play: &PlayArgs) -> Result<(), String> {
+        if let Some(mode) = play.mode {
+            self.mode = mode;
+        }
+        if let Some(level) = play.level {
+            self.starting_level = level.min(self.config.max_level());
+        }
+        self.seed = play.seed;
+        if let Some(setup) = &play.fumen {
+            setup
+                .validate(self.config.board_size)
+                .map_err(|problem| format!("the fumen can't be played, it {problem}"))?;
+        }
+        self.setup = play.fumen.clone();
+        Ok(())
+    }
+
+    /// should the application exit?
+    pub fn should_quit(&self) -> bool {
+        matches!(self.screen, Screen::Quit)
+    }
+
+    /// Starts a game of the selected mode, puzzles have to be picked first.
+    pub fn play(&mut self) {
+        if self.mode == GameMode::Puzzle && self.puzzle.is_none() {
+            self.open_puzzles();
+        } else {
+            self.reset();
+        }
+    }
+
+    /// Starts a new game with the selected mode and starting level.
     pub fn reset(&mut self) {
-        self.board = Board::new();
+        let seed = self.seed.unwrap_or_else(|| thread_rng().gen());
+        self.replay = Replay::new(self.config.clone(), seed, self.mode, self.starting_level);
+        let setup = match (self.mode, &mut self.puzzle) {
+            (GameMode::Puzzle, Some(run)) => {
+                *run = PuzzleRun::new(run.pack.clone(), run.puzzle.clone());
+                Some(run.puzzle.setup())
+            }
+            _ => self.setup.clone(),
+        };
+        if let Some(setup) = setup {
+            self.replay = std::mem::take(&mut self.replay).with_setup(setup);
+        }
+        self.board = self.replay.new_board();
+        self.animations = Animations::default();
+        self.stats = GameStats::new(self.board.config().board_size.0);
+        self.frame_time = Duration::ZERO;
+        self.frames = 0;
+        self.practice = (self.mode == GameMode::Practice).then(|| Practice::new(self.snapshot()));
+        self.master = self.new_master();
+        self.clock = Stopwatch::started();
+        self.screen = Screen::Playing;
     }
 
-    /// Set should_quit to true to quit the application.
+    /// The game as it is now, for practice games to go back to.
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            board: self.board.clone(),
+            stats: self.stats.clone(),
+            actions: self.replay.actions().len(),
+            frames: self.frames,
+        }
+    }
+
+    /// Goes back to before the last placed piece of a practice game, or
+    /// places the last undone one again.
+    pub fn undo_placement(&mut self, redo: bool) {
+        let (Screen::Playing, Some(practice)) = (&self.screen, &mut self.practice) else {
+            return;
+        };
+        let snapshot = if redo {
+            practice.redo(&mut self.replay)
+        } else {
+            practice.undo(&mut self.replay)
+        };
+        if let Some(snapshot) = snapshot {
+            self.board = snapshot.board;
+            self.board.reload_config(&self.config);
+            self.stats = snapshot.stats;
+            self.animations = Animations::default();
+            self.frames = snapshot.frames;
+        }
+    }
+
+    /// Quit the application.
     pub fn quit(&mut self) {
-        self.should_quit = true;
+        self.screen = Screen::Quit;
+    }
+
+    pub fn open_menu(&mut self) {
+        self.screen = Screen::Menu(Menu::new());
+    }
+
+    /// Lists the puzzles of every pack, starting after the last one solved.
+    pub fn open_puzzles(&mut self) {
+        let (packs, problems) = PuzzlePack::load_all(self.config.board_size);
+        if let Some(problem) = problems.first() {
+            self.set_notice(format!("puzzle pack not loaded, {problem}"));
+        }
+        let mut menu = PuzzleMenu::new(packs);
+        menu.select_from(self.puzzle.as_ref());
+        self.screen = Screen::Puzzles(menu);
+    }
+
+    pub fn select_puzzle(&mut self, down: bool) {
+        if let Screen::Puzzles(menu) = &mut self.screen {
+            menu.select(down);
+        }
+    }
+
+    /// Plays the selected puzzle.
+    pub fn start_puzzle(&mut self) {
+        let Screen::Puzzles(menu) = &self.screen else {
+            return;
+        };
+        if let Some(run) = menu.start() {
+            self.puzzle = Some(run);
+            self.reset();
+        }
+    }
+
+    /// Edits the board games start from, or an empty one.
+    pub fn open_editor(&mut self) {
+        self.screen = Screen::Editor(Editor::new(self.setup.as_ref(), self.config.board_size));
+    }
+
+    /// Starts games from the edited board, in marathon unless the mode plays
+    /// any board.
+    pub fn play_editor(&mut self) {
+        let Screen::Editor(editor) = &self.screen else {
+            return;
+        };
+        self.setup = Some(editor.setup());
+        if self.mode == GameMode::Puzzle || !self.mode.is_available() {
+            self.mode = GameMode::Marathon;
+        }
+        self.reset();
     }
 
     pub fn pause(&mut self, paused: bool) {
-        self.paused = paused;
+        match (&mut self.screen, paused) {
+            (Screen::Replay(playback, _), paused) => playback.pause(paused),
+            (Screen::Playing, true) => {
+                // keys let go while paused aren't seen, so none stay held
+                for direction in [Direction::Left, Direction::Right] {
+                    self.act(Action::MoveReleased(direction));
+                }
+                self.clock.stop();
+                self.screen = Screen::Paused;
+            }
+            (Screen::Paused, false) => {
+                self.clock.start();
+                self.screen = Screen::Playing;
+            }
+            _ => {}
+        }
+    }
+
+    /// Applies an action to the board and records it for the replay.
+    pub fn act(&mut self, action: Action) {
+        self.record_action(action, true);
+    }
+
+    /// Applies the action of a key that is held down, which doesn't count as
+    /// another key press.
+    pub fn act_held(&mut self, action: Action) {
+        self.record_action(action, false);
+    }
+
+    /// Records `action` for the replay with the frame it comes after, and applies it.
+    fn record_action(&mut self, action: Action, pressed: bool) {
+        if !matches!(self.screen, Screen::Playing) {
+            return;
+        }
+        if pressed {
+            self.stats.record_key(action);
+        }
+        self.replay
+            .record(self.clock.elapsed(), self.frames, action, pressed);
+        self.apply_action(action);
+    }
+
+    /// Runs the frames of the game that are due by the clock, in fixed steps
+    /// however often it is called: gravity pulls the piece, the lock delay
+    /// counts down and so do the line clear and entry delays. Replays run
+    /// the same frames from the frame recorded with each action.
+    pub fn run_frames(&mut self) {
+        while matches!(self.screen, Screen::Playing)
+            && self.frame_time + FRAME <= self.clock.elapsed()
+        {
+            self.frame_time += FRAME;
+            self.frames += 1;
+            self.apply_action(Action::Gravity);
+        }
+        self.check_time_limit();
+    }
+
+    /// Applies `action` to the board, ending the game once it is over.
+    fn apply_action(&mut self, action: Action) {
+        let result = self.board.apply(action);
+        self.handle_board_events();
+        // the last piece of a puzzle can solve it and run out of pieces at once
+        if self.goal_reached() {
+            self.end_game(true);
+        } else if result.is_err() {
+            self.end_game(false);
+        }
+    }
+
+    /// Whether the goal of the mode, or of the puzzle being played, has been reached.
+    fn goal_reached(&self) -> bool {
+        self.goal_reached_at(self.clock.elapsed())
+    }
+
+    /// Whether the goal had been reached `elapsed` into the game.
+    fn goal_reached_at(&self, elapsed: Duration) -> bool {
+        match (self.mode, &self.puzzle, &self.master) {
+            (GameMode::Puzzle, Some(run), _) => run.is_solved(),
+            (GameMode::Master, _, Some(master)) => master.is_finished(),
+            _ => self.mode.is_finished(&self.board, elapsed),
+        }
+    }
+
+    /// Progress of a new master game, if one is played.
+    fn new_master(&self) -> Option<Master> {
+        (self.mode == GameMode::Master).then(Master::new)
+    }
+
+    /// Score of the current game, master games score apart from the board.
+    pub fn score(&self) -> u128 {
+        match &self.master {
+            Some(master) => master.score().into(),
+            None => self.board.get_score(),
+        }
+    }
+
+    /// Hands everything that happened on the board since the last call to
+    /// the parts of the app that follow the game.
+    fn handle_board_events(&mut self) {
+        let now = Instant::now();
+        let elapsed = self.game_time();
+        let mut spawned = false;
+        for event in self.board.take_events() {
+            self.animations.push(&event, now);
+            self.stats.record(&event);
+            if let Some(run) = &mut self.puzzle {
+                run.record(&event);
+            }
+            if let Some(practice) = &mut self.practice {
+                spawned |= practice.record(&event);
+            }
+            if let Some(master) = &mut self.master {
+                if master.record(&event, elapsed) {
+                    self.board.set_rules(master.rules());
+                }
+            }
+        }
+        if spawned {
+            let snapshot = self.snapshot();
+            if let Some(practice) = &mut self.practice {
+                practice.start_piece(snapshot);
+            }
+        }
+    }
+
+    /// Plays every action of the replay on a fresh board at once, counting
+    /// them without animating them.
+    fn catch_up_replay(&mut self) {
+        self.board = self.replay.new_board();
+        self.stats = GameStats::new(self.board.config().board_size.0);
+        self.master = self.new_master();
+        for RecordedAction {
+            time,
+            action,
+            pressed,
+            ..
+        } in self.replay.steps()
+        {
+            if pressed {
+                self.stats.record_key(action);
+            }
+            let result = self.board.apply(action);
+            // master rules change with the level, so events are followed as they happen
+            for event in self.board.take_events() {
+                self.stats.record(&event);
+                if let Some(master) = &mut self.master {
+                    if master.record(&event, time) {
+                        self.board.set_rules(master.rules());
+                    }
+                }
+            }
+            if result.is_err() {
+                break;
+            }
+        }
+        self.animations = Animations::default();
+    }
+
+    /// Ends the game of timed modes once their time is up.
+    fn check_time_limit(&mut self) {
+        if matches!(self.screen, Screen::Playing) && self.goal_reached() {
+            self.end_game(true);
+        }
+    }
+
+    fn end_game(&mut self, finished: bool) {
+        self.clock.stop();
+        let time = match self.mode {
+            GameMode::Ultra => self.clock.elapsed().min(ULTRA_TIME),
+            _ => self.clock.elapsed(),
+        };
+        self.replay.finish(self.frames, time);
+
+        let (personal_best, best) = match (self.mode, &self.puzzle) {
+            // puzzles have no high scores, only whether they were solved
+            (GameMode::Puzzle, run) => {
+                if let Some(run) = run.as_ref().filter(|_| finished) {
+                    let mut solved = SolvedPuzzles::load();
+                    solved.insert(&run.pack, &run.puzzle.name);
+                    let _ = solved.save();
+                }
+                (false, None)
+            }
+            // scores with undone placements or without gravity don't compare to real games
+            (GameMode::Practice | GameMode::Zen, _) => (false, None),
+            _ => {
+                let mut high_scores = HighScores::load();
+                let best = high_scores.best(self.mode).cloned();
+                // games that are ranked by time only count if their goal was reached
+                let personal_best = (finished || !self.mode.ranks_by_time())
+                    && high_scores.submit(ScoreEntry {
+                        mode: self.mode,
+                        score: self.score(),
+                        level: self.board.get_level(),
+                        lines_cleared: self.board.get_lines_cleared(),
+                        time,
+                    });
+                let _ = high_scores.save();
+                (personal_best, best)
+            }
+        };
+        let _ = HistoryEntry::new(self.summary(time), finished).append();
+
+        self.screen = Screen::GameOver(GameOver {
+            selected: 0,
+            finished,
+            time,
+            personal_best,
+            best,
+            animation_done: false,
+        });
+    }
+
+    /// Everything about the current game, `time` being how long it took.
+    pub fn summary(&self, time: Duration) -> GameSummary {
+        GameSummary::new(
+            &self.stats,
+            self.mode,
+            self.replay.seed(),
+            self.score(),
+            self.board.get_level(),
+            time,
+        )
+    }
+
+    /// In-game time of the game being played or watched.
+    pub fn game_time(&self) -> Duration {
+        match &self.screen {
+            Screen::Replay(playback, _) => playback.elapsed(),
+            _ => self.clock.elapsed(),
+        }
+    }
+
+    /// Copies the board, the falling piece, the hold and the queue as a fumen.
+    pub fn copy_fumen(&mut self) {
+        let notice = match FumenPage::from_board(&self.board) {
+            Ok(page) => {
+                let text = fumen::encode(&[page]);
+                match copy_to_clipboard(&text) {
+                    Ok(()) => format!("copied {text}"),
+                    Err(error) => format!("failed to copy the fumen: {error}"),
+                }
+            }
+            Err(problem) => format!("can't copy as a fumen, {problem}"),
+        };
+        self.set_notice(notice);
+    }
+
+    /// Shows every statistic in the stats panel, or just the main ones.
+    pub fn toggle_all_stats(&mut self) {
+        self.all_stats = !self.all_stats;
+    }
+
+    /// Advances the top out animation by one row.
+    pub fn animate_game_over(&mut self) {
+        if let Screen::GameOver(game_over) = &mut self.screen {
+            game_over.animation_done = self.board.grey_out_row();
+        }
+    }
+
+    /// Moves the game over selection up or down, wrapping around.
+    pub fn select_game_over_option(&mut self, down: bool) {
+        if let Screen::GameOver(game_over) = &mut self.screen {
+            game_over.selected = cycle(game_over.selected, GameOverOption::ALL.len(), down);
+        }
+    }
+
+    pub fn confirm_game_over_option(&mut self) {
+        let Screen::GameOver(game_over) = &self.screen else {
+            return;
+        };
+        match GameOverOption::ALL[game_over.selected] {
+            GameOverOption::Retry => self.play(),
+            GameOverOption::Replay => self.watch_replay(),
+            GameOverOption::SaveReplay => match self.replay.save() {
+                Ok(path) => self.set_notice(format!("replay saved to {path}")),
+                Err(error) => self.set_notice(format!("failed to save replay: {error}")),
+            },
+            GameOverOption::ExportStats => match self.summary(game_over.time).export() {
+                Ok(path) => self.set_notice(format!("stats exported to {path}")),
+                Err(error) => self.set_notice(format!("failed to export stats: {error}")),
+            },
+            GameOverOption::Menu => self.open_menu(),
+            GameOverOption::Quit => self.quit(),
+        }
+    }
+
+    /// Watches a saved replay, ending up on its game over screen.
+    pub fn open_replay(&mut self, replay: Replay) {
+        // the puzzle of a saved replay isn't known, retrying picks one
+        self.puzzle = None;
+        self.practice = None;
+        self.mode = replay.mode();
+        self.starting_level = replay.starting_level();
+        self.replay = replay;
+        self.catch_up_replay();
+        let time = self.replay.duration();
+        self.screen = Screen::GameOver(GameOver {
+            selected: 0,
+            finished: self.goal_reached_at(time),
+            time,
+            personal_best: false,
+            best: None,
+            animation_done: false,
+        });
+        self.watch_replay();
+    }
+
+    /// Replays the last game from the beginning on a fresh board.
+    fn watch_replay(&mut self) {
+        if let Screen::GameOver(game_over) = std::mem::take(&mut self.screen) {
+            // the recorded rules, drawn the way the local config draws them
+            self.board = self.replay.new_board();
+            self.board.reload_config(&self.config);
+            self.master = self.new_master();
+            self.animations = Animations::default();
+            self.stats = GameStats::new(self.board.config().board_size.0);
+            self.screen = Screen::Replay(Playback::new(), game_over);
+        }
+    }
+
+    /// Applies every replay action that is due, returns to the game over
+    /// screen once the replay ends.
+    pub fn step_playback(&mut self) {
+        let Screen::Replay(playback, _) = &mut self.screen else {
+            return;
+        };
+        while let Some(RecordedAction {
+            action, pressed, ..
+        }) = playback.next_due_action(&self.replay)
+        {
+            // held keys only count once, like in the game
+            if pressed {
+                self.stats.record_key(action);
+            }
+            if self.board.apply(action).is_err() {
+                break;
+            }
+        }
+        let finished = playback.is_finished(&self.replay);
+        self.handle_board_events();
+        if finished {
+            self.stop_playback();
+        }
+    }
+
+    /// Skips to the end of the replay and goes back to the game over screen.
+    pub fn stop_playback(&mut self) {
+        let Screen::Replay(_, mut game_over) = std::mem::take(&mut self.screen) else {
+            return;
+        };
+        self.catch_up_replay();
+        self.board.reload_config(&self.config);
+        game_over.selected = 0;
+        game_over.animation_done = false;
+        self.screen = Screen::GameOver(game_over);
+    }
+
+    pub fn select_menu_item(&mut self, down: bool) {
+        if let Screen::Menu(menu) = &mut self.screen {
+            menu.select(down);
+        }
+    }
+
+    /// Changes the value of the selected menu item, for items that have one.
+    pub fn change_menu_value(&mut self, forward: bool) {
+        let Screen::Menu(menu) = &self.screen else {
+            return;
+        };
+        match menu.selected_item() {
+            MenuItem::Mode => self.mode = cycle_mode(self.mode, forward),
+            MenuItem::Level => {
+                let max_level = self.config.max_level() as usize;
+                self.starting_level =
+                    cycle(self.starting_level as usize - 1, max_level, forward) as u8 + 1;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn confirm_menu_item(&mut self) {
+        let Screen::Menu(menu) = &self.screen else {
+            return;
+        };
+        match menu.selected_item() {
+            MenuItem::Mode | MenuItem::Level => self.change_menu_value(true),
+            MenuItem::Play => match self.mode {
+                GameMode::Puzzle => self.open_puzzles(),
+                mode if mode.is_available() => self.reset(),
+                _ => {}
+            },
+            MenuItem::Editor => self.open_editor(),
+            MenuItem::HighScores => {
+                self.high_scores = HighScores::load();
+                self.screen = Screen::HighScores(self.mode);
+            }
+            MenuItem::Settings => {
+                self.screen = Screen::Settings(Settings::new(self.config.clone()))
+            }
+            MenuItem::Quit => self.quit(),
+        }
+    }
+
+    /// Shows the high scores of the next or previous mode.
+    pub fn cycle_high_scores_mode(&mut self, forward: bool) {
+        if let Screen::HighScores(mode) = &mut self.screen {
+            *mode = cycle_mode(*mode, forward);
+        }
+    }
+
+    /// Rereads the config file, keeping the current configuration if it can't be read.
+    pub fn reload_config(&mut self) {
+        match self.config_source.load() {
+            Ok((config, warnings)) => {
+                self.apply_config(config);
+                let notice = match warnings.first() {
+                    Some(warning) => format!("config reloaded, {warning}"),
+                    None => "config reloaded".to_string(),
+                };
+                self.set_notice(notice);
+            }
+            Err(error) => {
+                let more = match error.problems.len() {
+                    0 | 1 => String::new(),
+                    count => format!(" (and {} more)", count - 1),
+                };
+                self.set_notice(format!(
+                    "config not reloaded, {}{more}",
+                    error.problems.first().map_or("", String::as_str)
+                ));
+            }
+        }
+    }
+
+    /// Uses `config` from now on. Colors and gravity change right away, the
+    /// rest applies from the next game.
+    pub fn apply_config(&mut self, config: Config) {
+        match Theme::from_config(&config) {
+            Ok(theme) => self.theme = theme,
+            Err(problem) => self.set_notice(problem),
+        }
+        self.board.reload_config(&config);
+        self.starting_level = self.starting_level.min(config.max_level());
+        self.config = config;
+    }
+
+    /// Saves the configuration edited in the settings and applies it.
+    pub fn save_settings(&mut self) {
+        let Screen::Settings(settings) = &mut self.screen else {
+            return;
+        };
+        if settings.save(&self.config_source.path) {
+            let config = settings.config.clone();
+            self.apply_config(config);
+        }
+    }
+
+    pub fn set_notice(&mut self, notice: String) {
+        self.notice = Some((notice, Instant::now()));
+    }
+
+    /// The last notice, if it was set recently.
+    pub fn notice(&self) -> Option<&str> {
+        match &self.notice {
+            Some((notice, since)) if since.elapsed() < NOTICE_TIME => Some(notice),
+            _ => None,
+        }
+    }
+
+    /// Shortest time between two draws of the screen.
+    pub fn render_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.config.render_rate
+    }
+
+    /// How long to wait between updates, a frame of the game while playing.
+    pub fn tick_delay(&self) -> Duration {
+        match self.screen {
+            Screen::Playing => FRAME,
+            Screen::Replay(..) => Duration::from_millis(10),
+            Screen::GameOver(_) => Duration::from_millis(40),
+            _ => Duration::from_millis(100),
+        }
+    }
+}
+
+fn cycle_mode(mode: GameMode, forward: bool) -> GameMode {
+    let index = GameMode::ALL.iter().position(|m| *m == mode).unwrap_or(0);
+    GameMode::ALL[cycle(index, GameMode::ALL.len(), forward)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Cell;
+    use crate::tetromino::{Tetromino, TetrominoShape};
+    use crate::update::{key_event_update, update};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    /// Games write their scores and history next to the config file, the
+    /// tests keep them in a directory of their own and run one at a time.
+    static CONFIG_DIR: Mutex<()> = Mutex::new(());
+
+    /// An app on the menu with the default config and an empty config directory.
+    fn test_app() -> (App, MutexGuard<'static, ()>) {
+        let guard = CONFIG_DIR.lock().unwrap_or_else(PoisonError::into_inner);
+        let dir = std::env::temp_dir().join(format!("termtris-app-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::env::set_var("XDG_CONFIG_HOME", &dir);
+        (App::new(ConfigSource::default(), Config::default()), guard)
+    }
+
+    fn press(app: &mut App, code: KeyCode) {
+        key_event_update(app, KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    /// Hard drops pieces onto the middle of the board until the stack reaches the top.
+    fn top_out(app: &mut App) {
+        for _ in 0..1000 {
+            press(app, KeyCode::Char(' '));
+            if !matches!(app.screen, Screen::Playing) {
+                return;
+            }
+            update(app);
+        }
+    }
+
+    /// Runs the top out animation to its end.
+    fn finish_animation(app: &mut App) {
+        while matches!(&app.screen, Screen::GameOver(game_over) if !game_over.animation_done) {
+            update(app);
+        }
+    }
+
+    fn press_ctrl(app: &mut App, c: char) {
+        key_event_update(app, KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+    }
+
+    /// What a player sees of a board.
+    #[derive(Debug, PartialEq)]
+    struct BoardState {
+        grid: Vec<Vec<Cell>>,
+        hold: Option<TetrominoShape>,
+        score: u128,
+        piece: Option<TetrominoShape>,
+        queue: Vec<Option<TetrominoShape>>,
+    }
+
+    fn board_state(app: &App) -> BoardState {
+        BoardState {
+            grid: app.board.grid().to_vec(),
+            hold: app.board.get_held_tetromino(),
+            score: app.board.get_score(),
+            piece: app.board.current_piece().map(Tetromino::get_shape),
+            queue: (1..=7).map(|n| app.board.calc_next_piece(n)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_top_out_game_over() {
+        let (mut app, _guard) = test_app();
+        press(&mut app, KeyCode::Enter);
+        top_out(&mut app);
+        let Screen::GameOver(game_over) = &app.screen else {
+            panic!("topping out didn't end the game");
+        };
+        assert!(!game_over.finished);
+        assert!(!game_over.animation_done);
+
+        // the animation greys out a row of the board a frame
+        let height = app.board.config().board_size.1;
+        for _ in 1..height {
+            update(&mut app);
+        }
+        assert!(matches!(&app.screen, Screen::GameOver(game_over) if !game_over.animation_done));
+        update(&mut app);
+        assert!(matches!(&app.screen, Screen::GameOver(game_over) if game_over.animation_done));
+    }
+
+    #[test]
+    fn test_personal_best() {
+        let (mut app, _guard) = test_app();
+        let best = ScoreEntry {
+            mode: GameMode::Marathon,
+            score: 1_000_000,
+            level: 20,
+            lines_cleared: 200,
+            time: Duration::from_secs(600),
+        };
+        let mut high_scores = HighScores::default();
+        high_scores.submit(best.clone());
+        high_scores.save().expect("failed to save high scores");
+
+        press(&mut app, KeyCode::Enter);
+        top_out(&mut app);
+        let Screen::GameOver(game_over) = &app.screen else {
+            panic!("topping out didn't end the game");
+        };
+        assert!(!game_over.personal_best);
+        assert_eq!(game_over.best, Some(best));
+
+        // the first game of a mode is a personal best
+        app.mode = GameMode::Ultra;
+        app.play();
+        top_out(&mut app);
+        let Screen::GameOver(game_over) = &app.screen else {
+            panic!("topping out didn't end the game");
+        };
+        assert!(game_over.personal_best);
+        assert_eq!(game_over.best, None);
+        assert_eq!(HighScores::load().entries(GameMode::Ultra).count(), 1);
+    }
+
+    #[test]
+    fn test_practice_undo_redo() {
+        let (mut app, _guard) = test_app();
+        app.mode = GameMode::Practice;
+        app.play();
+        let mut states = vec![board_state(&app)];
+        // hold and place, move and place, place
+        for keys in [
+            &[KeyCode::Char('c'), KeyCode::Char(' ')][..],
+            &[KeyCode::Left, KeyCode::Left, KeyCode::Char(' ')],
+            &[KeyCode::Right, KeyCode::Char(' ')],
+        ] {
+            for key in keys {
+                press(&mut app, *key);
+            }
+            states.push(board_state(&app));
+        }
+        let actions = app.replay.actions().len();
+
+        press_ctrl(&mut app, 'z');
+        press_ctrl(&mut app, 'z');
+        assert_eq!(board_state(&app), states[1]);
+        assert_eq!(app.stats.pieces, 1);
+
+        // redo places the same pieces where they were
+        press_ctrl(&mut app, 'y');
+        assert_eq!(board_state(&app), states[2]);
+        press_ctrl(&mut app, 'y');
+        assert_eq!(board_state(&app), states[3]);
+        assert_eq!(app.replay.actions().len(), actions);
+        assert_eq!(app.stats.pieces, 3);
+    }
+
+    #[test]
+    fn test_screens() {
+        let (mut app, _guard) = test_app();
+        assert!(matches!(app.screen, Screen::Menu(_)));
+
+        // sprint from level 3
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.screen, Screen::Playing));
+        assert_eq!(app.mode, GameMode::Sprint);
+        assert_eq!(app.board.get_level(), 3);
+
+        top_out(&mut app);
+        assert!(matches!(app.screen, Screen::GameOver(_)));
+        // keys held from the game are ignored until the animation is over
+        press(&mut app, KeyCode::Char('r'));
+        assert!(matches!(app.screen, Screen::GameOver(_)));
+        finish_animation(&mut app);
+
+        // retry starts a new board of the same mode and level
+        press(&mut app, KeyCode::Char('r'));
+        assert!(matches!(app.screen, Screen::Playing));
+        assert_eq!(app.mode, GameMode::Sprint);
+        assert_eq!(app.board.get_level(), 3);
+        assert!(app.board.grid().iter().flatten().all(Cell::is_empty));
+        assert_eq!(app.stats.pieces, 0);
+
+        top_out(&mut app);
+        finish_animation(&mut app);
+        press(&mut app, KeyCode::Esc);
+        assert!(matches!(app.screen, Screen::Menu(_)));
+
+        // esc leaves a game for the menu too, and the menu quits
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.screen, Screen::Playing));
+        press(&mut app, KeyCode::Esc);
+        assert!(matches!(app.screen, Screen::Menu(_)));
+        press(&mut app, KeyCode::Esc);
+        assert!(app.should_quit());
     }
 }
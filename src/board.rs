@@ -1,11 +1,15 @@
-use crate::config::{BagType, CONFIG};
+use crate::config::{BagType, Config};
+use crate::grid::Tile;
 use crate::tetromino::{Direction, Tetromino, TetrominoShape};
-use rand::rngs::ThreadRng;
-use rand::{seq::SliceRandom, thread_rng};
-use ratatui::widgets::canvas::{Painter, Shape};
+use crate::theme::Theme;
+use rand::rngs::StdRng;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
 use std::num::TryFromIntError;
+use std::time::Duration;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum DifficultClear {
     Tetris,
     TSpinDouble,
@@ -16,6 +20,8 @@ enum DifficultClear {
 pub enum TetrominoPositionError {
     NegativePosition,
     Collision,
+    /// the fixed queue of a board set up by hand ran out
+    OutOfPieces,
 }
 impl From<TryFromIntError> for TetrominoPositionError {
     fn from(_: TryFromIntError) -> Self {
@@ -23,13 +29,211 @@ impl From<TryFromIntError> for TetrominoPositionError {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// A single input to the board, recorded so games can be replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Gravity,
+    /// moves the piece one cell, for terminals that don't report key releases
+    Move(Direction),
+    /// the key of a direction was pressed, moves the piece one cell and
+    /// auto shifts it once DAS is charged, until the key is let go
+    MovePressed(Direction),
+    /// the key of a direction was let go
+    MoveReleased(Direction),
+    Rotate(bool), // clockwise
+    Hold,
+    HardDrop,
+    SoftDrop(bool), // activate
+    /// drops the piece onto the stack without locking it
+    SonicDrop,
+    /// the line clear delay is over
+    FinishLineClear,
+    /// the entry delay is over and the next piece comes in
+    FinishEntry,
+}
+
+/// Something that happened on the board. The board queues these as they
+/// happen, whoever drives it takes them with [`Board::take_events`] and hands
+/// them to everything that shows or counts them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardEvent {
+    PieceSpawned {
+        shape: TetrominoShape,
+    },
+    PieceLocked {
+        shape: TetrominoShape,
+        cells: [(usize, usize); 4],
+    },
+    LinesCleared {
+        /// the cleared rows, top to bottom
+        rows: Vec<usize>,
+        count: u8,
+        tspin: bool,
+        /// back to back clears in a row before this one, 0 if it isn't one
+        b2b: u32,
+        /// clears in a row before this one
+        combo: u8,
+    },
+    PerfectClear,
+    /// the current piece was put in hold, the piece that replaces it spawns after this
+    Hold {
+        shape: TetrominoShape,
+    },
+    LevelUp {
+        level: u8,
+    },
+    /// a piece spawned where it overlaps the stack, the game is over
+    TopOut,
+    GarbageReceived {
+        rows: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Cell {
     Empty,
     Occupied(TetrominoShape),
+    Garbage,
 }
+impl Cell {
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Cell::Empty)
+    }
 
-#[derive(Debug)]
+    /// Reads a cell of a board written as text: `.` or a space for empty
+    /// cells, `#` for garbage and the letter of a shape for its cells.
+    pub fn from_char(c: char) -> Option<Cell> {
+        match c.to_ascii_uppercase() {
+            '.' | ' ' => Some(Cell::Empty),
+            '#' => Some(Cell::Garbage),
+            letter => TetrominoShape::ALL
+                .into_iter()
+                .find(|shape| format!("{shape:?}").starts_with(letter))
+                .map(Cell::Occupied),
+        }
+    }
+
+    /// The character [`Cell::from_char`] reads as this cell.
+    pub fn to_char(&self) -> char {
+        match self {
+            Cell::Empty => '.',
+            Cell::Garbage => '#',
+            Cell::Occupied(shape) => format!("{shape:?}").chars().next().unwrap_or('#'),
+        }
+    }
+}
+
+/// Frames of the game every second, gravity and lock delay count frames.
+pub const FRAMES_PER_SECOND: u64 = 60;
+/// Length of a frame of the game.
+pub const FRAME: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND);
+/// Gravity of a row every frame, G units are counted in 1/256 rows like TGM does.
+pub const ONE_G: u32 = 256;
+/// Gravity that puts pieces on the stack as soon as they spawn.
+pub const TWENTY_G: u32 = 20 * ONE_G;
+/// Milliseconds of `frames` frames.
+pub fn frames_to_millis(frames: u64) -> u64 {
+    frames * 1000 / FRAMES_PER_SECOND
+}
+/// Frames it takes for `millis` milliseconds to pass, counting a started frame.
+pub fn millis_to_frames(millis: u64) -> u64 {
+    (millis * FRAMES_PER_SECOND).div_ceil(1000)
+}
+
+/// How pieces fall by themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gravity {
+    /// with the gravity delay of the level
+    Levels,
+    /// one row every this many milliseconds, whatever the level
+    Fixed(u64),
+    /// this many 1/256 rows every frame, see [`ONE_G`]
+    PerFrame(u32),
+    /// pieces only fall while soft dropped
+    Off,
+}
+
+impl Gravity {
+    /// Gravity of `g` rows every frame.
+    pub fn from_g(g: f32) -> Gravity {
+        Gravity::PerFrame((g * ONE_G as f32).round() as u32)
+    }
+}
+
+/// Rules of the board that the game mode decides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardRules {
+    pub gravity: Gravity,
+    /// topping out empties the board instead of ending the game
+    pub top_out_clears: bool,
+    /// do clears and drops add to the score
+    pub scoring: bool,
+    /// pieces locked without clearing a line before a row of garbage rises, 0 for none
+    pub garbage_interval: u32,
+    /// frames a piece rests on the stack before it locks, the configured
+    /// lock delay if not set
+    pub lock_delay: Option<u8>,
+    /// milliseconds between a lock and the next piece (ARE), the configured
+    /// entry delay if not set
+    pub entry_delay: Option<u64>,
+    /// milliseconds between the end of a line clear and the next piece (line
+    /// ARE), the configured line entry delay if not set
+    pub line_entry_delay: Option<u64>,
+}
+
+impl Default for BoardRules {
+    fn default() -> Self {
+        BoardRules {
+            gravity: Gravity::Levels,
+            top_out_clears: false,
+            scoring: true,
+            garbage_interval: 0,
+            lock_delay: None,
+            entry_delay: None,
+            line_entry_delay: None,
+        }
+    }
+}
+
+/// A board set up by hand, for puzzles.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoardSetup {
+    /// rows of the stack as text, see [`Cell::from_char`], the last one is the bottom row
+    pub rows: Vec<String>,
+    /// pieces in the order they come, the first one starts falling right away
+    pub queue: Vec<TetrominoShape>,
+    pub hold: Option<TetrominoShape>,
+    /// do random bags follow the queue, or does the game end when it runs out
+    pub then_random: bool,
+}
+
+impl BoardSetup {
+    /// Checks that the setup can be played on a board of `board_size`.
+    pub fn validate(&self, (width, height): (usize, usize)) -> Result<(), String> {
+        if self.rows.len() > height {
+            return Err(format!(
+                "has {} rows, the board has {height}",
+                self.rows.len()
+            ));
+        }
+        for row in &self.rows {
+            if row.chars().count() > width {
+                return Err(format!("row `{row}` is wider than the board ({width})"));
+            }
+            if let Some(c) = row.chars().find(|c| Cell::from_char(*c).is_none()) {
+                return Err(format!(
+                    "`{c}` in row `{row}` is not `.`, `#` or the letter of a shape"
+                ));
+            }
+        }
+        if self.queue.is_empty() && !self.then_random {
+            return Err("has no pieces in its queue".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Board {
     grid: Vec<Vec<Cell>>,
     bag: Vec<TetrominoShape>,
@@ -41,17 +245,281 @@ pub struct Board {
     last_rotation_check: Option<usize>, // last rotation check index, if there was any rotation
     last_difficult_clear: Option<DifficultClear>,
     combo_count: u8,
+    back_to_back: u32,
     score: u128,
     lines_cleared: u128,
     level: u8,
-    pub tick_delay: u64,
+    rng: StdRng,
+    /// the pieces end with the bag instead of being followed by new ones
+    fixed_queue: bool,
+    rules: BoardRules,
+    /// pieces locked without clearing a line since the last garbage row
+    pieces_since_garbage: u32,
+    soft_dropping: bool,
+    /// part of a row the piece has fallen, see [`Board::gravity_rate`]
+    gravity_progress: u64,
+    /// is the next piece waiting for the entry delay to end
+    entering: bool,
+    /// frames left until the line clear or entry delay ends
+    delay_frames: u64,
+    /// direction whose key is down, see [`Action::MovePressed`]
+    shift_direction: Option<Direction>,
+    /// frames the direction has been held, DAS is charged once they reach
+    /// `das_frames`, they go on counting while there is no piece
+    shift_frames: u64,
+    greyed_rows: usize, // rows greyed out by the top out animation, counted from the bottom
+    clearing_rows: Vec<usize>, // full rows left on the board for the line clear delay
+    events: Vec<BoardEvent>,
+    config: Config,
 }
 impl Board {
-    pub fn new() -> Board {
-        Board::default()
+    /// Constructs a board whose piece sequence is fully determined by `seed`.
+    pub fn new(config: &Config, seed: u64, starting_level: u8) -> Board {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let starting_bag = new_bag(&config.bag_type, &mut rng);
+        let next_bag = new_bag(&config.bag_type, &mut rng);
+        Board {
+            grid: vec![vec![Cell::Empty; config.board_size.0]; config.board_size.1],
+            bag: starting_bag.clone(),
+            bag_index: 0,
+            next_bag,
+            current_tetromino: Tetromino::new(
+                starting_bag[0],
+                config.board_size.0,
+                config.lock_delay_frames,
+            ),
+            held_tetromino: None,
+            already_held: false,
+            last_rotation_check: None, // last rotation check index, if there was any rotation
+            last_difficult_clear: None,
+            combo_count: 0,
+            back_to_back: 0,
+            score: 0,
+            lines_cleared: 0,
+            level: starting_level,
+            rng,
+            fixed_queue: false,
+            rules: BoardRules::default(),
+            pieces_since_garbage: 0,
+            soft_dropping: false,
+            gravity_progress: 0,
+            entering: false,
+            delay_frames: 0,
+            shift_direction: None,
+            shift_frames: 0,
+            greyed_rows: 0,
+            clearing_rows: Vec::new(),
+            events: vec![BoardEvent::PieceSpawned {
+                shape: starting_bag[0],
+            }],
+            config: config.clone(),
+        }
+    }
+
+    /// Constructs a board with the stack, queue and hold of `setup`, any
+    /// random pieces after the queue are determined by `seed`. The setup
+    /// must be valid for the board size of `config`.
+    pub fn from_setup(config: &Config, seed: u64, setup: &BoardSetup) -> Board {
+        let mut board = Board::new(config, seed, 1);
+        let (width, height) = config.board_size;
+        let top = height - setup.rows.len().min(height);
+        for (y, row) in setup.rows.iter().enumerate() {
+            for (x, c) in row.chars().take(width).enumerate() {
+                board.grid[top + y][x] = Cell::from_char(c).unwrap_or(Cell::Empty);
+            }
+        }
+        board.held_tetromino = setup.hold;
+        if !setup.queue.is_empty() {
+            board.next_bag = if setup.then_random {
+                board.bag.clone()
+            } else {
+                Vec::new()
+            };
+            board.bag = setup.queue.clone();
+            board.fixed_queue = !setup.then_random;
+            board.current_tetromino = Tetromino::new(board.bag[0], width, config.lock_delay_frames);
+            board.events = vec![BoardEvent::PieceSpawned {
+                shape: board.bag[0],
+            }];
+        }
+        board
+    }
+
+    /// Applies a changed configuration to the running game. Only how the
+    /// game looks changes, everything that changes how it plays out is kept
+    /// until the next game to keep replays deterministic, so boards of replays
+    /// keep the recorded rules too.
+    pub fn reload_config(&mut self, config: &Config) {
+        self.config = Config {
+            board_size: self.config.board_size,
+            bag_type: self.config.bag_type,
+            lock_delay_frames: self.config.lock_delay_frames,
+            line_clear_delay: self.config.line_clear_delay,
+            entry_delay: self.config.entry_delay,
+            line_entry_delay: self.config.line_entry_delay,
+            soft_drop_factor: self.config.soft_drop_factor,
+            das_frames: self.config.das_frames,
+            arr_frames: self.config.arr_frames,
+            tick_delay: self.config.tick_delay.clone(),
+            gravity: self.config.gravity,
+            zen_tick_delay: self.config.zen_tick_delay,
+            zen_scoring: self.config.zen_scoring,
+            zen_garbage: self.config.zen_garbage,
+            number_of_previews: config.number_of_previews.min(self.bag.len()),
+            ..config.clone()
+        };
+    }
+
+    /// Changes the rules of the board, before the game starts or as the
+    /// mode makes it harder.
+    pub fn set_rules(&mut self, rules: BoardRules) {
+        self.rules = rules;
+        self.current_tetromino.set_lock_delay(self.lock_delay());
+        let _ = self.apply_instant_gravity();
+    }
+
+    pub fn rules(&self) -> BoardRules {
+        self.rules
+    }
+
+    /// Rows the piece falls every frame as a fraction, the progress a frame
+    /// adds and the progress of a row. Soft dropping multiplies it by the
+    /// soft drop factor, pieces without gravity soft drop from the speed of
+    /// the level.
+    fn gravity_rate(&self) -> (u64, u64) {
+        // a row every `delay` milliseconds is 1000 / (60 * delay) rows a frame
+        let every = |delay: u64| (1000, delay * FRAMES_PER_SECOND);
+        let (frame, row) = match self.rules.gravity {
+            Gravity::Levels | Gravity::Off => every(self.config.tick_delay_for_level(self.level)),
+            Gravity::Fixed(delay) => every(delay),
+            Gravity::PerFrame(gravity) => (u64::from(gravity), u64::from(ONE_G)),
+        };
+        match (self.soft_dropping, self.config.soft_drop_factor) {
+            // soft dropping in G is at least 1G, as in TGM
+            (true, Some(factor)) if matches!(self.rules.gravity, Gravity::PerFrame(_)) => {
+                ((frame * u64::from(factor)).max(row), row)
+            }
+            (true, Some(factor)) => (frame * u64::from(factor), row),
+            _ => (frame, row),
+        }
+    }
+
+    fn lock_delay(&self) -> u8 {
+        self.rules
+            .lock_delay
+            .unwrap_or(self.config.lock_delay_frames)
+    }
+
+    fn add_score(&mut self, points: u128) {
+        if self.rules.scoring {
+            self.score += points;
+        }
+    }
+
+    /// The configuration the board is played with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn apply(&mut self, action: Action) -> Result<(), TetrominoPositionError> {
+        match action {
+            Action::MovePressed(direction) => {
+                self.shift_direction = Some(direction);
+                self.shift_frames = 0;
+            }
+            Action::MoveReleased(direction) if self.shift_direction == Some(direction) => {
+                self.shift_direction = None;
+                self.shift_frames = 0;
+            }
+            // DAS charges while there is no piece too
+            Action::Gravity if self.shift_direction.is_some() => self.shift_frames += 1,
+            _ => {}
+        }
+        // there is no piece to control until the cleared lines are removed
+        // and the next piece comes in
+        if !self.has_piece() {
+            match action {
+                Action::Gravity => self.count_down_delay()?,
+                Action::SoftDrop(activate) => self.soft_drop(activate)?,
+                Action::FinishLineClear => self.finish_line_clear()?,
+                Action::FinishEntry => self.finish_entry()?,
+                _ => {}
+            }
+            return Ok(());
+        }
+        match action {
+            Action::Gravity => {
+                self.auto_shift();
+                self.gravity()?
+            }
+            Action::Move(direction) | Action::MovePressed(direction) => {
+                self.move_current_piece(direction)
+            }
+            Action::Rotate(clockwise) => self.rotate_current_piece(clockwise),
+            Action::Hold => self.hold()?,
+            Action::HardDrop => self.hard_drop()?,
+            Action::SoftDrop(activate) => self.soft_drop(activate)?,
+            Action::SonicDrop => {
+                self.fall_to_stack()?;
+            }
+            Action::MoveReleased(_) | Action::FinishLineClear | Action::FinishEntry => {}
+        }
+        Ok(())
+    }
+
+    /// Moves the piece towards the direction held down once DAS is charged,
+    /// a cell every `arr_frames` frames or to the wall if that is 0.
+    fn auto_shift(&mut self) {
+        let Some(direction) = self.shift_direction else {
+            return;
+        };
+        let das = u64::from(self.config.das_frames);
+        let arr = u64::from(self.config.arr_frames);
+        if self.shift_frames < das {
+            return;
+        }
+        if arr == 0 {
+            self.shift_to_wall(direction);
+        } else if (self.shift_frames - das).is_multiple_of(arr) {
+            self.move_current_piece(direction);
+        }
+    }
+
+    /// Moves the piece as far towards `direction` as it goes.
+    fn shift_to_wall(&mut self, direction: Direction) {
+        for _ in 0..self.config.board_size.0 {
+            self.move_current_piece(direction);
+        }
+    }
+
+    /// Runs a frame: moves the piece down when gravity or soft dropping
+    /// pulls it, and counts down the lock delay of a piece on the stack.
+    fn gravity(&mut self) -> Result<(), TetrominoPositionError> {
+        if self.rules.gravity == Gravity::Off && !self.soft_dropping {
+            return Ok(());
+        }
+        self.update()
+    }
+
+    /// Rows the piece falls in this frame, gravity of less than a row a
+    /// frame adds up over the frames.
+    fn rows_to_fall(&mut self) -> usize {
+        // an infinite soft drop factor keeps the piece on the stack
+        if self.soft_dropping && self.config.soft_drop_factor.is_none() {
+            return usize::MAX;
+        }
+        let (frame, row) = self.gravity_rate();
+        self.gravity_progress += frame;
+        let rows = self.gravity_progress / row;
+        self.gravity_progress %= row;
+        rows as usize
     }
 
     pub fn update(&mut self) -> Result<(), TetrominoPositionError> {
+        if !self.has_piece() {
+            return Ok(());
+        }
+        let rows = self.rows_to_fall();
         match self.current_tetromino.calc_horizontal_move((0, 1)) {
             Ok(full_position) => {
                 if self.check_collision(full_position) {
@@ -63,11 +531,14 @@ impl Board {
                 self.piece_on_ground()?;
             }
         }
-        self.current_tetromino.update();
+        let rows = rows.min(self.calc_relative_height()?);
+        if rows == 0 {
+            return Ok(());
+        }
+        self.current_tetromino.fall(rows);
 
-        if self.tick_delay != CONFIG.tick_delay[&self.level] {
-            // soft dropping
-            self.score += 1;
+        if self.soft_dropping {
+            self.add_score(rows as u128);
         }
 
         // reset the last rotation check
@@ -76,25 +547,36 @@ impl Board {
         Ok(())
     }
 
-    fn clear_lines(&mut self) {
-        let mut lines_cleared = 0;
-        for y in 0..CONFIG.board_size.1 {
-            if self.grid[y]
-                .iter()
-                .all(|cell| matches!(cell, Cell::Occupied(_)))
-            {
-                // clear line
-                self.grid[y] = vec![Cell::Empty; CONFIG.board_size.0];
-
-                // move all lines above down
-                for y_to_move in (1..y + 1).rev() {
-                    self.grid[y_to_move] = self.grid[y_to_move - 1].clone();
-                }
+    /// Moves the piece straight down onto the stack, returns the rows it fell.
+    fn fall_to_stack(&mut self) -> Result<usize, TetrominoPositionError> {
+        let height = self.calc_relative_height()?;
+        if height > 0 {
+            self.current_tetromino.fall(height);
+            self.last_rotation_check = None;
+        }
+        Ok(height)
+    }
 
-                // update lines cleared counter
-                lines_cleared += 1;
+    /// Keeps the piece on the stack under 20G, wherever it moves.
+    fn apply_instant_gravity(&mut self) -> Result<(), TetrominoPositionError> {
+        match self.rules.gravity {
+            Gravity::PerFrame(gravity) if gravity >= TWENTY_G && self.has_piece() => {
+                self.fall_to_stack().map(|_| ())
             }
+            _ => Ok(()),
         }
+    }
+
+    /// Rows that are completely filled, top to bottom.
+    fn full_rows(&self) -> Vec<usize> {
+        (0..self.config.board_size.1)
+            .filter(|y| self.grid[*y].iter().all(|cell| !cell.is_empty()))
+            .collect()
+    }
+
+    /// Scores the clear of `rows` by the piece that was just locked.
+    fn score_clear(&mut self, rows: &[usize]) {
+        let lines_cleared = rows.len();
 
         // update score
         let mut difficult_clear = None;
@@ -127,57 +609,136 @@ impl Board {
                     )
                 }
             };
+        if lines_cleared == 0 {
+            // the combo ends with the first piece that doesn't clear a line
+            self.combo_count = 0;
+            return;
+        }
         if difficult_clear.is_some() && difficult_clear == self.last_difficult_clear {
             // back to back
             additional_score = (additional_score as f32 * 1.5) as u128;
+            self.back_to_back += 1;
+        } else {
+            self.back_to_back = 0;
         }
-        if additional_score > 0 {
-            // lines were cleared
-            // combo
-            additional_score += self.combo_count as u128 * 50 * self.level as u128;
-            self.combo_count += 1;
+        // T-spin singles score like any single, but they are still T-spins
+        let tspin = self.current_tetromino.get_shape() == TetrominoShape::T
+            && self.last_rotation_check.is_some();
+        self.events.push(BoardEvent::LinesCleared {
+            rows: rows.to_vec(),
+            count: lines_cleared as u8,
+            tspin,
+            b2b: self.back_to_back,
+            combo: self.combo_count,
+        });
 
-            // set the last difficult_clear to the current
-            self.last_difficult_clear = difficult_clear;
-        }
-        if self.grid[CONFIG.board_size.1 - 1]
+        // combo
+        additional_score += self.combo_count as u128 * 50 * self.level as u128;
+        self.combo_count = self.combo_count.saturating_add(1);
+
+        // set the last difficult_clear to the current
+        self.last_difficult_clear = difficult_clear;
+
+        let perfect_clear = self
+            .grid
             .iter()
-            .all(|cell| matches!(cell, Cell::Empty))
-        {
-            // perfect clear
+            .enumerate()
+            .all(|(y, row)| rows.contains(&y) || row.iter().all(|cell| cell.is_empty()));
+        if perfect_clear {
             additional_score += 5_000;
+            self.events.push(BoardEvent::PerfectClear);
         }
-        self.score += additional_score;
+        self.add_score(additional_score);
 
         // update lines cleared count
-        self.lines_cleared += lines_cleared;
+        self.lines_cleared += lines_cleared as u128;
 
         // update level
         if self.lines_cleared >= self.level as u128 * 10 + 10 {
             self.level += 1;
-            self.tick_delay = CONFIG.tick_delay[&self.level];
+            self.events.push(BoardEvent::LevelUp { level: self.level });
+        }
+    }
+
+    /// Removes `rows` and moves everything above them down.
+    fn remove_rows(&mut self, rows: &[usize]) {
+        for y in rows {
+            self.grid.remove(*y);
+            self.grid
+                .insert(0, vec![Cell::Empty; self.config.board_size.0]);
+        }
+    }
+
+    /// Removes the rows being cleared once the line clear delay is over, and
+    /// spawns the next piece.
+    pub fn finish_line_clear(&mut self) -> Result<(), TetrominoPositionError> {
+        if self.clearing_rows.is_empty() {
+            return Ok(());
+        }
+        let rows = std::mem::take(&mut self.clearing_rows);
+        self.remove_rows(&rows);
+        self.spawn_after_lock(true)
+    }
+
+    /// Brings in the next piece once the entry delay is over.
+    pub fn finish_entry(&mut self) -> Result<(), TetrominoPositionError> {
+        if !std::mem::take(&mut self.entering) {
+            return Ok(());
+        }
+        self.spawn_entering_piece()
+    }
+
+    /// Runs a frame of the line clear or entry delay, ending it on its last frame.
+    fn count_down_delay(&mut self) -> Result<(), TetrominoPositionError> {
+        self.delay_frames = self.delay_frames.saturating_sub(1);
+        match self.delay() {
+            Some(action) if self.delay_frames == 0 => self.apply(action),
+            _ => Ok(()),
         }
     }
 
+    /// Whether cleared rows are still shown, waiting for the line clear delay to end.
+    pub fn is_clearing(&self) -> bool {
+        !self.clearing_rows.is_empty()
+    }
+
+    /// Whether there is a falling piece, there is none while lines are
+    /// cleared and during the entry delay.
+    fn has_piece(&self) -> bool {
+        !self.is_clearing() && !self.entering
+    }
+
+    /// The action that ends the delay the board waits out before the next
+    /// piece can be played, gravity ends it once its frames have run.
+    pub fn delay(&self) -> Option<Action> {
+        if self.is_clearing() {
+            return Some(Action::FinishLineClear);
+        }
+        self.entering.then_some(Action::FinishEntry)
+    }
+
+    /// Everything that happened since the last call.
+    pub fn take_events(&mut self) -> Vec<BoardEvent> {
+        std::mem::take(&mut self.events)
+    }
+
     pub fn move_current_piece(&mut self, direction: Direction) {
-        match self
+        if let Ok(full_position) = self
             .current_tetromino
             .calc_horizontal_move((direction.into(), 0))
         {
-            Ok(full_position) => {
-                if !self.check_collision(full_position) {
-                    self.current_tetromino.horizontal_move(direction);
-                }
+            if !self.check_collision(full_position) {
+                self.current_tetromino.horizontal_move(direction);
+                let _ = self.apply_instant_gravity();
             }
-            Err(_) => {}
         }
     }
 
     fn check_collision(&self, new_full_position: [(usize, usize); 4]) -> bool {
         new_full_position.iter().any(|(x, y)| {
-            *x >= CONFIG.board_size.0
-                || *y >= CONFIG.board_size.1
-                || matches!(self.grid[*y][*x], Cell::Occupied(_))
+            *x >= self.config.board_size.0
+                || *y >= self.config.board_size.1
+                || !self.grid[*y][*x].is_empty()
         })
     }
 
@@ -185,14 +746,12 @@ impl Board {
         for offset_index in 0..5 {
             let full_position_rotated = self.current_tetromino.calc_rotate(clockwise, offset_index);
             self.last_rotation_check = Some(offset_index);
-            match full_position_rotated {
-                Ok(full_position) => {
-                    if !self.check_collision(full_position) {
-                        let _ = self.current_tetromino.rotate(clockwise, offset_index);
-                        return;
-                    }
+            if let Ok(full_position) = full_position_rotated {
+                if !self.check_collision(full_position) {
+                    let _ = self.current_tetromino.rotate(clockwise, offset_index);
+                    let _ = self.apply_instant_gravity();
+                    return;
                 }
-                Err(_) => {}
             }
         }
     }
@@ -202,8 +761,11 @@ impl Board {
             return Ok(());
         }
         self.already_held = true;
+        self.events.push(BoardEvent::Hold {
+            shape: self.current_tetromino.get_shape(),
+        });
 
-        match self.held_tetromino.clone() {
+        match self.held_tetromino {
             Some(held_tetromino) => {
                 self.held_tetromino = Some(self.current_tetromino.get_shape());
                 self.spawn_tetromino(held_tetromino)?;
@@ -217,24 +779,22 @@ impl Board {
         Ok(())
     }
 
-    pub fn soft_drop(&mut self, activate: bool) {
-        if activate {
-            self.tick_delay = CONFIG.tick_delay[&self.level] / 8;
-            return;
+    pub fn soft_drop(&mut self, activate: bool) -> Result<(), TetrominoPositionError> {
+        self.soft_dropping = activate;
+        // an infinite soft drop factor drops the piece right away, without locking it
+        if activate && self.config.soft_drop_factor.is_none() && self.has_piece() {
+            let height = self.fall_to_stack()?;
+            self.add_score(height as u128);
         }
-        self.tick_delay = CONFIG.tick_delay[&self.level];
+        Ok(())
     }
 
+    /// Moves the piece straight to its ghost and locks it at once, scoring
+    /// 2 points for every row it fell.
     pub fn hard_drop(&mut self) -> Result<(), TetrominoPositionError> {
-        let height = self.calc_relative_height()?;
-
-        // increase score
-        self.score += height as u128 as u128 * 2;
-
-        for _ in 0..(self.calc_relative_height()? + CONFIG.lock_delay as usize) {
-            let _ = self.update();
-        }
-        Ok(())
+        let height = self.fall_to_stack()?;
+        self.add_score(height as u128 * 2);
+        self.lock_piece()
     }
 
     pub fn calc_relative_height(&self) -> Result<usize, TetrominoPositionError> {
@@ -243,12 +803,12 @@ impl Board {
             .get_full_position()?
             .iter()
             .map(|(x_pos, y_pos)| {
-                for y in (y_pos + 1)..CONFIG.board_size.1 {
-                    if matches!(self.grid[y][*x_pos], Cell::Occupied(_)) {
+                for y in (y_pos + 1)..self.config.board_size.1 {
+                    if !self.grid[y][*x_pos].is_empty() {
                         return y - y_pos - 1;
                     }
                 }
-                CONFIG.board_size.1 - y_pos - 1
+                self.config.board_size.1 - y_pos - 1
             })
             .min()
             .expect("current tetromino doesn't have a position");
@@ -256,16 +816,28 @@ impl Board {
     }
 
     fn spawn_tetromino(&mut self, shape: TetrominoShape) -> Result<(), TetrominoPositionError> {
-        self.current_tetromino = Tetromino::new(shape);
+        self.current_tetromino = Tetromino::new(shape, self.config.board_size.0, self.lock_delay());
+        self.gravity_progress = 0;
         if self.check_collision(self.current_tetromino.get_full_position()?) {
-            return Err(TetrominoPositionError::Collision);
+            self.events.push(BoardEvent::TopOut);
+            if !self.rules.top_out_clears {
+                return Err(TetrominoPositionError::Collision);
+            }
+            self.grid
+                .iter_mut()
+                .flatten()
+                .for_each(|cell| *cell = Cell::Empty);
         }
-        Ok(())
+        self.events.push(BoardEvent::PieceSpawned { shape });
+        self.apply_instant_gravity()
     }
 
     fn spawn_next_piece(&mut self) -> Result<(), TetrominoPositionError> {
         self.bag_index += 1;
         if self.bag_index >= self.bag.len() {
+            if self.fixed_queue {
+                return Err(TetrominoPositionError::OutOfPieces);
+            }
             self.fill_bag();
             self.bag_index = 0;
         }
@@ -279,45 +851,122 @@ impl Board {
         if !self.current_tetromino.update_lock_delay() {
             return Ok(());
         }
+        self.lock_piece()
+    }
 
+    /// Puts the piece on the board, clears the lines it fills and brings in
+    /// the next piece.
+    fn lock_piece(&mut self) -> Result<(), TetrominoPositionError> {
         // lock current piece on the board
-        self.current_tetromino
-            .get_full_position()?
-            .iter()
-            .for_each(|(x, y)| {
-                self.grid[*y][*x] = Cell::Occupied(self.current_tetromino.get_shape());
-            });
+        let cells = self.current_tetromino.get_full_position()?;
+        let shape = self.current_tetromino.get_shape();
+        for (x, y) in cells {
+            self.grid[y][x] = Cell::Occupied(shape);
+        }
+        self.events.push(BoardEvent::PieceLocked { shape, cells });
 
-        // clear lines
-        self.clear_lines();
+        // clear lines, leaving them on the board for the line clear delay
+        let rows = self.full_rows();
+        self.score_clear(&rows);
+        if rows.is_empty() && self.rules.garbage_interval > 0 {
+            self.pieces_since_garbage += 1;
+            if self.pieces_since_garbage >= self.rules.garbage_interval {
+                self.pieces_since_garbage = 0;
+                self.add_garbage(1);
+            }
+        }
+        if !rows.is_empty() && self.config.line_clear_delay > 0 {
+            self.clearing_rows = rows;
+            self.delay_frames = millis_to_frames(self.config.line_clear_delay);
+            return Ok(());
+        }
+        self.remove_rows(&rows);
+        self.spawn_after_lock(!rows.is_empty())
+    }
 
+    /// Brings in the next piece after a lock, or starts its entry delay.
+    fn spawn_after_lock(&mut self, cleared: bool) -> Result<(), TetrominoPositionError> {
+        let delay = if cleared {
+            self.rules
+                .line_entry_delay
+                .unwrap_or(self.config.line_entry_delay)
+        } else {
+            self.rules.entry_delay.unwrap_or(self.config.entry_delay)
+        };
+        if delay > 0 {
+            self.entering = true;
+            self.delay_frames = millis_to_frames(delay);
+            return Ok(());
+        }
+        self.spawn_entering_piece()
+    }
+
+    fn spawn_entering_piece(&mut self) -> Result<(), TetrominoPositionError> {
         // spawn new piece
         self.spawn_next_piece()?;
 
         // reenable holding
         self.already_held = false;
 
+        // DAS charged during the delays moves the piece to the wall right away
+        if let Some(direction) = self.shift_direction {
+            if self.shift_frames >= u64::from(self.config.das_frames) {
+                self.shift_to_wall(direction);
+            }
+        }
+
         Ok(())
     }
 
-    // calculates a future tetromino's shape, can only return up to self.bag.len() pieces ahead
-    pub fn calc_next_piece(&self, n: usize) -> TetrominoShape {
-        assert!(
-            n <= self.bag.len(),
-            "can see only {} pieces ahead",
-            self.bag.len()
-        );
-
-        if self.bag_index + n >= self.bag.len() {
-            self.next_bag[self.bag_index + n - self.bag.len()]
-        } else {
-            self.bag[self.bag_index + n]
+    // calculates a future tetromino's shape, None if it isn't known yet or
+    // the fixed queue ends before it
+    pub fn calc_next_piece(&self, n: usize) -> Option<TetrominoShape> {
+        let index = self.bag_index + n;
+        match self.bag.get(index) {
+            Some(shape) => Some(*shape),
+            None => self.next_bag.get(index - self.bag.len()).copied(),
         }
     }
 
     fn fill_bag(&mut self) {
         self.bag = self.next_bag.clone();
-        self.next_bag = new_bag(&CONFIG.bag_type);
+        self.next_bag = new_bag(&self.config.bag_type, &mut self.rng);
+    }
+
+    /// Greys out the next row of the stack for the top out animation,
+    /// returns true once the whole board is grey.
+    pub fn grey_out_row(&mut self) -> bool {
+        if self.greyed_rows < self.config.board_size.1 {
+            self.greyed_rows += 1;
+        }
+        self.greyed_rows == self.config.board_size.1
+    }
+
+    fn cell_color(&self, y: usize, color: Color) -> Color {
+        if y >= self.config.board_size.1 - self.greyed_rows {
+            return Color::DarkGray;
+        }
+        color
+    }
+
+    /// Pushes rows of garbage, each with a single random hole, up from the bottom of the board.
+    pub fn add_garbage(&mut self, rows: usize) {
+        for _ in 0..rows {
+            let hole = self.rng.gen_range(0..self.config.board_size.0);
+            let mut row = vec![Cell::Garbage; self.config.board_size.0];
+            row[hole] = Cell::Empty;
+            self.grid.remove(0);
+            self.grid.push(row);
+        }
+        self.events.push(BoardEvent::GarbageReceived { rows });
+    }
+
+    /// Counts the rows that still contain garbage.
+    pub fn garbage_rows(&self) -> usize {
+        self.grid
+            .iter()
+            .filter(|row| row.iter().any(|cell| matches!(cell, Cell::Garbage)))
+            .count()
     }
 
     pub fn get_score(&self) -> u128 {
@@ -335,118 +984,104 @@ impl Board {
     pub fn get_held_tetromino(&self) -> Option<TetrominoShape> {
         self.held_tetromino
     }
-}
-impl Default for Board {
-    fn default() -> Self {
-        let starting_bag = new_bag(&CONFIG.bag_type);
-        Board {
-            grid: vec![vec![Cell::Empty; CONFIG.board_size.0]; CONFIG.board_size.1],
-            bag: starting_bag.clone(),
-            bag_index: 0,
-            next_bag: new_bag(&CONFIG.bag_type),
-            current_tetromino: starting_bag[0].into(),
-            held_tetromino: None,
-            already_held: false,
-            last_rotation_check: None, // last rotation check index, if there was any rotation
-            last_difficult_clear: None,
-            combo_count: 0,
-            tick_delay: CONFIG.tick_delay[&1],
-            score: 0,
-            lines_cleared: 0,
-            level: 1,
-        }
+
+    /// The locked cells, top row first.
+    pub fn grid(&self) -> &[Vec<Cell>] {
+        &self.grid
+    }
+
+    /// The falling piece, None while lines are being cleared.
+    pub fn current_piece(&self) -> Option<&Tetromino> {
+        self.has_piece().then_some(&self.current_tetromino)
     }
 }
-impl Shape for Board {
-    fn draw(&self, painter: &mut Painter) {
-        // get current tetromino positions
-        let tetromino_positions = self
-            .current_tetromino
-            .get_full_position()
-            .expect("negative tetromino position while drawing");
-        let tetromino_height = self
-            .calc_relative_height()
-            .expect("could not calculate tetromino height");
+impl Board {
+    /// Colors of every cell of the board in `theme`, top row first, with the
+    /// border on both sides and below. Empty cells are None.
+    pub fn cells(&self, theme: &Theme) -> Vec<Vec<Option<Tile>>> {
+        let (width, height) = self.config.board_size;
+        let mut cells = vec![vec![None; width + 2]; height + 1];
+
+        // get current tetromino positions, the locked piece is already part
+        // of the board while lines are cleared and until the next one enters
+        let (tetromino_positions, tetromino_height) = if !self.has_piece() {
+            (Vec::new(), 0)
+        } else {
+            (
+                self.current_tetromino
+                    .get_full_position()
+                    .expect("negative tetromino position while drawing")
+                    .to_vec(),
+                self.calc_relative_height()
+                    .expect("could not calculate tetromino height"),
+            )
+        };
 
-        // draw borders
-        let preview_piece = self.calc_next_piece(1);
+        // draw borders, dashed next to the top rows
+        let preview_piece = self
+            .calc_next_piece(1)
+            .unwrap_or(self.current_tetromino.get_shape());
+        let border_color = theme.border_color[&preview_piece];
+        let top_border_color = match self.held_tetromino {
+            Some(held) => theme.border_color[&held],
+            None => border_color,
+        };
         let start_continuous = 5;
-        for y in 0..start_continuous {
-            let color;
-            if let Some(held) = self.held_tetromino {
-                color = CONFIG.border_color[&held];
-            } else {
-                color = CONFIG.border_color[&preview_piece];
-            }
-            if y % 2 != 0 {
-                painter.paint(0, y + 1, color);
-                painter.paint(CONFIG.board_size.0 + 1, y + 1, color);
-            }
-        }
-        for y in start_continuous..CONFIG.board_size.1 {
-            painter.paint(0, y + 1, CONFIG.border_color[&preview_piece]);
-            painter.paint(
-                CONFIG.board_size.0 + 1,
-                y + 1,
-                CONFIG.border_color[&preview_piece],
-            );
-        }
-        for x in 0..CONFIG.board_size.0 + 2 {
-            painter.paint(
-                x,
-                CONFIG.board_size.1 + 1,
-                CONFIG.border_color[&preview_piece],
-            );
+        for (y, row) in cells.iter_mut().enumerate().take(height) {
+            let color = match y {
+                y if y < start_continuous && y % 2 == 0 => continue,
+                y if y < start_continuous => top_border_color,
+                _ => border_color,
+            };
+            row[0] = Some(Tile::plain(color));
+            row[width + 1] = Some(Tile::plain(color));
         }
+        cells[height] = vec![Some(Tile::plain(border_color)); width + 2];
 
         // draw the board
         for (y, row) in self.grid.iter().enumerate() {
             for (x, cell) in row.iter().enumerate() {
-                // draw the current tetromino
-                if tetromino_positions.contains(&(x, y)) {
-                    painter.paint(
-                        x + 1,
-                        y + 1,
-                        CONFIG.tetromino_color[&self.current_tetromino.get_shape()],
-                    );
-                    continue;
-                }
-
-                // draw ghost
-                if tetromino_positions
+                cells[y][x + 1] = if tetromino_positions.contains(&(x, y)) {
+                    // draw the current tetromino
+                    let shape = self.current_tetromino.get_shape();
+                    Some(Tile::piece(
+                        self.cell_color(y, theme.tetromino_color[&shape]),
+                        shape,
+                    ))
+                } else if tetromino_positions
                     .iter()
                     .any(|(x_pos, y_pos)| *x_pos == x && *y_pos + tetromino_height == y)
                 {
-                    painter.paint(
-                        x + 1,
-                        y + 1,
-                        CONFIG.ghost_color[&self.current_tetromino.get_shape()],
-                    );
-                    continue;
-                }
-
-                // draw the existing board
-                match cell {
-                    Cell::Empty => {}
-                    Cell::Occupied(shape) => {
-                        painter.paint(x + 1, y + 1, CONFIG.tetromino_color[shape])
+                    // draw ghost
+                    let shape = self.current_tetromino.get_shape();
+                    Some(Tile::piece(theme.ghost_color[&shape], shape))
+                } else {
+                    // draw the existing board
+                    match cell {
+                        Cell::Empty => None,
+                        Cell::Occupied(shape) => Some(Tile::piece(
+                            self.cell_color(y, theme.tetromino_color[shape]),
+                            *shape,
+                        )),
+                        Cell::Garbage => Some(Tile::plain(self.cell_color(y, theme.garbage))),
                     }
-                }
+                };
             }
         }
+        cells
     }
 }
 
-fn new_bag(bag_type: &BagType) -> Vec<TetrominoShape> {
+fn new_bag(bag_type: &BagType, rng: &mut StdRng) -> Vec<TetrominoShape> {
     match bag_type {
-        BagType::Classic => classic_bag(),
-        BagType::Fourteen => fourteen_bag(),
-        BagType::Seven => seven_bag(),
-        BagType::Pairs => pairs_bag(),
+        BagType::Classic => classic_bag(rng),
+        BagType::Fourteen => fourteen_bag(rng),
+        BagType::Seven => seven_bag(rng),
+        BagType::Pairs => pairs_bag(rng),
     }
 }
 
-fn seven_bag() -> Vec<TetrominoShape> {
+fn seven_bag(rng: &mut StdRng) -> Vec<TetrominoShape> {
     let mut bag = vec![
         TetrominoShape::I,
         TetrominoShape::J,
@@ -456,16 +1091,15 @@ fn seven_bag() -> Vec<TetrominoShape> {
         TetrominoShape::T,
         TetrominoShape::Z,
     ];
-    bag.shuffle(&mut thread_rng());
+    bag.shuffle(rng);
     bag
 }
 
-fn pairs_bag() -> Vec<TetrominoShape> {
+fn pairs_bag(rng: &mut StdRng) -> Vec<TetrominoShape> {
     let mut bag: Vec<TetrominoShape> = Vec::with_capacity(14);
-    let mut rng: ThreadRng = thread_rng();
 
     for _ in 0..7 {
-        let random_number = rand::Rng::gen_range(&mut rng, 0..7);
+        let random_number = rng.gen_range(0..7);
         let random_tetromino = match random_number {
             0 => TetrominoShape::I,
             1 => TetrominoShape::J,
@@ -482,7 +1116,7 @@ fn pairs_bag() -> Vec<TetrominoShape> {
     bag
 }
 
-fn fourteen_bag() -> Vec<TetrominoShape> {
+fn fourteen_bag(rng: &mut StdRng) -> Vec<TetrominoShape> {
     let mut bag: Vec<TetrominoShape> = vec![
         TetrominoShape::I,
         TetrominoShape::J,
@@ -499,16 +1133,15 @@ fn fourteen_bag() -> Vec<TetrominoShape> {
         TetrominoShape::T,
         TetrominoShape::Z,
     ];
-    bag.shuffle(&mut thread_rng());
+    bag.shuffle(rng);
     bag
 }
 
-fn classic_bag() -> Vec<TetrominoShape> {
+fn classic_bag(rng: &mut StdRng) -> Vec<TetrominoShape> {
     let mut bag: Vec<TetrominoShape> = Vec::with_capacity(7); // Create a Vec with a capacity of 7
-    let mut rng: ThreadRng = thread_rng();
 
     for _ in 0..7 {
-        let random_number = rand::Rng::gen_range(&mut rng, 0..7); // Changed to use the rng
+        let random_number = rng.gen_range(0..7);
         let random_tetromino = match random_number {
             0 => TetrominoShape::I,
             1 => TetrominoShape::J,
@@ -530,10 +1163,542 @@ mod tests {
 
     #[test]
     fn test_new_bag() {
-        let bag = new_bag(&CONFIG.bag_type);
-        match CONFIG.bag_type {
-            BagType::Classic | BagType::Seven => assert_eq!(bag.len(), 7),
-            BagType::Fourteen | BagType::Pairs => assert_eq!(bag.len(), 14),
+        for bag_type in BagType::ALL {
+            let bag = new_bag(&bag_type, &mut StdRng::seed_from_u64(0));
+            assert_eq!(bag.len(), bag_type.size());
         }
     }
+
+    #[test]
+    fn test_hard_drops_top_out() {
+        let config = Config::default();
+        let mut board = Board::new(&config, 0, 1);
+        let topped_out = (0..config.board_size.1 * config.board_size.0)
+            .any(|_| board.apply(Action::HardDrop).is_err());
+        assert!(topped_out);
+    }
+
+    #[test]
+    fn test_zen_rules() {
+        let config = Config::default();
+        let mut board = Board::new(&config, 0, 1);
+        board.set_rules(BoardRules {
+            gravity: Gravity::Off,
+            top_out_clears: true,
+            scoring: false,
+            ..BoardRules::default()
+        });
+        let position = board.current_tetromino.get_full_position().unwrap();
+        for _ in 0..10 {
+            board
+                .apply(Action::Gravity)
+                .expect("failed to apply gravity");
+        }
+        assert_eq!(
+            board.current_tetromino.get_full_position().unwrap(),
+            position
+        );
+        // soft dropping still moves the piece down
+        board
+            .apply(Action::SoftDrop(true))
+            .expect("failed to soft drop");
+        for _ in 0..10 {
+            board
+                .apply(Action::Gravity)
+                .expect("failed to apply gravity");
+        }
+        assert_ne!(
+            board.current_tetromino.get_full_position().unwrap(),
+            position
+        );
+        board
+            .apply(Action::SoftDrop(false))
+            .expect("failed to soft drop");
+
+        for _ in 0..config.board_size.1 * config.board_size.0 {
+            board
+                .apply(Action::HardDrop)
+                .expect("topping out ended the game");
+        }
+        assert!(board.take_events().contains(&BoardEvent::TopOut));
+        assert_eq!(board.get_score(), 0);
+    }
+
+    #[test]
+    fn test_gravity_in_frames() {
+        let config = Config::default();
+        let mut board = Board::new(&config, 0, 1);
+        let position = board.current_tetromino.get_full_position().unwrap();
+        let frame = |board: &mut Board| {
+            board
+                .apply(Action::Gravity)
+                .expect("failed to apply gravity");
+        };
+        // a row every 800 ms at level 1 is a row every 48 frames
+        for _ in 0..47 {
+            frame(&mut board);
+        }
+        assert_eq!(
+            board.current_tetromino.get_full_position().unwrap(),
+            position
+        );
+        frame(&mut board);
+        assert_eq!(
+            board.current_tetromino.get_full_position().unwrap(),
+            position.map(|(x, y)| (x, y + 1))
+        );
+        // soft dropping falls 8 times faster
+        board
+            .apply(Action::SoftDrop(true))
+            .expect("failed to soft drop");
+        for _ in 0..6 {
+            frame(&mut board);
+        }
+        assert_eq!(
+            board.current_tetromino.get_full_position().unwrap(),
+            position.map(|(x, y)| (x, y + 2))
+        );
+    }
+
+    #[test]
+    fn test_hard_drop_locks_at_once() {
+        let config = Config::default();
+        let mut board = Board::new(&config, 0, 1);
+        board.take_events();
+        let shape = board.current_tetromino.get_shape();
+        let start = board.current_tetromino.get_full_position().unwrap();
+        let height = board.calc_relative_height().unwrap();
+        // soft dropping adds nothing to the points of the drop
+        board
+            .apply(Action::SoftDrop(true))
+            .expect("failed to soft drop");
+        board.apply(Action::HardDrop).expect("failed to hard drop");
+        assert_eq!(board.get_score(), height as u128 * 2);
+        assert_eq!(
+            board.take_events()[0],
+            BoardEvent::PieceLocked {
+                shape,
+                cells: start.map(|(x, y)| (x, y + height)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_instant_soft_drop_and_sonic_drop() {
+        let config = Config {
+            soft_drop_factor: None,
+            ..Config::default()
+        };
+        let mut board = Board::new(&config, 0, 1);
+        let height = board.calc_relative_height().unwrap();
+        board
+            .apply(Action::SoftDrop(true))
+            .expect("failed to soft drop");
+        // on the stack right away, scoring the rows it fell, but not locked
+        assert_eq!(board.calc_relative_height().unwrap(), 0);
+        assert_eq!(board.get_score(), height as u128);
+        board
+            .apply(Action::Gravity)
+            .expect("failed to apply gravity");
+        assert!(!board
+            .take_events()
+            .iter()
+            .any(|event| matches!(event, BoardEvent::PieceLocked { .. })));
+
+        let mut board = Board::new(&Config::default(), 0, 1);
+        board
+            .apply(Action::SonicDrop)
+            .expect("failed to sonic drop");
+        assert_eq!(board.calc_relative_height().unwrap(), 0);
+        assert_eq!(board.get_score(), 0);
+        assert!(board.current_piece().is_some());
+    }
+
+    #[test]
+    fn test_twenty_g_and_entry_delay() {
+        let config = Config {
+            line_clear_delay: 0,
+            das_frames: 1,
+            ..Config::default()
+        };
+        let mut board = Board::new(&config, 0, 1);
+        board.set_rules(BoardRules {
+            gravity: Gravity::PerFrame(TWENTY_G),
+            entry_delay: Some(100),
+            ..BoardRules::default()
+        });
+        // the piece is on the floor before it ever falls
+        assert_eq!(board.calc_relative_height().unwrap(), 0);
+        board.apply(Action::HardDrop).expect("failed to hard drop");
+        assert!(board.current_piece().is_none());
+        assert_eq!(board.delay(), Some(Action::FinishEntry));
+        board
+            .apply(Action::Gravity)
+            .expect("failed to apply gravity");
+        assert!(board.current_piece().is_none());
+        // DAS charged during the entry delay moves the next piece to the wall
+        // at once, where it stays on the floor
+        board
+            .apply(Action::MovePressed(Direction::Left))
+            .expect("failed to press");
+        board
+            .apply(Action::Gravity)
+            .expect("failed to apply gravity");
+        board
+            .apply(Action::FinishEntry)
+            .expect("failed to bring in the next piece");
+        assert_eq!(shifted(&board).1, 0);
+        assert_eq!(board.calc_relative_height().unwrap(), 0);
+    }
+
+    /// Applies `frames` frames of gravity.
+    fn run_frames(board: &mut Board, frames: u64) {
+        for _ in 0..frames {
+            board.apply(Action::Gravity).expect("topped out");
+        }
+    }
+
+    #[test]
+    fn test_entry_delay_in_frames() {
+        let config = Config {
+            entry_delay: 100,
+            ..Config::default()
+        };
+        let mut board = Board::new(&config, 0, 1);
+        board.apply(Action::HardDrop).expect("failed to hard drop");
+        board.take_events();
+        // 100 milliseconds are 6 frames
+        run_frames(&mut board, 5);
+        assert!(board.current_piece().is_none());
+        run_frames(&mut board, 1);
+        assert!(board.current_piece().is_some());
+        assert!(matches!(
+            board.take_events()[..],
+            [BoardEvent::PieceSpawned { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_line_entry_delay_in_frames() {
+        let config = Config {
+            line_clear_delay: 100,
+            entry_delay: 1000,
+            line_entry_delay: 50,
+            ..Config::default()
+        };
+        let mut board = Board::new(&config, 0, 1);
+        board
+            .spawn_tetromino(TetrominoShape::I)
+            .expect("failed to spawn");
+        let columns = board
+            .current_tetromino
+            .get_full_position()
+            .expect("invalid position")
+            .map(|(x, _)| x);
+        let bottom = board.grid.len() - 1;
+        for (x, cell) in board.grid[bottom].iter_mut().enumerate() {
+            if !columns.contains(&x) {
+                *cell = Cell::Garbage;
+            }
+        }
+        board.apply(Action::HardDrop).expect("failed to hard drop");
+        assert!(board.is_clearing());
+        run_frames(&mut board, 5);
+        assert!(board.is_clearing());
+        // the line clear delay of 6 frames is followed by the line entry
+        // delay of 3, not the entry delay
+        run_frames(&mut board, 1);
+        assert!(!board.is_clearing());
+        assert_eq!(board.delay(), Some(Action::FinishEntry));
+        run_frames(&mut board, 2);
+        assert!(board.current_piece().is_none());
+        run_frames(&mut board, 1);
+        assert!(board.current_piece().is_some());
+    }
+
+    /// Columns the piece is shifted right of where it spawned, and its leftmost column.
+    fn shifted(board: &Board) -> (isize, usize) {
+        let piece = board.current_piece().expect("no piece entered");
+        let spawned = Tetromino::new(piece.get_shape(), board.config.board_size.0, 1);
+        let left = |tetromino: &Tetromino| {
+            let cells = tetromino.get_full_position().expect("invalid position");
+            cells.iter().map(|(x, _)| *x).min().expect("no cells")
+        };
+        (left(piece) as isize - left(&spawned) as isize, left(piece))
+    }
+
+    #[test]
+    fn test_auto_shift() {
+        let config = Config {
+            das_frames: 10,
+            arr_frames: 2,
+            ..Config::default()
+        };
+        let mut board = Board::new(&config, 0, 1);
+        board
+            .apply(Action::MovePressed(Direction::Right))
+            .expect("failed to move");
+        assert_eq!(shifted(&board).0, 1);
+        // DAS charges for 10 frames, then the piece moves every 2 frames
+        run_frames(&mut board, 9);
+        assert_eq!(shifted(&board).0, 1);
+        run_frames(&mut board, 1);
+        assert_eq!(shifted(&board).0, 2);
+        run_frames(&mut board, 1);
+        assert_eq!(shifted(&board).0, 2);
+        run_frames(&mut board, 1);
+        assert_eq!(shifted(&board).0, 3);
+        board
+            .apply(Action::MoveReleased(Direction::Right))
+            .expect("failed to release");
+        run_frames(&mut board, 20);
+        assert_eq!(shifted(&board).0, 3);
+
+        // an ARR of 0 moves the piece to the wall as soon as DAS is charged
+        let config = Config {
+            arr_frames: 0,
+            ..config
+        };
+        let mut board = Board::new(&config, 0, 1);
+        board
+            .apply(Action::MovePressed(Direction::Left))
+            .expect("failed to move");
+        run_frames(&mut board, 9);
+        assert_eq!(shifted(&board).0, -1);
+        run_frames(&mut board, 1);
+        assert_eq!(shifted(&board).1, 0);
+    }
+
+    #[test]
+    fn test_das_charged_during_delays() {
+        // 100 milliseconds of entry delay are 6 frames
+        let config = Config {
+            das_frames: 4,
+            entry_delay: 100,
+            ..Config::default()
+        };
+        let mut board = Board::new(&config, 0, 1);
+
+        // charged during the entry delay, the piece comes in at the wall
+        board.apply(Action::HardDrop).expect("failed to hard drop");
+        board
+            .apply(Action::MovePressed(Direction::Left))
+            .expect("failed to press");
+        run_frames(&mut board, 6);
+        assert_eq!(shifted(&board).1, 0);
+        board
+            .apply(Action::MoveReleased(Direction::Left))
+            .expect("failed to release");
+
+        // let go before the piece comes in, it stays where it spawns
+        board.apply(Action::HardDrop).expect("failed to hard drop");
+        board
+            .apply(Action::MovePressed(Direction::Right))
+            .expect("failed to press");
+        run_frames(&mut board, 5);
+        board
+            .apply(Action::MoveReleased(Direction::Right))
+            .expect("failed to release");
+        run_frames(&mut board, 1);
+        assert_eq!(shifted(&board).0, 0);
+        run_frames(&mut board, 20);
+        assert_eq!(shifted(&board).0, 0);
+
+        // not yet charged as the piece comes in, it auto shifts once DAS is
+        let config = Config {
+            das_frames: 10,
+            ..config
+        };
+        let mut board = Board::new(&config, 0, 1);
+        board.apply(Action::HardDrop).expect("failed to hard drop");
+        board
+            .apply(Action::MovePressed(Direction::Right))
+            .expect("failed to press");
+        run_frames(&mut board, 6);
+        assert_eq!(shifted(&board).0, 0);
+        run_frames(&mut board, 3);
+        assert_eq!(shifted(&board).0, 0);
+        run_frames(&mut board, 1);
+        assert_eq!(shifted(&board).0, 1);
+    }
+
+    #[test]
+    fn test_das_charged_during_line_clear() {
+        let config = Config {
+            das_frames: 4,
+            line_clear_delay: 100,
+            ..Config::default()
+        };
+        let mut board = Board::new(&config, 0, 1);
+        board
+            .spawn_tetromino(TetrominoShape::I)
+            .expect("failed to spawn");
+        let columns = board
+            .current_tetromino
+            .get_full_position()
+            .expect("invalid position")
+            .map(|(x, _)| x);
+        let bottom = board.grid.len() - 1;
+        for (x, cell) in board.grid[bottom].iter_mut().enumerate() {
+            if !columns.contains(&x) {
+                *cell = Cell::Garbage;
+            }
+        }
+        board.apply(Action::HardDrop).expect("failed to hard drop");
+        board
+            .apply(Action::MovePressed(Direction::Right))
+            .expect("failed to press");
+        run_frames(&mut board, 5);
+        assert!(board.is_clearing());
+        run_frames(&mut board, 1);
+        let right = board
+            .current_piece()
+            .expect("no piece entered")
+            .get_full_position()
+            .expect("invalid position")
+            .map(|(x, _)| x)
+            .into_iter()
+            .max();
+        assert_eq!(right, Some(config.board_size.0 - 1));
+    }
+
+    #[test]
+    fn test_board_size_from_config() {
+        let config = Config {
+            board_size: (6, 12),
+            ..Config::default()
+        };
+        let mut board = Board::new(&config, 0, 1);
+        assert_eq!(board.grid.len(), 12);
+        assert!(board.grid.iter().all(|row| row.len() == 6));
+        let topped_out = (0..12).any(|_| board.apply(Action::HardDrop).is_err());
+        assert!(topped_out);
+    }
+
+    #[test]
+    fn test_reload_config_keeps_rules() {
+        let mut board = Board::new(&Config::default(), 0, 1);
+        let mut config = Config {
+            board_size: (6, 12),
+            gravity: Some(20.0),
+            theme: "mono".to_string(),
+            ..Config::default()
+        };
+        config.tick_delay.insert(1, 100);
+        board.reload_config(&config);
+        assert_eq!(board.config().board_size, Config::default().board_size);
+        assert_eq!(
+            board.config().tick_delay_for_level(1),
+            Config::default().tick_delay_for_level(1)
+        );
+        assert_eq!(board.config().gravity, None);
+        assert_eq!(board.config().theme, "mono");
+    }
+
+    #[test]
+    fn test_add_garbage() {
+        let mut board = Board::new(&Config::default(), 0, 1);
+        board.add_garbage(3);
+        assert_eq!(board.garbage_rows(), 3);
+        for row in &board.grid[board.grid.len() - 3..] {
+            assert_eq!(row.iter().filter(|cell| cell.is_empty()).count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_game() {
+        let actions = [
+            Action::Rotate(true),
+            Action::Move(Direction::Left),
+            Action::HardDrop,
+            Action::Hold,
+            Action::Move(Direction::Right),
+            Action::HardDrop,
+            Action::Gravity,
+        ];
+        let mut first = Board::new(&Config::default(), 42, 1);
+        let mut second = Board::new(&Config::default(), 42, 1);
+        for action in actions {
+            first.apply(action).expect("topped out");
+            second.apply(action).expect("topped out");
+        }
+        assert_eq!(first.get_score(), second.get_score());
+        assert_eq!(first.get_held_tetromino(), second.get_held_tetromino());
+        for n in 0..first.bag.len() {
+            assert_eq!(first.calc_next_piece(n), second.calc_next_piece(n));
+        }
+    }
+
+    #[test]
+    fn test_line_clear_delay() {
+        let mut board = Board::new(&Config::default(), 0, 1);
+        board
+            .spawn_tetromino(TetrominoShape::I)
+            .expect("failed to spawn");
+        let columns = board
+            .current_tetromino
+            .get_full_position()
+            .expect("invalid position")
+            .map(|(x, _)| x);
+        let bottom = board.grid.len() - 1;
+        for (x, cell) in board.grid[bottom].iter_mut().enumerate() {
+            if !columns.contains(&x) {
+                *cell = Cell::Garbage;
+            }
+        }
+        board.take_events();
+
+        board.apply(Action::HardDrop).expect("topped out");
+        assert!(board.is_clearing());
+        // nothing can be controlled until the line is removed
+        board.apply(Action::Hold).expect("topped out");
+        assert_eq!(board.get_held_tetromino(), None);
+        assert!(matches!(
+            board.take_events()[..],
+            [
+                BoardEvent::PieceLocked {
+                    shape: TetrominoShape::I,
+                    ..
+                },
+                BoardEvent::LinesCleared { count: 1, .. },
+                BoardEvent::PerfectClear
+            ]
+        ));
+
+        board.apply(Action::FinishLineClear).expect("topped out");
+        assert!(!board.is_clearing());
+        assert!(matches!(
+            board.take_events()[..],
+            [BoardEvent::PieceSpawned { .. }]
+        ));
+        assert_eq!(board.get_lines_cleared(), 1);
+        assert!(board.grid[bottom].iter().all(Cell::is_empty));
+    }
+
+    #[test]
+    fn test_events() {
+        let mut board = Board::new(&Config::default(), 0, 1);
+        let first = board.current_tetromino.get_shape();
+        assert_eq!(
+            board.take_events(),
+            [BoardEvent::PieceSpawned { shape: first }]
+        );
+
+        board.apply(Action::Hold).expect("topped out");
+        board.add_garbage(2);
+        assert_eq!(
+            board.take_events(),
+            [
+                BoardEvent::Hold { shape: first },
+                BoardEvent::PieceSpawned {
+                    shape: board.current_tetromino.get_shape()
+                },
+                BoardEvent::GarbageReceived { rows: 2 },
+            ]
+        );
+
+        let topped_out = (0..100).any(|_| board.apply(Action::HardDrop).is_err());
+        assert!(topped_out);
+        assert_eq!(board.take_events().last(), Some(&BoardEvent::TopOut));
+    }
 }
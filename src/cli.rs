@@ -0,0 +1,282 @@
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+use crate::board::{Action, Board, BoardSetup};
+use crate::clock::format_duration;
+use crate::config::{BagType, Config, ConfigSource};
+use crate::fumen;
+use crate::history::History;
+use crate::mode::GameMode;
+use crate::scores::HighScores;
+use crate::tetromino::Direction;
+
+/// A terminal tetris game.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    #[command(flatten)]
+    pub play: PlayArgs,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start a game right away
+    Play(PlayArgs),
+    /// Watch a saved replay
+    Replay {
+        /// replay file, saved from the game over screen
+        file: String,
+    },
+    /// Print the high scores
+    Scores {
+        /// only print the scores of this mode
+        #[arg(long)]
+        mode: Option<GameMode>,
+    },
+    /// Show personal bests and trends of the games played
+    Stats {
+        /// only show the games of this mode
+        #[arg(long)]
+        mode: Option<GameMode>,
+    },
+    /// Inspect the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Measure how fast the game logic runs
+    Bench {
+        /// number of games to simulate
+        #[arg(long, default_value_t = 100)]
+        games: u64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the configuration in use, after the overrides
+    Dump,
+    /// Check the configuration for problems
+    Check,
+    /// Print a theme as a theme file, to start a new theme from
+    Theme {
+        /// bundled theme (guideline, tgm, monochrome, solarized, high-contrast,
+        /// deuteranopia, protanopia, tritanopia) or theme file, the configured theme if left out
+        name: Option<String>,
+    },
+}
+
+/// Where the configuration comes from and what overrides it.
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    /// config file to use instead of the one in the config directory
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<String>,
+    /// bag type (seven, fourteen, classic, pairs)
+    #[arg(long, global = true)]
+    pub bag_type: Option<BagType>,
+    /// color theme, bundled (see `config theme --help`) or a theme file
+    #[arg(long, global = true)]
+    pub theme: Option<String>,
+    /// board size, as WIDTHxHEIGHT
+    #[arg(long, global = true, value_name = "WIDTHxHEIGHT", value_parser = parse_board_size)]
+    pub board_size: Option<(usize, usize)>,
+    /// override a config key, the value is written like in the config file
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub overrides: Vec<(String, String)>,
+}
+
+impl ConfigArgs {
+    pub fn source(&self) -> ConfigSource {
+        let mut source = ConfigSource::default();
+        if let Some(path) = &self.config {
+            source.path = path.clone();
+        }
+        if let Some(bag_type) = self.bag_type {
+            source
+                .overrides
+                .push(("bag_type".to_string(), format!("{bag_type:?}")));
+        }
+        if let Some(theme) = &self.theme {
+            source
+                .overrides
+                .push(("theme".to_string(), format!("{theme:?}")));
+        }
+        if let Some((width, height)) = self.board_size {
+            source
+                .overrides
+                .push(("board_size".to_string(), format!("({width}, {height})")));
+        }
+        source.overrides.extend(self.overrides.iter().cloned());
+        source
+    }
+}
+
+/// How the games of this session start.
+#[derive(Debug, Args, Clone)]
+pub struct PlayArgs {
+    /// game mode (marathon, sprint, ultra, dig, puzzle, practice, zen, master)
+    #[arg(long)]
+    pub mode: Option<GameMode>,
+    /// starting level
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..))]
+    pub level: Option<u8>,
+    /// seed of the piece sequence, the same for every game
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// start every game from the first page of a fumen (v115@...), random
+    /// pieces follow its queue
+    #[arg(long, value_parser = parse_fumen)]
+    pub fumen: Option<BoardSetup>,
+}
+
+fn parse_board_size(size: &str) -> Result<(usize, usize), String> {
+    let error = || format!("`{size}` isn't WIDTHxHEIGHT, like 10x24");
+    let (width, height) = size.split_once(['x', 'X']).ok_or_else(error)?;
+    Ok((
+        width.trim().parse().map_err(|_| error())?,
+        height.trim().parse().map_err(|_| error())?,
+    ))
+}
+
+fn parse_fumen(text: &str) -> Result<BoardSetup, String> {
+    let pages = fumen::decode(text)?;
+    Ok(pages[0].setup())
+}
+
+fn parse_key_value(pair: &str) -> Result<(String, String), String> {
+    let (key, value) = pair
+        .split_once('=')
+        .ok_or_else(|| format!("`{pair}` isn't KEY=VALUE"))?;
+    Ok((key.trim().to_string(), value.trim().to_string()))
+}
+
+pub fn print_scores(mode: Option<GameMode>) {
+    let high_scores = HighScores::load();
+    let modes = match mode {
+        Some(mode) => vec![mode],
+        None => GameMode::ALL.to_vec(),
+    };
+    for mode in modes {
+        let entries = high_scores.entries(mode).collect::<Vec<_>>();
+        if entries.is_empty() {
+            continue;
+        }
+        println!("{}", mode.name());
+        for (rank, entry) in entries.iter().enumerate() {
+            println!(
+                "{:>3}. {:>10} level {:>3} lines {:>5} {}",
+                rank + 1,
+                entry.score,
+                entry.level,
+                entry.lines_cleared,
+                format_duration(entry.time)
+            );
+        }
+    }
+}
+
+/// Prints the reports of the game history, as wide as the terminal.
+pub fn print_stats(mode: Option<GameMode>) {
+    let history = History::load();
+    let width = crossterm::terminal::size().map_or(80, |(width, _)| width);
+    let modes = match mode {
+        Some(mode) => vec![mode],
+        None => GameMode::ALL.to_vec(),
+    };
+    let reports = modes
+        .into_iter()
+        .filter_map(|mode| history.report(mode, width))
+        .collect::<Vec<_>>();
+    if reports.is_empty() {
+        println!("no games played yet");
+    }
+    for report in reports {
+        println!("{}\n", report.join("\n"));
+    }
+}
+
+/// Plays games of random inputs as fast as possible and prints how long they took.
+pub fn bench(config: &Config, games: u64) {
+    let mut actions = 0u64;
+    let mut pieces = 0u64;
+    let mut elapsed = Duration::ZERO;
+    for seed in 0..games {
+        let mut board = Board::new(config, seed, 1);
+        let mut inputs = StdRng::seed_from_u64(seed);
+        let start = Instant::now();
+        loop {
+            // no one watches the cleared lines or waits for the next piece
+            if let Some(action) = board.delay() {
+                if board.apply(action).is_err() {
+                    break;
+                }
+            }
+            board.take_events();
+            let action = match inputs.gen_range(0..6) {
+                0 => Action::Move(Direction::Left),
+                1 => Action::Move(Direction::Right),
+                2 => Action::Rotate(inputs.gen()),
+                3 => Action::Gravity,
+                4 => Action::Hold,
+                _ => Action::HardDrop,
+            };
+            actions += 1;
+            pieces += u64::from(action == Action::HardDrop);
+            if board.apply(action).is_err() {
+                break;
+            }
+        }
+        elapsed += start.elapsed();
+    }
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    println!(
+        "{games} games, {pieces} pieces, {actions} actions in {}",
+        format_duration(elapsed)
+    );
+    println!(
+        "{:.0} pieces/s, {:.0} actions/s",
+        pieces as f64 / seconds,
+        actions as f64 / seconds
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arguments() {
+        let cli = Cli::parse_from([
+            "termtris",
+            "--set",
+            "lock_delay_frames=5",
+            "play",
+            "--mode",
+            "sprint",
+            "--seed",
+            "3",
+            "--board-size",
+            "12x30",
+        ]);
+        let Some(Command::Play(play)) = cli.command else {
+            panic!("expected the play command");
+        };
+        assert_eq!(play.mode, Some(GameMode::Sprint));
+        assert_eq!(play.seed, Some(3));
+        let config = cli.config.source().overrides;
+        assert_eq!(
+            config,
+            [
+                ("board_size".to_string(), "(12, 30)".to_string()),
+                ("lock_delay_frames".to_string(), "5".to_string()),
+            ]
+        );
+        assert!(parse_board_size("12 by 30").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+/// Measures in-game time, not counting time spent paused.
+#[derive(Debug, Clone, Default)]
+pub struct Stopwatch {
+    /// time accumulated before the last pause
+    elapsed: Duration,
+    /// when the stopwatch was last started, None while stopped
+    running_since: Option<Instant>,
+}
+
+impl Stopwatch {
+    /// Constructs a new [`Stopwatch`] that is already running.
+    pub fn started() -> Self {
+        Self {
+            elapsed: Duration::ZERO,
+            running_since: Some(Instant::now()),
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.running_since.is_none() {
+            self.running_since = Some(Instant::now());
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(since) = self.running_since.take() {
+            self.elapsed += since.elapsed();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running_since.is_some()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match self.running_since {
+            Some(since) => self.elapsed + since.elapsed(),
+            None => self.elapsed,
+        }
+    }
+}
+
+/// Formats a duration as minutes, seconds and milliseconds.
+pub fn format_duration(duration: Duration) -> String {
+    format!(
+        "{}:{:02}.{:03}",
+        duration.as_secs() / 60,
+        duration.as_secs() % 60,
+        duration.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(83_042)), "1:23.042");
+        assert_eq!(format_duration(Duration::ZERO), "0:00.000");
+    }
+}
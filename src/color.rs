@@ -0,0 +1,337 @@
+use ratatui::{prelude::Buffer, style::Color};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::config::Config;
+use crate::tetromino::TetrominoShape;
+use crate::theme::Theme;
+
+/// How many colors the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorDepth {
+    pub const ALL: [ColorDepth; 3] = [
+        ColorDepth::TrueColor,
+        ColorDepth::Ansi256,
+        ColorDepth::Ansi16,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorDepth::TrueColor => "truecolor",
+            ColorDepth::Ansi256 => "256",
+            ColorDepth::Ansi16 => "16",
+        }
+    }
+
+    /// Color depth of the terminal termtris runs in, from the environment and terminfo.
+    pub fn detect() -> ColorDepth {
+        let colorterm = std::env::var("COLORTERM").ok();
+        let term = std::env::var("TERM").ok();
+        detect_from(colorterm.as_deref(), term.as_deref(), terminfo_colors)
+    }
+}
+
+impl FromStr for ColorDepth {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        ColorDepth::ALL
+            .into_iter()
+            .find(|depth| depth.name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "color depth must be one of {}",
+                    ColorDepth::ALL.map(|depth| depth.name()).join(", ")
+                )
+            })
+    }
+}
+
+fn detect_from(
+    colorterm: Option<&str>,
+    term: Option<&str>,
+    terminfo_colors: impl Fn(&str) -> Option<i32>,
+) -> ColorDepth {
+    if matches!(colorterm, Some("truecolor" | "24bit")) {
+        return ColorDepth::TrueColor;
+    }
+    let Some(term) = term.filter(|term| !term.is_empty() && *term != "dumb") else {
+        return ColorDepth::Ansi16;
+    };
+    match terminfo_colors(term) {
+        Some(colors) if colors >= 1 << 24 => ColorDepth::TrueColor,
+        Some(colors) if colors >= 256 => ColorDepth::Ansi256,
+        Some(_) => ColorDepth::Ansi16,
+        // no terminfo entry, guess from the name
+        None if term.contains("direct") => ColorDepth::TrueColor,
+        None if term.contains("256color") => ColorDepth::Ansi256,
+        None => ColorDepth::Ansi16,
+    }
+}
+
+/// Number of colors of `term` in its compiled terminfo entry.
+fn terminfo_colors(term: &str) -> Option<i32> {
+    let first = term.chars().next()?;
+    let mut dirs = Vec::new();
+    if let Ok(dir) = std::env::var("TERMINFO") {
+        dirs.push(dir);
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        dirs.push(format!("{home}/.terminfo"));
+    }
+    if let Ok(list) = std::env::var("TERMINFO_DIRS") {
+        dirs.extend(
+            list.split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(String::from),
+        );
+    }
+    dirs.extend(
+        [
+            "/etc/terminfo",
+            "/lib/terminfo",
+            "/usr/share/terminfo",
+            "/usr/lib/terminfo",
+        ]
+        .map(String::from),
+    );
+    dirs.iter()
+        .flat_map(|dir| {
+            // entries are filed under their first letter, or its hex code on macOS
+            [
+                format!("{dir}/{first}/{term}"),
+                format!("{dir}/{:x}/{term}", first as u32),
+            ]
+        })
+        .find_map(|path| std::fs::read(path).ok())
+        .and_then(|entry| parse_terminfo_colors(&entry))
+}
+
+/// Reads the `colors` number out of a compiled terminfo entry, see term(5).
+fn parse_terminfo_colors(entry: &[u8]) -> Option<i32> {
+    const COLORS: usize = 13;
+    let short = |at: usize| -> Option<usize> {
+        Some(u16::from_le_bytes(entry.get(at..at + 2)?.try_into().ok()?) as usize)
+    };
+    let number_size = match short(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let (names_size, bools_count, numbers_count) = (short(2)?, short(4)?, short(6)?);
+    if numbers_count <= COLORS {
+        return None;
+    }
+    // the numbers start on an even byte
+    let numbers = (12 + names_size + bools_count).next_multiple_of(2);
+    let at = numbers + COLORS * number_size;
+    let colors = match number_size {
+        2 => i32::from(i16::from_le_bytes(entry.get(at..at + 2)?.try_into().ok()?)),
+        _ => i32::from_le_bytes(entry.get(at..at + 4)?.try_into().ok()?),
+    };
+    (colors > 0).then_some(colors)
+}
+
+/// Colors that replace the configured ones on terminals of a color depth,
+/// shapes left out keep the configured color.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Palette {
+    #[serde(default)]
+    pub tetromino_color: BTreeMap<TetrominoShape, Color>,
+    #[serde(default)]
+    pub ghost_color: BTreeMap<TetrominoShape, Color>,
+    #[serde(default)]
+    pub border_color: BTreeMap<TetrominoShape, Color>,
+}
+
+impl Palette {
+    /// The theme the board's cells are drawn with on terminals of `depth`,
+    /// with the colors of its palette in place of the theme's.
+    pub fn cell_theme<'a>(config: &Config, theme: &'a Theme, depth: ColorDepth) -> Cow<'a, Theme> {
+        let Some(palette) = config.palettes.get(&depth) else {
+            return Cow::Borrowed(theme);
+        };
+        let mut theme = theme.clone();
+        theme.tetromino_color.extend(&palette.tetromino_color);
+        theme.ghost_color.extend(&palette.ghost_color);
+        theme.border_color.extend(&palette.border_color);
+        Cow::Owned(theme)
+    }
+}
+
+/// Turns the colors of a rendered frame into ones the terminal can show.
+pub struct ColorMapper {
+    depth: ColorDepth,
+}
+
+impl ColorMapper {
+    pub fn new(depth: ColorDepth) -> ColorMapper {
+        ColorMapper { depth }
+    }
+
+    pub fn map(&self, color: Color) -> Color {
+        match (self.depth, color) {
+            (ColorDepth::TrueColor, _) => color,
+            (ColorDepth::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(nearest_256((r, g, b))),
+            (ColorDepth::Ansi256, _) => color,
+            (ColorDepth::Ansi16, Color::Rgb(r, g, b)) => nearest_16((r, g, b)),
+            (ColorDepth::Ansi16, Color::Indexed(index)) => nearest_16(indexed_rgb(index)),
+            (ColorDepth::Ansi16, _) => color,
+        }
+    }
+
+    pub fn apply(&self, buf: &mut Buffer) {
+        if self.depth == ColorDepth::TrueColor {
+            return;
+        }
+        for cell in &mut buf.content {
+            cell.fg = self.map(cell.fg);
+            cell.bg = self.map(cell.bg);
+        }
+    }
+}
+
+/// The 16 standard colors, as xterm shows them.
+const ANSI_16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Levels of each channel in the 6x6x6 color cube of the 256 color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let channel = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+    channel(r1, r2) + channel(g1, g2) + channel(b1, b2)
+}
+
+fn nearest_16(rgb: (u8, u8, u8)) -> Color {
+    ANSI_16
+        .iter()
+        .min_by_key(|(_, ansi)| distance(rgb, *ansi))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |channel: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|i| CUBE_LEVELS[*i].abs_diff(channel))
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * level(rgb.0) + 6 * level(rgb.1) + level(rgb.2);
+    let average = (u16::from(rgb.0) + u16::from(rgb.1) + u16::from(rgb.2)) / 3;
+    let gray = 232 + ((average.saturating_sub(3)) / 10).min(23) as u8;
+    if distance(rgb, indexed_rgb(gray)) < distance(rgb, indexed_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// The color an entry of the 256 color palette stands for.
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_16[usize::from(index)].1,
+        16..=231 => {
+            let cube = index - 16;
+            let level = |i: u8| CUBE_LEVELS[usize::from(i)];
+            (level(cube / 36), level(cube / 6 % 6), level(cube % 6))
+        }
+        _ => {
+            let gray = 8 + 10 * (index - 232);
+            (gray, gray, gray)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let no_terminfo = |_: &str| None;
+        assert_eq!(
+            detect_from(Some("truecolor"), Some("xterm"), no_terminfo),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(
+            detect_from(None, Some("screen-256color"), no_terminfo),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(
+            detect_from(None, Some("linux"), |_| Some(8)),
+            ColorDepth::Ansi16
+        );
+        assert_eq!(
+            detect_from(None, Some("xterm"), |_| Some(256)),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(detect_from(None, None, no_terminfo), ColorDepth::Ansi16);
+    }
+
+    #[test]
+    fn test_parse_terminfo() {
+        // names "x\0", no booleans, 14 numbers with colors set to 256
+        let mut entry = vec![0x1a, 0x01, 2, 0, 0, 0, 14, 0, 0, 0, 0, 0, b'x', 0];
+        for i in 0..14 {
+            let number: i16 = if i == 13 { 256 } else { -1 };
+            entry.extend(number.to_le_bytes());
+        }
+        assert_eq!(parse_terminfo_colors(&entry), Some(256));
+        assert_eq!(parse_terminfo_colors(&entry[..20]), None);
+    }
+
+    #[test]
+    fn test_map_colors() {
+        let mapper = ColorMapper::new(ColorDepth::Ansi256);
+        assert_eq!(mapper.map(Color::Rgb(255, 0, 0)), Color::Indexed(196));
+        assert_eq!(mapper.map(Color::Rgb(128, 128, 128)), Color::Indexed(244));
+        assert_eq!(mapper.map(Color::Red), Color::Red);
+
+        let mapper = ColorMapper::new(ColorDepth::Ansi16);
+        assert_eq!(mapper.map(Color::Rgb(250, 10, 10)), Color::LightRed);
+        assert_eq!(mapper.map(Color::Indexed(21)), Color::Blue);
+    }
+
+    #[test]
+    fn test_palette_cell_theme() {
+        let config = Config::default();
+        let theme = Theme::default();
+        assert_eq!(
+            *Palette::cell_theme(&config, &theme, ColorDepth::TrueColor),
+            theme
+        );
+
+        // the palette of the depth replaces the colors of the cells only
+        let cell_theme = Palette::cell_theme(&config, &theme, ColorDepth::Ansi16);
+        let palette = &config.palettes[&ColorDepth::Ansi16];
+        assert_eq!(
+            cell_theme.ghost_color[&TetrominoShape::T],
+            palette.ghost_color[&TetrominoShape::T]
+        );
+        assert_eq!(cell_theme.text, theme.text);
+    }
+}
@@ -1,29 +1,113 @@
+use crate::animation::ClearAnimation;
+use crate::board::millis_to_frames;
+use crate::color::{ColorDepth, Palette};
+use crate::grid::{CellStyle, CellTexture, ShapeMarks};
 use crate::tetromino::TetrominoShape;
-use lazy_static::lazy_static;
+use crate::theme::{Theme, BUNDLED_THEMES};
+use anyhow::Result;
 use ratatui::style::Color;
-use ron::from_str;
-use serde::Deserialize;
-use std::collections::HashMap;
+use ron::{
+    from_str,
+    ser::{to_string_pretty, PrettyConfig},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
 
 pub const CONFIG_FILE_NAME: &str = "config.ron";
 const PROJECT_NAME: &str = "termtris";
 
-#[derive(PartialEq, Deserialize, Debug)]
+#[derive(PartialEq, Eq, Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum BagType {
     Seven,
     Fourteen,
     Classic,
     Pairs,
 }
+impl BagType {
+    pub const ALL: [BagType; 4] = [
+        BagType::Seven,
+        BagType::Fourteen,
+        BagType::Classic,
+        BagType::Pairs,
+    ];
 
-lazy_static! {
-    pub static ref CONFIG: Config = Config::default();
+    pub fn name(&self) -> &'static str {
+        match self {
+            BagType::Seven => "seven",
+            BagType::Fourteen => "fourteen",
+            BagType::Classic => "classic",
+            BagType::Pairs => "pairs",
+        }
+    }
+
+    /// Number of pieces in a single bag, which is also how far ahead pieces can be previewed.
+    pub fn size(&self) -> usize {
+        match self {
+            BagType::Seven | BagType::Classic => 7,
+            BagType::Fourteen | BagType::Pairs => 14,
+        }
+    }
 }
 
+impl FromStr for BagType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        BagType::ALL
+            .into_iter()
+            .find(|bag_type| bag_type.name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "bag type must be one of {}",
+                    BagType::ALL.map(|bag_type| bag_type.name()).join(", ")
+                )
+            })
+    }
+}
+
+/// Narrowest board the pieces can spawn and rotate on.
+pub const MIN_BOARD_WIDTH: usize = 4;
+/// Pieces spawn in the third row, this leaves them room to move and rotate.
+pub const MIN_BOARD_HEIGHT: usize = 6;
+/// Most rows the fixed `gravity` lets pieces fall every frame.
+pub const MAX_GRAVITY: f32 = 20.0;
+
+/// How often [`ConfigWatcher`] looks at the config file.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
 fn default_bag_type() -> BagType {
     BagType::Seven
 }
 
+fn default_cell_style() -> CellStyle {
+    CellStyle::Auto
+}
+
+fn default_cell_texture() -> CellTexture {
+    CellTexture::Flat
+}
+
+fn default_shape_marks() -> ShapeMarks {
+    ShapeMarks::Off
+}
+
+fn default_palettes() -> BTreeMap<ColorDepth, Palette> {
+    // the nearest of the 16 colors to the dark ghost colors is black, which
+    // can't be seen on most terminals
+    BTreeMap::from([(
+        ColorDepth::Ansi16,
+        Palette {
+            ghost_color: TetrominoShape::ALL
+                .into_iter()
+                .map(|shape| (shape, Color::DarkGray))
+                .collect(),
+            ..Palette::default()
+        },
+    )])
+}
+
 fn default_board_size() -> (usize, usize) {
     (10, 24)
 }
@@ -32,12 +116,32 @@ fn default_number_of_previews() -> usize {
     4
 }
 
-fn default_lock_delay() -> u8 {
-    3
+fn default_lock_delay_frames() -> u8 {
+    30
+}
+
+fn default_das_frames() -> u8 {
+    10
 }
 
-fn default_tick_delay() -> HashMap<u8, u64> {
-    HashMap::from([
+fn default_arr_frames() -> u8 {
+    2
+}
+
+fn default_line_clear_delay() -> u64 {
+    300
+}
+
+fn default_clear_animation() -> ClearAnimation {
+    ClearAnimation::Flash
+}
+
+fn default_action_text() -> bool {
+    true
+}
+
+fn default_tick_delay() -> BTreeMap<u8, u64> {
+    BTreeMap::from([
         (1, 800),
         (2, 717),
         (3, 633),
@@ -55,93 +159,653 @@ fn default_tick_delay() -> HashMap<u8, u64> {
     ])
 }
 
-fn default_tetromino_color() -> HashMap<TetrominoShape, Color> {
-    HashMap::from([
-        (TetrominoShape::I, Color::Rgb(0, 255, 255)),
-        (TetrominoShape::L, Color::Rgb(255, 127, 0)),
-        (TetrominoShape::J, Color::Rgb(0, 0, 255)),
-        (TetrominoShape::O, Color::Rgb(255, 255, 0)),
-        (TetrominoShape::S, Color::Rgb(0, 255, 0)),
-        (TetrominoShape::T, Color::Rgb(128, 0, 128)),
-        (TetrominoShape::Z, Color::Rgb(255, 0, 0)),
-    ])
+fn default_soft_drop_factor() -> Option<u32> {
+    Some(8)
 }
 
-fn default_ghost_color() -> HashMap<TetrominoShape, Color> {
-    HashMap::from([
-        (TetrominoShape::I, Color::Rgb(0, 127, 128)),
-        (TetrominoShape::L, Color::Rgb(128, 64, 0)),
-        (TetrominoShape::J, Color::Rgb(0, 0, 128)),
-        (TetrominoShape::O, Color::Rgb(128, 127, 0)),
-        (TetrominoShape::S, Color::Rgb(0, 128, 0)),
-        (TetrominoShape::T, Color::Rgb(61, 0, 61)),
-        (TetrominoShape::Z, Color::Rgb(128, 0, 0)),
-    ])
+fn default_render_rate() -> u32 {
+    60
 }
 
-fn default_border_color() -> HashMap<TetrominoShape, Color> {
-    HashMap::from([
-        (TetrominoShape::I, Color::Rgb(64, 191, 191)),
-        (TetrominoShape::L, Color::Rgb(191, 127, 64)),
-        (TetrominoShape::J, Color::Rgb(64, 64, 191)),
-        (TetrominoShape::O, Color::Rgb(191, 191, 64)),
-        (TetrominoShape::S, Color::Rgb(64, 191, 64)),
-        (TetrominoShape::T, Color::Rgb(96, 32, 96)),
-        (TetrominoShape::Z, Color::Rgb(191, 64, 64)),
-    ])
+fn default_theme() -> String {
+    BUNDLED_THEMES[0].to_string()
 }
 
-#[derive(PartialEq, Debug, Deserialize)]
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default = "default_board_size")]
     pub board_size: (usize, usize),
     #[serde(default = "default_number_of_previews")]
     pub number_of_previews: usize,
-    #[serde(default = "default_lock_delay")]
-    pub lock_delay: u8,
+    /// frames (1/60 s) a piece rests on the stack before it locks
+    #[serde(default = "default_lock_delay_frames")]
+    pub lock_delay_frames: u8,
+    /// milliseconds cleared lines stay on the board before the next piece spawns
+    #[serde(default = "default_line_clear_delay")]
+    pub line_clear_delay: u64,
+    /// times faster pieces fall while soft dropped, instantly onto the stack
+    /// without locking if not set
+    #[serde(default = "default_soft_drop_factor")]
+    pub soft_drop_factor: Option<u32>,
+    /// frames (1/60 s) a direction is held before the piece starts moving on
+    /// its own (DAS), also charged while there is no piece
+    #[serde(default = "default_das_frames")]
+    pub das_frames: u8,
+    /// frames between the moves of a piece moving on its own (ARR), 0 moves
+    /// it to the wall at once
+    #[serde(default = "default_arr_frames")]
+    pub arr_frames: u8,
+    /// milliseconds between a lock and the next piece (ARE)
+    #[serde(default)]
+    pub entry_delay: u64,
+    /// milliseconds between the end of a line clear and the next piece (line ARE)
+    #[serde(default)]
+    pub line_entry_delay: u64,
     #[serde(default = "default_tick_delay")]
-    pub tick_delay: HashMap<u8, u64>,
-    #[serde(default = "default_tetromino_color")]
-    pub tetromino_color: HashMap<TetrominoShape, Color>,
-    #[serde(default = "default_ghost_color")]
-    pub ghost_color: HashMap<TetrominoShape, Color>,
-    #[serde(default = "default_border_color")]
-    pub border_color: HashMap<TetrominoShape, Color>,
+    pub tick_delay: BTreeMap<u8, u64>,
+    /// rows pieces fall every frame (1/60 s) at any level instead of the
+    /// tick delays, 20 puts them on the stack as soon as they spawn
+    #[serde(default)]
+    pub gravity: Option<f32>,
+    /// milliseconds between gravity steps in zen mode, pieces only fall while
+    /// soft dropped if not set
+    #[serde(default)]
+    pub zen_tick_delay: Option<u64>,
+    /// do clears and drops score in zen mode
+    #[serde(default)]
+    pub zen_scoring: bool,
+    /// pieces placed without clearing a line before a row of garbage rises in
+    /// zen mode, 0 for no garbage
+    #[serde(default)]
+    pub zen_garbage: u32,
+    /// a bundled theme or a theme file, see [`Theme::load`]
+    #[serde(default = "default_theme")]
+    pub theme: String,
+    /// colors that replace the theme's, for some or all of the shapes
+    #[serde(default)]
+    pub tetromino_color: BTreeMap<TetrominoShape, Color>,
+    #[serde(default)]
+    pub ghost_color: BTreeMap<TetrominoShape, Color>,
+    #[serde(default)]
+    pub border_color: BTreeMap<TetrominoShape, Color>,
     #[serde(default = "default_bag_type")]
     pub bag_type: BagType,
+    /// how cells are drawn, auto picks the largest that fits the terminal
+    #[serde(default = "default_cell_style")]
+    pub cell_style: CellStyle,
+    /// bevel or shading of the cells, only the wide styles have room for it
+    #[serde(default = "default_cell_texture")]
+    pub cell_texture: CellTexture,
+    /// letters or patterns that tell the shapes apart without their colors
+    #[serde(default = "default_shape_marks")]
+    pub shape_marks: ShapeMarks,
+    /// colors the terminal can show, detected when not set
+    #[serde(default)]
+    pub color_depth: Option<ColorDepth>,
+    /// colors used instead of the ones above on terminals with fewer colors,
+    /// the rest are shown as the nearest color the terminal has
+    #[serde(default = "default_palettes")]
+    pub palettes: BTreeMap<ColorDepth, Palette>,
+    /// how cleared lines disappear during the line clear delay
+    #[serde(default = "default_clear_animation")]
+    pub clear_animation: ClearAnimation,
+    /// show the names of clears, like TETRIS or COMBO 3, beside the board
+    #[serde(default = "default_action_text")]
+    pub action_text: bool,
+    /// most times the screen is drawn every second, the game itself always
+    /// runs at 60 frames a second
+    #[serde(default = "default_render_rate")]
+    pub render_rate: u32,
+}
+
+impl Config {
+    /// Gravity delay for a level, levels missing from the map use the closest lower level.
+    pub fn tick_delay_for_level(&self, level: u8) -> u64 {
+        self.tick_delay
+            .iter()
+            .filter(|(from_level, _)| **from_level <= level)
+            .max_by_key(|(from_level, _)| **from_level)
+            .or_else(|| {
+                self.tick_delay
+                    .iter()
+                    .min_by_key(|(from_level, _)| **from_level)
+            })
+            .map(|(_, delay)| *delay)
+            .expect("tick_delay must not be empty")
+    }
+
+    /// Parses and validates a configuration, returns every problem found,
+    /// prefixed with where it is in `ron`. A valid configuration comes with
+    /// warnings about the deprecated keys it was read from.
+    pub fn from_ron(ron: &str) -> Result<(Config, Vec<String>), Vec<String>> {
+        let mut config: Config = from_str(ron).map_err(|error| {
+            vec![format!(
+                "line {}, column {}: {}",
+                error.position.line, error.position.col, error.code
+            )]
+        })?;
+
+        let known_keys = known_keys();
+        let fields = top_level_fields(ron);
+        let location = |key: &str| line_column(ron, key.as_ptr() as usize - ron.as_ptr() as usize);
+
+        // problems along with where they are, problems of fields that were left out come last
+        let mut problems: Vec<(Option<(usize, usize)>, String)> = Vec::new();
+        let mut warnings = Vec::new();
+        for (key, value) in &fields {
+            if *key == DEPRECATED_LOCK_DELAY {
+                let (line, column) = location(key);
+                if fields.iter().any(|(key, _)| *key == "lock_delay_frames") {
+                    warnings.push(format!(
+                        "line {line}, column {column}: `{DEPRECATED_LOCK_DELAY}` is deprecated \
+                         and ignored, `lock_delay_frames` is set"
+                    ));
+                    continue;
+                }
+                match config.convert_lock_delay(value) {
+                    Ok(warning) => {
+                        warnings.push(format!("line {line}, column {column}: {warning}"))
+                    }
+                    Err(problem) => problems.push((Some((line, column)), problem)),
+                }
+            } else if !known_keys.iter().any(|known| known == key) {
+                problems.push((Some(location(key)), unknown_key(key, &known_keys)));
+            }
+        }
+        for (field, problem) in config.validate() {
+            match fields.iter().find(|(key, _)| *key == field) {
+                Some((key, _)) => {
+                    problems.push((Some(location(key)), format!("{field}: {problem}")))
+                }
+                None => problems.push((None, format!("{field}: {problem}"))),
+            }
+        }
+        problems.sort_by_key(|(location, _)| location.unwrap_or((usize::MAX, 0)));
+
+        if problems.is_empty() {
+            Ok((config, warnings))
+        } else {
+            Err(problems
+                .into_iter()
+                .map(|(location, problem)| match location {
+                    Some((line, column)) => format!("line {line}, column {column}: {problem}"),
+                    None => problem,
+                })
+                .collect())
+        }
+    }
+
+    /// Reads the deprecated `lock_delay`, which counted gravity ticks of the
+    /// first level, into `lock_delay_frames`, returns the warning to show.
+    fn convert_lock_delay(&mut self, ticks: &str) -> Result<String, String> {
+        let ticks: u64 = ticks
+            .trim()
+            .parse()
+            .map_err(|_| format!("`{DEPRECATED_LOCK_DELAY}` must be a number of ticks"))?;
+        let tick_delay = self.tick_delay_for_level(1);
+        let frames = millis_to_frames(ticks * tick_delay).clamp(1, u64::from(u8::MAX));
+        self.lock_delay_frames = frames as u8;
+        Ok(format!(
+            "`{DEPRECATED_LOCK_DELAY}` is deprecated, its {ticks} ticks of {tick_delay} ms \
+             were read as `lock_delay_frames: {frames}`, set that instead"
+        ))
+    }
+
+    /// Changes a single top level key as if `key: value` was written in the
+    /// config file, the result still has to be validated.
+    pub fn set(&self, key: &str, value: &str) -> Result<Config, String> {
+        let known_keys = known_keys();
+        if !known_keys.iter().any(|known| known == key) {
+            return Err(unknown_key(key, &known_keys));
+        }
+        let ron =
+            to_string_pretty(self, PrettyConfig::default()).map_err(|error| error.to_string())?;
+        let fields = top_level_fields(&ron)
+            .into_iter()
+            .map(|(field, current)| {
+                let value = if field == key { value } else { current };
+                format!("{field}: {value}")
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        from_str(&format!("({fields})")).map_err(|error| error.code.to_string())
+    }
+
+    /// Checks the values that parse fine but can't be played with,
+    /// returns the name of each offending field along with what is wrong with it.
+    pub fn validate(&self) -> Vec<(&'static str, String)> {
+        let mut problems = Vec::new();
+        let (width, height) = self.board_size;
+        if width < MIN_BOARD_WIDTH {
+            problems.push((
+                "board_size",
+                format!("the board must be at least {MIN_BOARD_WIDTH} cells wide, not {width}"),
+            ));
+        }
+        if height < MIN_BOARD_HEIGHT {
+            problems.push((
+                "board_size",
+                format!(
+                    "the board must be at least {MIN_BOARD_HEIGHT} cells high for pieces to spawn, not {height}"
+                ),
+            ));
+        }
+        if self.number_of_previews > self.bag_type.size() {
+            problems.push((
+                "number_of_previews",
+                format!(
+                    "a {:?} bag can show at most {} previews, not {}",
+                    self.bag_type,
+                    self.bag_type.size(),
+                    self.number_of_previews
+                ),
+            ));
+        }
+        if self.lock_delay_frames == 0 {
+            problems.push(("lock_delay_frames", "must be at least 1".to_string()));
+        }
+        if self.soft_drop_factor == Some(0) {
+            problems.push((
+                "soft_drop_factor",
+                "must be at least 1, or None for an instant soft drop".to_string(),
+            ));
+        }
+        if self.render_rate == 0 {
+            problems.push(("render_rate", "must be at least 1".to_string()));
+        }
+        if self.tick_delay.is_empty() {
+            problems.push((
+                "tick_delay",
+                "needs a delay for at least one level".to_string(),
+            ));
+        }
+        for (level, delay) in &self.tick_delay {
+            if *delay == 0 {
+                problems.push((
+                    "tick_delay",
+                    format!("the delay of level {level} must be at least 1 ms"),
+                ));
+            }
+        }
+        if self.gravity.is_some_and(|g| !(g > 0.0 && g <= MAX_GRAVITY)) {
+            problems.push((
+                "gravity",
+                format!("must be more than 0 and at most {MAX_GRAVITY} G"),
+            ));
+        }
+        if self.zen_tick_delay == Some(0) {
+            problems.push(("zen_tick_delay", "must be at least 1 ms".to_string()));
+        }
+        if let Err(problem) = Theme::load(&self.theme) {
+            problems.push(("theme", problem));
+        }
+        problems
+    }
+
+    /// Highest level that has its own gravity delay, used as the highest starting level.
+    pub fn max_level(&self) -> u8 {
+        self.tick_delay.keys().copied().max().unwrap_or(1)
+    }
+
+    /// Writes the configuration to the config file at `path`, keeping any keys
+    /// of the existing file that termtris doesn't know about.
+    pub fn save(&self, path: &str) -> Result<()> {
+        let existing = std::fs::read_to_string(path).unwrap_or_default();
+        if let Some(dir) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_ron_keeping_unknown(&existing)?)?;
+        Ok(())
+    }
+
+    /// The configuration as it would be written to the config file.
+    pub fn to_ron(&self) -> Result<String> {
+        self.to_ron_keeping_unknown("")
+    }
+
+    fn to_ron_keeping_unknown(&self, existing: &str) -> Result<String> {
+        let mut ron = to_string_pretty(self, PrettyConfig::default())?;
+        let known_fields = top_level_fields(&ron)
+            .into_iter()
+            .map(|(key, _)| key.to_string())
+            .collect::<Vec<_>>();
+        let unknown_fields = top_level_fields(existing)
+            .into_iter()
+            .filter(|(key, _)| !known_fields.iter().any(|known| known == key))
+            .map(|(key, value)| format!("    {key}: {value},\n"))
+            .collect::<String>();
+        if let Some(closing) = ron.rfind(')') {
+            ron.insert_str(closing, &unknown_fields);
+        }
+        ron.push('\n');
+        Ok(ron)
+    }
+}
+
+/// Splits the top level struct of a RON document into its keys and raw values,
+/// returns nothing if the document isn't a struct.
+fn top_level_fields(ron: &str) -> Vec<(&str, &str)> {
+    enum State {
+        BeforeStruct,
+        BeforeKey,
+        Key(usize),
+        // start of the value and end of its last character that isn't whitespace
+        Value(usize, usize),
+    }
+
+    let mut fields = Vec::new();
+    let mut state = State::BeforeStruct;
+    // bracket depth inside the struct
+    let mut depth = 0usize;
+    let mut key = "";
+    for (offset, c, in_literal) in code_chars(ron) {
+        let end = offset + c.len_utf8();
+        match (&state, c, in_literal) {
+            (State::BeforeStruct, '(', false) => {
+                state = State::BeforeKey;
+                continue;
+            }
+            (State::BeforeStruct, _, _) => continue,
+            (State::Value(start, _), _, true) => {
+                state = State::Value(*start, end);
+                continue;
+            }
+            (_, _, true) => continue,
+            _ => {}
+        }
+
+        if depth == 0 {
+            match (&state, c) {
+                (State::BeforeKey, ')') => return fields,
+                (State::Value(start, value_end), ',' | ')') => {
+                    fields.push((key, ron[*start..*value_end].trim()));
+                    if c == ')' {
+                        return fields;
+                    }
+                    state = State::BeforeKey;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        match (&state, c) {
+            (State::BeforeKey, c) if !c.is_whitespace() => state = State::Key(offset),
+            (State::Key(start), ':') => {
+                key = ron[*start..offset].trim();
+                state = State::Value(end, end);
+            }
+            (State::Value(start, _), c) if !c.is_whitespace() => state = State::Value(*start, end),
+            _ => {}
+        }
+    }
+    fields
+}
+
+/// Keys of the top level struct of the config file.
+fn known_keys() -> Vec<String> {
+    let default_ron = to_string_pretty(&Config::default(), PrettyConfig::default())
+        .expect("failed to serialize the default config");
+    top_level_fields(&default_ron)
+        .into_iter()
+        .map(|(key, _)| key.to_string())
+        .collect()
+}
+
+/// The lock delay in gravity ticks, replaced by `lock_delay_frames` and
+/// still read by converting it, see [`Config::convert_lock_delay`].
+const DEPRECATED_LOCK_DELAY: &str = "lock_delay";
+
+/// Reports an unknown key, suggesting the known key closest to it.
+fn unknown_key(key: &str, known_keys: &[String]) -> String {
+    let suggestion = known_keys
+        .iter()
+        .filter(|known| edit_distance(key, known) <= 3)
+        .min_by_key(|known| edit_distance(key, known))
+        .map(|known| format!(", did you mean `{known}`?"))
+        .unwrap_or_default();
+    format!("unknown key `{key}`{suggestion}")
+}
+
+/// One based line and column of a byte offset into `text`.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Number of single character insertions, deletions and substitutions that turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Byte offsets of the characters of a RON document that aren't comments,
+/// along with whether they are part of a string or character literal.
+fn code_chars(ron: &str) -> Vec<(usize, char, bool)> {
+    let mut code = Vec::new();
+    let mut chars = ron.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '/' if matches!(chars.peek(), Some((_, '/'))) => {
+                chars.find(|(_, c)| *c == '\n');
+            }
+            '/' if matches!(chars.peek(), Some((_, '*'))) => {
+                chars.next();
+                let mut previous = ' ';
+                chars.find(|(_, c)| {
+                    let end = previous == '*' && *c == '/';
+                    previous = *c;
+                    end
+                });
+            }
+            '"' | '\'' => {
+                code.push((offset, c, true));
+                let mut escaped = false;
+                for (offset, next) in chars.by_ref() {
+                    code.push((offset, next, true));
+                    if !escaped && next == c {
+                        break;
+                    }
+                    escaped = !escaped && next == '\\';
+                }
+            }
+            _ => code.push((offset, c, false)),
+        }
+    }
+    code
 }
 
 impl Default for Config {
     fn default() -> Self {
-        let config_path = find_config_file();
+        Config {
+            board_size: default_board_size(),
+            number_of_previews: default_number_of_previews(),
+            lock_delay_frames: default_lock_delay_frames(),
+            line_clear_delay: default_line_clear_delay(),
+            soft_drop_factor: default_soft_drop_factor(),
+            das_frames: default_das_frames(),
+            arr_frames: default_arr_frames(),
+            entry_delay: 0,
+            line_entry_delay: 0,
+            tick_delay: default_tick_delay(),
+            gravity: None,
+            zen_tick_delay: None,
+            zen_scoring: false,
+            zen_garbage: 0,
+            theme: default_theme(),
+            tetromino_color: BTreeMap::new(),
+            ghost_color: BTreeMap::new(),
+            border_color: BTreeMap::new(),
+            bag_type: default_bag_type(),
+            cell_style: default_cell_style(),
+            cell_texture: default_cell_texture(),
+            shape_marks: default_shape_marks(),
+            color_depth: None,
+            palettes: default_palettes(),
+            clear_animation: default_clear_animation(),
+            action_text: default_action_text(),
+            render_rate: default_render_rate(),
+        }
+    }
+}
+
+/// Where the configuration comes from: a config file and values that
+/// override it, given on the command line.
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    pub path: String,
+    /// keys and the values they are set to, applied in order
+    pub overrides: Vec<(String, String)>,
+}
 
-        // read config file
-        match std::fs::read_to_string(config_path) {
-            Ok(config) => from_str(&config).expect("Failed to parse config"),
-            Err(_) => Config {
-                board_size: default_board_size(),
-                number_of_previews: default_number_of_previews(),
-                lock_delay: default_lock_delay(),
-                tick_delay: default_tick_delay(),
-                tetromino_color: default_tetromino_color(),
-                ghost_color: default_ghost_color(),
-                border_color: default_border_color(),
-                bag_type: default_bag_type(),
-            },
+impl ConfigSource {
+    /// Reads the config file, using the defaults if there is none, and applies
+    /// the overrides. Returns warnings about deprecated keys with the config.
+    pub fn load(&self) -> Result<(Config, Vec<String>), ConfigError> {
+        let error = |problems| ConfigError {
+            path: self.path.clone(),
+            problems,
+        };
+        let (mut config, mut warnings) = match std::fs::read_to_string(&self.path) {
+            Ok(ron) => Config::from_ron(&ron).map_err(error)?,
+            Err(io_error) if io_error.kind() == std::io::ErrorKind::NotFound => {
+                (Config::default(), Vec::new())
+            }
+            Err(io_error) => return Err(error(vec![io_error.to_string()])),
+        };
+        if self.overrides.is_empty() {
+            return Ok((config, warnings));
+        }
+
+        let mut problems = Vec::new();
+        for (key, value) in &self.overrides {
+            if key == DEPRECATED_LOCK_DELAY {
+                match config.convert_lock_delay(value) {
+                    Ok(warning) => warnings.push(format!("{key}={value}: {warning}")),
+                    Err(problem) => problems.push(format!("{key}={value}: {problem}")),
+                }
+                continue;
+            }
+            match config.set(key, value) {
+                Ok(changed) => config = changed,
+                Err(problem) => problems.push(format!("{key}={value}: {problem}")),
+            }
+        }
+        if problems.is_empty() {
+            problems.extend(
+                config
+                    .validate()
+                    .into_iter()
+                    .map(|(field, problem)| format!("{field} (overridden): {problem}")),
+            );
+        }
+        if problems.is_empty() {
+            Ok((config, warnings))
+        } else {
+            Err(error(problems))
         }
     }
 }
 
-pub fn find_config_file() -> String {
+impl Default for ConfigSource {
+    fn default() -> Self {
+        Self {
+            path: find_config_file(),
+            overrides: Vec::new(),
+        }
+    }
+}
+
+/// Why the config file couldn't be used.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: String,
+    /// each problem found, most of them starting with their line and column
+    pub problems: Vec<String>,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid config file {}:", self.path)?;
+        for problem in &self.problems {
+            write!(f, "\n  {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Notices when the config file is changed so it can be reloaded while running.
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    path: String,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            modified: file_modified(path),
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Returns true if the config file was modified since the last call,
+    /// looks at the file at most once every [`WATCH_INTERVAL`].
+    pub fn changed(&mut self) -> bool {
+        if self.last_check.elapsed() < WATCH_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+        let modified = file_modified(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+fn file_modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+pub fn find_config_dir() -> String {
     match std::env::var("XDG_CONFIG_HOME") {
-        Ok(path) => path.to_owned() + &format!("/{PROJECT_NAME}/{CONFIG_FILE_NAME}"),
+        Ok(path) => path.to_owned() + &format!("/{PROJECT_NAME}/"),
         Err(_) => match std::env::var("HOME") {
-            Ok(path) => path + &format!("/.config/{PROJECT_NAME}/{CONFIG_FILE_NAME}"),
-            Err(_) => format!("./{PROJECT_NAME}/{CONFIG_FILE_NAME}"),
+            Ok(path) => path + &format!("/.config/{PROJECT_NAME}/"),
+            Err(_) => format!("./{PROJECT_NAME}/"),
         },
     }
 }
 
+pub fn find_config_file() -> String {
+    find_config_dir() + CONFIG_FILE_NAME
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -151,12 +815,31 @@ mod tests {
         let default_config = Config {
             board_size: default_board_size(),
             number_of_previews: default_number_of_previews(),
-            lock_delay: default_lock_delay(),
+            lock_delay_frames: default_lock_delay_frames(),
+            line_clear_delay: default_line_clear_delay(),
+            soft_drop_factor: default_soft_drop_factor(),
+            das_frames: default_das_frames(),
+            arr_frames: default_arr_frames(),
+            entry_delay: 0,
+            line_entry_delay: 0,
             tick_delay: default_tick_delay(),
-            tetromino_color: default_tetromino_color(),
-            ghost_color: default_ghost_color(),
-            border_color: default_border_color(),
+            gravity: None,
+            zen_tick_delay: None,
+            zen_scoring: false,
+            zen_garbage: 0,
+            theme: default_theme(),
+            tetromino_color: BTreeMap::new(),
+            ghost_color: BTreeMap::new(),
+            border_color: BTreeMap::new(),
             bag_type: default_bag_type(),
+            cell_style: default_cell_style(),
+            cell_texture: default_cell_texture(),
+            shape_marks: default_shape_marks(),
+            color_depth: None,
+            palettes: default_palettes(),
+            clear_animation: default_clear_animation(),
+            action_text: default_action_text(),
+            render_rate: default_render_rate(),
         };
 
         let config_text: String = std::fs::read_to_string("./examples/config.ron")
@@ -165,4 +848,150 @@ mod tests {
 
         assert_eq!(config, default_config);
     }
+
+    #[test]
+    fn test_write_config() {
+        let default_config: Config = from_str("()").expect("Failed to parse config");
+        assert_eq!(default_config, Config::default());
+        let config_text: String = std::fs::read_to_string("./examples/config.ron")
+            .expect("Example config file not found");
+
+        assert_eq!(
+            default_config
+                .to_ron_keeping_unknown("")
+                .expect("Failed to write config"),
+            config_text
+        );
+    }
+
+    #[test]
+    fn test_write_config_keeps_unknown_keys() {
+        let existing = "// my config
+            Config(
+                lock_delay_frames: 5,
+                my_key: [(1, \"a, )\"), /* ) */ (2, ')')],
+                other: Some(3) // trailing comment
+            )";
+        assert_eq!(
+            top_level_fields(existing),
+            vec![
+                ("lock_delay_frames", "5"),
+                ("my_key", "[(1, \"a, )\"), /* ) */ (2, ')')]"),
+                ("other", "Some(3)"),
+            ]
+        );
+
+        let config: Config = from_str(existing).expect("Failed to parse config");
+        let written = config
+            .to_ron_keeping_unknown(existing)
+            .expect("Failed to write config");
+        assert!(written.contains("    lock_delay_frames: 5,\n"));
+        assert!(written.contains("    my_key: [(1, \"a, )\"), /* ) */ (2, ')')],\n"));
+        assert!(written.contains("    other: Some(3),\n"));
+        assert_eq!(
+            from_str::<Config>(&written).expect("Failed to reparse"),
+            config
+        );
+    }
+
+    #[test]
+    fn test_tick_delay_for_level() {
+        let config: Config = from_str("(tick_delay: {2: 500, 5: 300})").expect("invalid config");
+        assert_eq!(config.tick_delay_for_level(1), 500);
+        assert_eq!(config.tick_delay_for_level(4), 500);
+        assert_eq!(config.tick_delay_for_level(5), 300);
+        assert_eq!(config.tick_delay_for_level(99), 300);
+    }
+
+    #[test]
+    fn test_from_ron_reports_problems() {
+        let problems = Config::from_ron("(board_size: (10, 20)\n  lock_delay_frames: 3)")
+            .expect_err("missing comma accepted");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("line 2, column 3:"));
+
+        let problems = Config::from_ron(
+            "(\n    board_size: (3, 20),\n    lock_delay_frmes: 0,\n    theme: \"no-such-theme\",\n)",
+        )
+        .expect_err("invalid config accepted");
+        assert!(problems[0].starts_with("line 2, column 5: board_size: "));
+        assert_eq!(
+            problems[1],
+            "line 3, column 5: unknown key `lock_delay_frmes`, did you mean `lock_delay_frames`?"
+        );
+        assert!(problems[2]
+            .starts_with("line 4, column 5: theme: `no-such-theme` is neither a bundled theme"));
+        assert_eq!(problems.len(), 3);
+
+        assert!(Config::from_ron("(lock_delay: soon)").is_err());
+        assert_eq!(Config::from_ron("()"), Ok((Config::default(), Vec::new())));
+    }
+
+    #[test]
+    fn test_load_old_config() {
+        // the old lock delay counted ticks of 800 ms at level 1, 3 of them are 144 frames
+        let source = ConfigSource {
+            path: "./examples/old_config.ron".to_string(),
+            overrides: Vec::new(),
+        };
+        let (config, warnings) = source.load().expect("failed to load the old example");
+        assert_eq!(config.lock_delay_frames, 144);
+        assert_eq!(
+            warnings,
+            vec![
+                "line 6, column 5: `lock_delay` is deprecated, its 3 ticks of 800 ms were read \
+                 as `lock_delay_frames: 144`, set that instead"
+                    .to_string()
+            ]
+        );
+
+        let source = ConfigSource {
+            overrides: vec![("lock_delay".to_string(), "1".to_string())],
+            ..source
+        };
+        let (config, warnings) = source.load().expect("failed to override the lock delay");
+        assert_eq!(config.lock_delay_frames, 48);
+        assert_eq!(warnings.len(), 2);
+
+        // the new key wins over the old one
+        let (config, warnings) =
+            Config::from_ron("(lock_delay: 3, lock_delay_frames: 20)").expect("invalid config");
+        assert_eq!(config.lock_delay_frames, 20);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Config::default().validate().is_empty());
+        let config = Config {
+            number_of_previews: 8,
+            lock_delay_frames: 0,
+            tick_delay: BTreeMap::new(),
+            ..Config::default()
+        };
+        let fields = config
+            .validate()
+            .into_iter()
+            .map(|(field, _)| field)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            ["number_of_previews", "lock_delay_frames", "tick_delay"]
+        );
+    }
+
+    #[test]
+    fn test_set() {
+        let config = Config::default()
+            .set("lock_delay_frames", "5")
+            .expect("failed to set lock delay");
+        assert_eq!(config.lock_delay_frames, 5);
+        assert_eq!(config.board_size, Config::default().board_size);
+        assert_eq!(
+            config.set("lock_delay_frmes", "5"),
+            Err("unknown key `lock_delay_frmes`, did you mean `lock_delay_frames`?".to_string())
+        );
+        assert!(config.set("board_size", "(10, 20").is_err());
+        assert_eq!("Fourteen".parse(), Ok(BagType::Fourteen));
+    }
 }
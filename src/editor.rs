@@ -0,0 +1,332 @@
+use crate::board::{BoardSetup, Cell};
+use crate::grid::Tile;
+use crate::tetromino::TetrominoShape;
+use crate::theme::Theme;
+
+/// Most edits that can be undone.
+const MAX_UNDO: usize = 100;
+
+/// What typed shape letters and erasing change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorFocus {
+    /// letters pick the brush, erasing empties the cell under the cursor
+    Board,
+    /// letters add pieces to the end of the queue, erasing removes the last one
+    Queue,
+    /// a letter sets the held piece, erasing empties it
+    Hold,
+}
+
+impl EditorFocus {
+    pub const ALL: [EditorFocus; 3] = [EditorFocus::Board, EditorFocus::Queue, EditorFocus::Hold];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EditorFocus::Board => "board",
+            EditorFocus::Queue => "queue",
+            EditorFocus::Hold => "hold",
+        }
+    }
+}
+
+/// Everything an edit can change.
+#[derive(Debug, Clone, PartialEq)]
+struct EditorState {
+    grid: Vec<Vec<Cell>>,
+    queue: Vec<TetrominoShape>,
+    hold: Option<TetrominoShape>,
+}
+
+/// State of the board editor, where a board is painted cell by cell to play from.
+#[derive(Debug)]
+pub struct Editor {
+    /// cells of the board, top row first
+    pub grid: Vec<Vec<Cell>>,
+    /// pieces in the order they come, the first one falls first
+    pub queue: Vec<TetrominoShape>,
+    pub hold: Option<TetrominoShape>,
+    /// cell the keyboard paints, as (x, y) from the top left
+    pub cursor: (usize, usize),
+    /// what painting puts in a cell
+    pub brush: Cell,
+    pub focus: EditorFocus,
+    undo: Vec<EditorState>,
+    redo: Vec<EditorState>,
+}
+
+impl Editor {
+    /// Starts editing `setup`, or an empty board, on a board of `board_size`.
+    pub fn new(setup: Option<&BoardSetup>, (width, height): (usize, usize)) -> Editor {
+        let mut grid = vec![vec![Cell::Empty; width]; height];
+        let (mut queue, mut hold) = (Vec::new(), None);
+        if let Some(setup) = setup {
+            let top = height - setup.rows.len().min(height);
+            for (y, row) in setup.rows.iter().rev().take(height).rev().enumerate() {
+                for (x, c) in row.chars().take(width).enumerate() {
+                    grid[top + y][x] = Cell::from_char(c).unwrap_or(Cell::Empty);
+                }
+            }
+            queue.clone_from(&setup.queue);
+            hold = setup.hold;
+        }
+        Editor {
+            grid,
+            queue,
+            hold,
+            cursor: (width / 2, height - 1),
+            brush: Cell::Garbage,
+            focus: EditorFocus::Board,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// The board to play, random bags follow the queue.
+    pub fn setup(&self) -> BoardSetup {
+        BoardSetup {
+            rows: self
+                .grid
+                .iter()
+                .skip_while(|row| row.iter().all(Cell::is_empty))
+                .map(|row| row.iter().map(Cell::to_char).collect())
+                .collect(),
+            queue: self.queue.clone(),
+            hold: self.hold,
+            then_random: true,
+        }
+    }
+
+    pub fn move_cursor(&mut self, dx: isize, dy: isize) {
+        let (x, y) = self.cursor;
+        self.cursor = (
+            x.saturating_add_signed(dx).min(self.grid[0].len() - 1),
+            y.saturating_add_signed(dy).min(self.grid.len() - 1),
+        );
+    }
+
+    pub fn cycle_focus(&mut self) {
+        let index = EditorFocus::ALL
+            .iter()
+            .position(|focus| *focus == self.focus)
+            .unwrap_or(0);
+        self.focus = EditorFocus::ALL[(index + 1) % EditorFocus::ALL.len()];
+    }
+
+    /// Uses a typed shape letter for whatever has the focus.
+    pub fn type_shape(&mut self, shape: TetrominoShape) {
+        match self.focus {
+            EditorFocus::Board => self.brush = Cell::Occupied(shape),
+            EditorFocus::Queue => self.edit(|state| state.queue.push(shape)),
+            EditorFocus::Hold => self.edit(|state| state.hold = Some(shape)),
+        }
+    }
+
+    /// Empties the cell under the cursor, or removes a piece from what has the focus.
+    pub fn erase(&mut self) {
+        match self.focus {
+            EditorFocus::Board => self.paint_cursor(Cell::Empty),
+            EditorFocus::Queue => self.edit(|state| {
+                state.queue.pop();
+            }),
+            EditorFocus::Hold => self.edit(|state| state.hold = None),
+        }
+    }
+
+    /// Puts `cell` under the cursor.
+    pub fn paint_cursor(&mut self, cell: Cell) {
+        let (x, y) = self.cursor;
+        self.edit(|state| state.grid[y][x] = cell);
+    }
+
+    /// Empties the whole board.
+    pub fn clear(&mut self) {
+        self.edit(|state| {
+            state
+                .grid
+                .iter_mut()
+                .flatten()
+                .for_each(|cell| *cell = Cell::Empty)
+        });
+    }
+
+    /// Starts a stroke of painting, which is undone as a whole.
+    pub fn begin_stroke(&mut self) {
+        self.undo.push(self.state());
+    }
+
+    /// Puts `cell` at `(x, y)` as part of the current stroke.
+    pub fn paint(&mut self, (x, y): (usize, usize), cell: Cell) {
+        if let Some(target) = self.grid.get_mut(y).and_then(|row| row.get_mut(x)) {
+            *target = cell;
+            self.cursor = (x, y);
+        }
+    }
+
+    /// Ends a stroke, forgetting it if it changed nothing.
+    pub fn end_stroke(&mut self) {
+        if let Some(before) = self.undo.pop() {
+            if before != self.state() {
+                self.checkpoint(before);
+            }
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(state) = self.undo.pop() {
+            self.redo.push(self.state());
+            self.restore(state);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(state) = self.redo.pop() {
+            self.undo.push(self.state());
+            self.restore(state);
+        }
+    }
+
+    /// Lines for the panel beside the board.
+    pub fn lines(&self) -> Vec<String> {
+        let brush = match self.brush {
+            Cell::Empty => "eraser".to_string(),
+            Cell::Garbage => "garbage".to_string(),
+            cell => cell.to_char().to_string(),
+        };
+        let letters = |shapes: &[TetrominoShape]| match shapes {
+            [] => "-".to_string(),
+            shapes => shapes
+                .iter()
+                .map(|shape| Cell::Occupied(*shape).to_char())
+                .collect(),
+        };
+        vec![
+            format!("brush: {brush}"),
+            format!("editing: {}", self.focus.name()),
+            format!("queue: {}", letters(&self.queue)),
+            format!("hold: {}", letters(self.hold.as_slice())),
+            String::new(),
+            "arrows: move, space: paint".to_string(),
+            "x: erase, n: clear".to_string(),
+            "ijlostz g e: brush".to_string(),
+            "mouse: left paints, right erases".to_string(),
+            "tab: edit the queue, hold".to_string(),
+            "ctrl+z, ctrl+y: undo, redo".to_string(),
+            "enter: play, esc: menu".to_string(),
+        ]
+    }
+
+    /// Colors of every cell of the board in `theme` like `Board::cells`, with
+    /// the cursor drawn as the ghost of the brush.
+    pub fn cells(&self, theme: &Theme) -> Vec<Vec<Option<Tile>>> {
+        let (width, height) = (self.grid[0].len(), self.grid.len());
+        let mut cells = vec![vec![None; width + 2]; height + 1];
+        let border = Some(Tile::plain(theme.panel_border));
+        for row in cells.iter_mut().take(height) {
+            row[0] = border;
+            row[width + 1] = border;
+        }
+        cells[height] = vec![border; width + 2];
+        for (y, row) in self.grid.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                cells[y][x + 1] = match cell {
+                    Cell::Empty => None,
+                    Cell::Occupied(shape) => {
+                        Some(Tile::piece(theme.tetromino_color[shape], *shape))
+                    }
+                    Cell::Garbage => Some(Tile::plain(theme.garbage)),
+                };
+            }
+        }
+        let (x, y) = self.cursor;
+        cells[y][x + 1] = Some(match self.brush {
+            Cell::Occupied(shape) => Tile::piece(theme.ghost_color[&shape], shape),
+            Cell::Empty | Cell::Garbage => Tile::plain(theme.highlight),
+        });
+        cells
+    }
+
+    fn state(&self) -> EditorState {
+        EditorState {
+            grid: self.grid.clone(),
+            queue: self.queue.clone(),
+            hold: self.hold,
+        }
+    }
+
+    fn restore(&mut self, state: EditorState) {
+        self.grid = state.grid;
+        self.queue = state.queue;
+        self.hold = state.hold;
+    }
+
+    /// Remembers the state to undo back to, and forgets what was undone.
+    fn checkpoint(&mut self, state: EditorState) {
+        self.undo.push(state);
+        if self.undo.len() > MAX_UNDO {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Changes the state as one step that can be undone, if it changes anything.
+    fn edit(&mut self, change: impl FnOnce(&mut EditorState)) {
+        let mut state = self.state();
+        change(&mut state);
+        if state != self.state() {
+            self.checkpoint(self.state());
+            self.restore(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_redo() {
+        let mut editor = Editor::new(None, (4, 3));
+        editor.begin_stroke();
+        editor.paint((0, 2), Cell::Garbage);
+        editor.paint((1, 2), Cell::Garbage);
+        editor.end_stroke();
+        editor.focus = EditorFocus::Queue;
+        editor.type_shape(TetrominoShape::T);
+        // a stroke that changes nothing can't be undone
+        editor.begin_stroke();
+        editor.paint((0, 2), Cell::Garbage);
+        editor.end_stroke();
+        assert_eq!(editor.setup().rows, ["##.."]);
+        assert_eq!(editor.setup().queue, [TetrominoShape::T]);
+
+        editor.undo();
+        assert!(editor.queue.is_empty());
+        editor.undo();
+        assert!(editor.setup().rows.is_empty());
+        editor.undo();
+        editor.redo();
+        assert_eq!(editor.setup().rows, ["##.."]);
+        // a new edit forgets what was undone
+        editor.focus = EditorFocus::Board;
+        editor.move_cursor(3, 0);
+        editor.paint_cursor(Cell::Garbage);
+        editor.redo();
+        assert!(editor.queue.is_empty());
+    }
+
+    #[test]
+    fn test_edit_setup() {
+        let setup = BoardSetup {
+            rows: vec!["T...".to_string(), "TT.#".to_string()],
+            queue: vec![TetrominoShape::I],
+            hold: Some(TetrominoShape::O),
+            then_random: true,
+        };
+        let mut editor = Editor::new(Some(&setup), (4, 3));
+        assert_eq!(editor.setup(), setup);
+        editor.move_cursor(-10, 10);
+        assert_eq!(editor.cursor, (0, 2));
+        editor.paint_cursor(Cell::Occupied(TetrominoShape::Z));
+        assert_eq!(editor.setup().rows, ["T...", "ZT.#"]);
+    }
+}
@@ -0,0 +1,684 @@
+use crate::board::{Board, BoardSetup, Cell};
+use crate::tetromino::TetrominoShape;
+
+const PREFIX: &str = "v115@";
+/// Digits of the numbers fumen strings are written in, least significant first.
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// Characters of escaped comments, a comment digit is an index into it.
+const COMMENT_TABLE: &[u8] =
+    b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const COMMENT_BASE: usize = 96;
+const MAX_COMMENT_LENGTH: usize = 4095;
+pub const FIELD_WIDTH: usize = 10;
+/// Rows of a field above its floor.
+pub const FIELD_HEIGHT: usize = 23;
+/// Cells of a field, with the row of garbage waiting below the floor.
+const FIELD_BLOCKS: usize = (FIELD_HEIGHT + 1) * FIELD_WIDTH;
+/// Fumen's numbers of the shapes, 0 is an empty cell and 8 garbage.
+const SHAPE_NUMBERS: [(TetrominoShape, usize); 7] = [
+    (TetrominoShape::I, 1),
+    (TetrominoShape::L, 2),
+    (TetrominoShape::O, 3),
+    (TetrominoShape::Z, 4),
+    (TetrominoShape::T, 5),
+    (TetrominoShape::J, 6),
+    (TetrominoShape::S, 7),
+];
+const GARBAGE: usize = 8;
+/// Fumen's numbers of the rotations, by rotation index (spawn, right, reverse, left).
+const ROTATION_NUMBERS: [usize; 4] = [2, 1, 0, 3];
+
+/// A piece placed on a page, `x` and `y` being the cell it turns around,
+/// counted from the left and from the floor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FumenPiece {
+    pub shape: TetrominoShape,
+    /// 0 where it spawns, 1 turned clockwise, 2 upside down, 3 turned counterclockwise
+    pub rotation: usize,
+    pub x: isize,
+    pub y: isize,
+}
+
+impl FumenPiece {
+    /// Cells of the piece, counted from the left and from the floor.
+    pub fn cells(&self) -> [(isize, isize); 4] {
+        shape_offsets(self.shape, self.rotation).map(|(dx, dy)| (self.x + dx, self.y + dy))
+    }
+
+    /// The number of the position of the piece, fumen doesn't place some
+    /// shapes around the same cell as the super rotation system.
+    fn coordinate(&self) -> usize {
+        let (dx, dy) = coordinate_shift(self.shape, self.rotation);
+        let (x, y) = (self.x - dx, self.y - dy);
+        (FIELD_HEIGHT as isize - 1 - y) as usize * FIELD_WIDTH + x as usize
+    }
+
+    fn from_coordinate(shape: TetrominoShape, rotation: usize, coordinate: usize) -> FumenPiece {
+        let (dx, dy) = coordinate_shift(shape, rotation);
+        FumenPiece {
+            shape,
+            rotation,
+            x: (coordinate % FIELD_WIDTH) as isize + dx,
+            y: FIELD_HEIGHT as isize - 1 - (coordinate / FIELD_WIDTH) as isize + dy,
+        }
+    }
+
+    fn fits(&self) -> bool {
+        self.cells().iter().all(|(x, y)| {
+            (0..FIELD_WIDTH as isize).contains(x) && (0..FIELD_HEIGHT as isize).contains(y)
+        })
+    }
+}
+
+/// A page of a fumen diagram.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FumenPage {
+    /// rows of the field from the top, [`FIELD_HEIGHT`] of them, without the piece
+    pub field: Vec<Vec<Cell>>,
+    pub piece: Option<FumenPiece>,
+    /// quiz pages list the hold and the queue in it, as `#Q=[hold](current)next`
+    pub comment: String,
+}
+
+impl FumenPage {
+    /// The stack, falling piece, hold and visible queue of `board`.
+    pub fn from_board(board: &Board) -> Result<FumenPage, String> {
+        let grid = board.grid();
+        let height = grid.len();
+        if grid.first().map_or(0, Vec::len) != FIELD_WIDTH {
+            return Err(format!(
+                "fumen fields are {FIELD_WIDTH} cells wide, the board is {}",
+                board.config().board_size.0
+            ));
+        }
+        let hidden = height.saturating_sub(FIELD_HEIGHT);
+        if grid[..hidden].iter().flatten().any(|cell| !cell.is_empty()) {
+            return Err(format!(
+                "the stack is higher than the {FIELD_HEIGHT} rows of a fumen field"
+            ));
+        }
+        let mut field = vec![vec![Cell::Empty; FIELD_WIDTH]; FIELD_HEIGHT.saturating_sub(height)];
+        field.extend(grid[hidden..].iter().cloned());
+
+        // the falling piece, if it is inside the field
+        let piece = board.current_piece().and_then(|tetromino| {
+            let cells = tetromino.get_full_position().ok()?;
+            let (x, row) = cells[0];
+            let y = height as isize - 1 - row as isize;
+            let shape = tetromino.get_shape();
+            let rotation = tetromino.get_rotation_index();
+            let mut expected =
+                cells.map(|(x, row)| (x as isize, height as isize - 1 - row as isize));
+            expected.sort();
+            shape_offsets(shape, rotation)
+                .into_iter()
+                .map(|(dx, dy)| FumenPiece {
+                    shape,
+                    rotation,
+                    x: x as isize - dx,
+                    y: y - dy,
+                })
+                .find(|piece| {
+                    let mut cells = piece.cells();
+                    cells.sort();
+                    cells == expected
+                })
+                .filter(FumenPiece::fits)
+        });
+
+        let letter = |shape: Option<TetrominoShape>| shape.map_or(String::new(), shape_letter);
+        let next = (1..=board.config().number_of_previews)
+            .map_while(|n| board.calc_next_piece(n))
+            .map(shape_letter)
+            .collect::<String>();
+        let comment = format!(
+            "#Q=[{}]({}){next}",
+            letter(board.get_held_tetromino()),
+            letter(board.current_piece().map(|tetromino| tetromino.get_shape())),
+        );
+        Ok(FumenPage {
+            field,
+            piece,
+            comment,
+        })
+    }
+
+    /// A board to play the page on. The pieces of its quiz, or its piece,
+    /// come first and spawn at the top, random bags follow them.
+    pub fn setup(&self) -> BoardSetup {
+        let (hold, queue) = parse_quiz(&self.comment)
+            .unwrap_or((None, self.piece.iter().map(|piece| piece.shape).collect()));
+        BoardSetup {
+            rows: self
+                .field
+                .iter()
+                .skip_while(|row| row.iter().all(Cell::is_empty))
+                .map(|row| row.iter().map(Cell::to_char).collect())
+                .collect(),
+            queue,
+            hold,
+            then_random: true,
+        }
+    }
+}
+
+/// Reads every page of a fumen string, which can be part of a link.
+pub fn decode(text: &str) -> Result<Vec<FumenPage>, String> {
+    let start = text
+        .find(PREFIX)
+        .ok_or_else(|| format!("not a fumen, it has no `{PREFIX}`"))?;
+    let digits = text[start + PREFIX.len()..]
+        .chars()
+        .filter(|c| *c != '?' && !c.is_whitespace())
+        .map(|c| {
+            ALPHABET
+                .iter()
+                .position(|digit| char::from(*digit) == c)
+                .ok_or_else(|| format!("`{c}` is not a fumen character"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut values = Values { digits, index: 0 };
+
+    let mut pages = Vec::new();
+    let mut field = [0; FIELD_BLOCKS];
+    let mut repeat = 0;
+    let mut comment = String::new();
+    while !values.is_empty() {
+        // the changes to the field of the last page, unless it is repeated
+        if repeat > 0 {
+            repeat -= 1;
+        } else {
+            let mut index = 0;
+            let mut changed = true;
+            while index < FIELD_BLOCKS {
+                let value = values.poll(2)?;
+                let (diff, count) = (value / FIELD_BLOCKS, value % FIELD_BLOCKS + 1);
+                if index == 0 && diff == 8 && count == FIELD_BLOCKS {
+                    changed = false;
+                }
+                if index + count > FIELD_BLOCKS {
+                    return Err("a field has more than 240 cells".to_string());
+                }
+                for cell in &mut field[index..index + count] {
+                    *cell = (*cell + diff)
+                        .checked_sub(8)
+                        .filter(|cell| *cell <= GARBAGE)
+                        .ok_or("a field has a cell that is not a piece")?;
+                }
+                index += count;
+            }
+            if !changed {
+                repeat = values.poll(1)?;
+            }
+        }
+
+        let action = values.poll(3)?;
+        let shape = action % 8;
+        let rotation = ROTATION_NUMBERS[action / 8 % 4];
+        let coordinate = action / 32 % FIELD_BLOCKS;
+        let flags = action / 32 / FIELD_BLOCKS;
+        let (rise, mirror, has_comment, lock) = (
+            flags & 1 == 1,
+            flags >> 1 & 1 == 1,
+            flags >> 3 & 1 == 1,
+            flags >> 4 & 1 == 0,
+        );
+        if has_comment {
+            comment = decode_comment(&mut values)?;
+        }
+        let piece = SHAPE_NUMBERS
+            .iter()
+            .find(|(_, number)| *number == shape)
+            .map(|(shape, _)| FumenPiece::from_coordinate(*shape, rotation, coordinate));
+        if piece.is_some_and(|piece| !piece.fits()) {
+            return Err(format!(
+                "page {} has a piece outside the field",
+                pages.len() + 1
+            ));
+        }
+        pages.push(FumenPage {
+            field: field[..FIELD_HEIGHT * FIELD_WIDTH]
+                .chunks(FIELD_WIDTH)
+                .map(|row| row.iter().map(|number| cell(*number)).collect())
+                .collect(),
+            piece,
+            comment: comment.clone(),
+        });
+
+        // what the next page starts from
+        if lock {
+            if let Some(piece) = piece {
+                place(&mut field, &piece);
+            }
+            clear_lines(&mut field);
+            if rise {
+                field.copy_within(FIELD_WIDTH.., 0);
+                field[FIELD_HEIGHT * FIELD_WIDTH..].fill(0);
+            }
+            if mirror {
+                for row in field[..FIELD_HEIGHT * FIELD_WIDTH].chunks_mut(FIELD_WIDTH) {
+                    row.reverse();
+                }
+            }
+        }
+    }
+    if pages.is_empty() {
+        return Err("the fumen has no pages".to_string());
+    }
+    Ok(pages)
+}
+
+/// Writes pages as a fumen string. Each page's piece locks into the field of
+/// the next page.
+pub fn encode(pages: &[FumenPage]) -> String {
+    let mut values = Values::default();
+    let mut previous = [0; FIELD_BLOCKS];
+    let mut previous_comment = String::new();
+    // where the count of repeated fields is, and the count
+    let mut repeated: Option<(usize, usize)> = None;
+    for (i, page) in pages.iter().enumerate() {
+        let mut field = [0; FIELD_BLOCKS];
+        for (y, row) in page.field.iter().take(FIELD_HEIGHT).enumerate() {
+            for (x, cell) in row.iter().take(FIELD_WIDTH).enumerate() {
+                field[y * FIELD_WIDTH + x] = number(cell);
+            }
+        }
+
+        if field == previous {
+            match &mut repeated {
+                Some((index, count)) if *count < ALPHABET.len() - 1 => {
+                    *count += 1;
+                    values.digits[*index] = *count;
+                }
+                _ => {
+                    values.push(8 * FIELD_BLOCKS + FIELD_BLOCKS - 1, 2);
+                    repeated = Some((values.digits.len(), 0));
+                    values.push(0, 1);
+                }
+            }
+        } else {
+            repeated = None;
+            // runs of cells that changed the same way
+            let diffs = field
+                .iter()
+                .zip(previous)
+                .map(|(cell, previous)| cell + 8 - previous)
+                .collect::<Vec<_>>();
+            for run in diffs.chunk_by(|a, b| a == b) {
+                values.push(run[0] * FIELD_BLOCKS + run.len() - 1, 2);
+            }
+        }
+
+        let has_comment = page.comment != previous_comment;
+        let mut flags = 0;
+        if i == 0 {
+            // guideline colors
+            flags |= 1 << 2;
+        }
+        if has_comment {
+            flags |= 1 << 3;
+        }
+        let (shape, rotation, coordinate) = page.piece.map_or((0, 0, 0), |piece| {
+            (
+                shape_number(piece.shape),
+                ROTATION_NUMBERS[piece.rotation],
+                piece.coordinate(),
+            )
+        });
+        values.push(
+            ((flags * FIELD_BLOCKS + coordinate) * 4 + rotation) * 8 + shape,
+            3,
+        );
+        if has_comment {
+            encode_comment(&mut values, &page.comment);
+            previous_comment = page.comment.clone();
+        }
+
+        if let Some(piece) = page.piece.filter(FumenPiece::fits) {
+            place(&mut field, &piece);
+        }
+        clear_lines(&mut field);
+        previous = field;
+    }
+
+    let data = values
+        .digits
+        .iter()
+        .map(|digit| char::from(ALPHABET[*digit]))
+        .collect::<Vec<_>>();
+    // like the fumen editor, break the data up with question marks
+    let (head, tail) = data.split_at(data.len().min(42));
+    let mut text = PREFIX.to_string() + &head.iter().collect::<String>();
+    for chunk in tail.chunks(47) {
+        text.push('?');
+        text.extend(chunk);
+    }
+    text
+}
+
+/// Base 64 numbers, least significant digit first.
+#[derive(Debug, Default)]
+struct Values {
+    digits: Vec<usize>,
+    index: usize,
+}
+
+impl Values {
+    fn is_empty(&self) -> bool {
+        self.index >= self.digits.len()
+    }
+
+    fn poll(&mut self, length: usize) -> Result<usize, String> {
+        let digits = self
+            .digits
+            .get(self.index..self.index + length)
+            .ok_or("the fumen ends too early")?;
+        self.index += length;
+        Ok(digits
+            .iter()
+            .rev()
+            .fold(0, |value, digit| value * 64 + digit))
+    }
+
+    fn push(&mut self, mut value: usize, length: usize) {
+        for _ in 0..length {
+            self.digits.push(value % 64);
+            value /= 64;
+        }
+    }
+}
+
+fn decode_comment(values: &mut Values) -> Result<String, String> {
+    let length = values.poll(2)?;
+    let mut escaped = String::new();
+    for chunk in 0..length.div_ceil(4) {
+        let mut value = values.poll(5)?;
+        for _ in 0..(length - chunk * 4).min(4) {
+            let c = COMMENT_TABLE
+                .get(value % COMMENT_BASE)
+                .ok_or("a comment has a character that is not in the comment table")?;
+            escaped.push(char::from(*c));
+            value /= COMMENT_BASE;
+        }
+    }
+    Ok(unescape(&escaped))
+}
+
+fn encode_comment(values: &mut Values, comment: &str) {
+    let escaped = escape(comment).into_bytes();
+    let escaped = &escaped[..escaped.len().min(MAX_COMMENT_LENGTH)];
+    values.push(escaped.len(), 2);
+    for chunk in escaped.chunks(4) {
+        let value = chunk.iter().rev().fold(0, |value, c| {
+            let digit = COMMENT_TABLE.iter().position(|t| t == c).unwrap_or(0);
+            value * COMMENT_BASE + digit
+        });
+        values.push(value, 5);
+    }
+}
+
+/// Escapes a comment like javascript's `escape`, which fumen comments are written with.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for unit in text.encode_utf16() {
+        match char::from_u32(u32::from(unit)) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => escaped.push(c),
+            _ if unit < 0x100 => escaped.push_str(&format!("%{unit:02X}")),
+            _ => escaped.push_str(&format!("%u{unit:04X}")),
+        }
+    }
+    escaped
+}
+
+/// Reverses [`escape`], leaving what isn't a valid escape as it is.
+fn unescape(text: &str) -> String {
+    let mut units = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let hex = |digits: &str| u16::from_str_radix(digits, 16).ok();
+        let (unit, length) = match (rest.get(1..2), rest.get(2..6), rest.get(1..3)) {
+            _ if c != '%' => (None, 0),
+            (Some("u"), Some(digits), _) if hex(digits).is_some() => (hex(digits), 6),
+            (_, _, Some(digits)) if hex(digits).is_some() => (hex(digits), 3),
+            _ => (None, 0),
+        };
+        match unit {
+            Some(unit) => {
+                units.push(unit);
+                rest = &rest[length..];
+            }
+            None => {
+                let mut buffer = [0; 2];
+                units.extend_from_slice(c.encode_utf16(&mut buffer));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+/// The hold and the pieces of a quiz comment, `#Q=[hold](current)next`.
+fn parse_quiz(comment: &str) -> Option<(Option<TetrominoShape>, Vec<TetrominoShape>)> {
+    let quiz = comment.strip_prefix("#Q=[")?;
+    let (hold, quiz) = quiz.split_once("](")?;
+    let (current, next) = quiz.split_once(')')?;
+    let shape = |c: char| match Cell::from_char(c) {
+        Some(Cell::Occupied(shape)) => Some(shape),
+        _ => None,
+    };
+    let hold = hold.chars().next().and_then(shape);
+    let queue = current
+        .chars()
+        .chain(next.chars().take_while(char::is_ascii_alphabetic))
+        .map_while(shape)
+        .collect();
+    Some((hold, queue))
+}
+
+fn shape_letter(shape: TetrominoShape) -> String {
+    Cell::Occupied(shape).to_char().to_string()
+}
+
+fn shape_number(shape: TetrominoShape) -> usize {
+    SHAPE_NUMBERS
+        .iter()
+        .find(|(other, _)| *other == shape)
+        .map_or(0, |(_, number)| *number)
+}
+
+fn number(cell: &Cell) -> usize {
+    match cell {
+        Cell::Empty => 0,
+        Cell::Occupied(shape) => shape_number(*shape),
+        Cell::Garbage => GARBAGE,
+    }
+}
+
+fn cell(number: usize) -> Cell {
+    SHAPE_NUMBERS
+        .iter()
+        .find(|(_, other)| *other == number)
+        .map_or(
+            if number == 0 {
+                Cell::Empty
+            } else {
+                Cell::Garbage
+            },
+            |(shape, _)| Cell::Occupied(*shape),
+        )
+}
+
+/// Cells of a shape around the cell it turns around, y pointing up.
+fn shape_offsets(shape: TetrominoShape, rotation: usize) -> [(isize, isize); 4] {
+    let spawn = match shape {
+        TetrominoShape::I => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+        TetrominoShape::T => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+        TetrominoShape::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        TetrominoShape::L => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+        TetrominoShape::J => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+        TetrominoShape::S => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+        TetrominoShape::Z => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+    };
+    spawn.map(|(x, y)| match rotation % 4 {
+        0 => (x, y),
+        1 => (y, -x),
+        2 => (-x, -y),
+        _ => (-y, x),
+    })
+}
+
+/// How far the cell a piece turns around is from the position fumen writes.
+fn coordinate_shift(shape: TetrominoShape, rotation: usize) -> (isize, isize) {
+    match (shape, rotation) {
+        (TetrominoShape::O, 3) => (1, -1),
+        (TetrominoShape::O, 2) | (TetrominoShape::I, 2) | (TetrominoShape::Z, 3) => (1, 0),
+        (TetrominoShape::O, 0)
+        | (TetrominoShape::I, 3)
+        | (TetrominoShape::S, 0)
+        | (TetrominoShape::Z, 0) => (0, -1),
+        (TetrominoShape::S, 1) => (-1, 0),
+        _ => (0, 0),
+    }
+}
+
+/// Puts a piece that fits into a field of fumen numbers.
+fn place(field: &mut [usize; FIELD_BLOCKS], piece: &FumenPiece) {
+    for (x, y) in piece.cells() {
+        field[(FIELD_HEIGHT - 1 - y as usize) * FIELD_WIDTH + x as usize] =
+            shape_number(piece.shape);
+    }
+}
+
+/// Removes the full rows of a field of fumen numbers, leaving its garbage row.
+fn clear_lines(field: &mut [usize; FIELD_BLOCKS]) {
+    let rows = field[..FIELD_HEIGHT * FIELD_WIDTH]
+        .chunks(FIELD_WIDTH)
+        .filter(|row| row.contains(&0))
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+    let cleared = FIELD_HEIGHT * FIELD_WIDTH - rows.len();
+    field[..cleared].fill(0);
+    field[cleared..FIELD_HEIGHT * FIELD_WIDTH].copy_from_slice(&rows);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_empty_field() {
+        let pages = decode("https://fumen.zui.jp/?v115@vhAAgH").expect("failed to decode");
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].field.iter().flatten().all(Cell::is_empty));
+        assert_eq!(pages[0].piece, None);
+        assert_eq!(encode(&pages), "v115@vhAAgH");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut field = vec![vec![Cell::Empty; FIELD_WIDTH]; FIELD_HEIGHT];
+        field[22] = "IIIIJJJ#..".chars().filter_map(Cell::from_char).collect();
+        field[21] = "########..".chars().filter_map(Cell::from_char).collect();
+        let page = FumenPage {
+            field,
+            piece: Some(FumenPiece {
+                shape: TetrominoShape::O,
+                rotation: 0,
+                x: 8,
+                y: 0,
+            }),
+            comment: "#Q=[T](O)SZ".to_string(),
+        };
+        // the O fills the gap, the second page starts from an empty field
+        let next = FumenPage {
+            field: vec![vec![Cell::Empty; FIELD_WIDTH]; FIELD_HEIGHT],
+            piece: None,
+            comment: "done, 100%".to_string(),
+        };
+        let pages = vec![page.clone(), next];
+        let text = encode(&pages);
+        assert_eq!(decode(&text), Ok(pages));
+
+        let setup = page.setup();
+        assert_eq!(setup.rows, ["########..", "IIIIJJJ#.."]);
+        assert_eq!(setup.hold, Some(TetrominoShape::T));
+        assert_eq!(
+            setup.queue,
+            [TetrominoShape::O, TetrominoShape::S, TetrominoShape::Z]
+        );
+    }
+
+    #[test]
+    fn test_rotated_pieces() {
+        // O, I, S and Z pieces in each of the rotations fumen writes shifted,
+        // locking into a stack that clears two lines after the sixth page
+        let pages = decode("v115@vhKTJJZlBplBDrBLsBbtBhwB3mBctBvpB0aB").expect("failed to decode");
+        let cells = pages
+            .iter()
+            .map(|page| {
+                let piece = page.piece.expect("every page has a piece");
+                let mut cells = piece.cells();
+                cells.sort();
+                (piece.shape, piece.rotation, cells)
+            })
+            .collect::<Vec<_>>();
+        use TetrominoShape::{I, O, S, Z};
+        assert_eq!(
+            cells,
+            [
+                (O, 0, [(0, 0), (0, 1), (1, 0), (1, 1)]),
+                (I, 3, [(2, 0), (2, 1), (2, 2), (2, 3)]),
+                (I, 1, [(3, 0), (3, 1), (3, 2), (3, 3)]),
+                (O, 2, [(4, 0), (4, 1), (5, 0), (5, 1)]),
+                (O, 1, [(6, 0), (6, 1), (7, 0), (7, 1)]),
+                (O, 3, [(8, 0), (8, 1), (9, 0), (9, 1)]),
+                (I, 2, [(4, 0), (5, 0), (6, 0), (7, 0)]),
+                (S, 0, [(4, 1), (5, 1), (5, 2), (6, 2)]),
+                (Z, 3, [(8, 0), (8, 1), (9, 1), (9, 2)]),
+                (S, 1, [(0, 1), (0, 2), (1, 0), (1, 1)]),
+                (Z, 0, [(0, 4), (1, 3), (1, 4), (2, 3)]),
+            ]
+        );
+        // the two full rows cleared, leaving the top of the vertical I pieces
+        assert_eq!(
+            pages[6].field[FIELD_HEIGHT - 1],
+            "..II......"
+                .chars()
+                .filter_map(Cell::from_char)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(encode(&pages), "v115@vhKTJJZlBplBDrBLsBbtBhwB3mBctBvpB0aB");
+    }
+
+    #[test]
+    fn test_from_board() {
+        let config = Config {
+            number_of_previews: 2,
+            ..Config::default()
+        };
+        let setup = BoardSetup {
+            rows: vec!["#########.".to_string()],
+            queue: vec![TetrominoShape::I, TetrominoShape::T, TetrominoShape::L],
+            hold: Some(TetrominoShape::S),
+            then_random: false,
+        };
+        let board = Board::from_setup(&config, 0, &setup);
+        let page = FumenPage::from_board(&board).expect("failed to make a page");
+        assert_eq!(page.comment, "#Q=[S](I)TL");
+        // the I spawns flat on the row below the top two of the board
+        assert_eq!(
+            page.piece.map(|piece| piece.cells()),
+            Some([(4, 21), (3, 21), (5, 21), (6, 21)])
+        );
+        let decoded = decode(&encode(&[page])).expect("failed to decode");
+        assert_eq!(
+            decoded[0].setup(),
+            BoardSetup {
+                then_random: true,
+                ..setup
+            }
+        );
+    }
+}
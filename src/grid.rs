@@ -0,0 +1,462 @@
+use ratatui::{
+    prelude::{Buffer, Rect},
+    style::{Color, Style},
+    widgets::Widget,
+};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::tetromino::TetrominoShape;
+
+/// How board cells are drawn, which also decides how many terminal cells they take up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CellStyle {
+    /// blocks when they fit, half blocks otherwise
+    #[default]
+    Auto,
+    /// "██", two columns wide and a whole row high
+    Blocks,
+    /// one column wide and half a row high
+    HalfBlock,
+    /// "[]", for fonts without block characters
+    Ascii,
+    /// braille dots, as small as half blocks
+    Braille,
+    /// blocks with a thin edge on both sides
+    Bordered,
+}
+
+impl CellStyle {
+    pub const ALL: [CellStyle; 6] = [
+        CellStyle::Auto,
+        CellStyle::Blocks,
+        CellStyle::HalfBlock,
+        CellStyle::Ascii,
+        CellStyle::Braille,
+        CellStyle::Bordered,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CellStyle::Auto => "auto",
+            CellStyle::Blocks => "blocks",
+            CellStyle::HalfBlock => "half block",
+            CellStyle::Ascii => "ascii",
+            CellStyle::Braille => "braille",
+            CellStyle::Bordered => "bordered",
+        }
+    }
+
+    /// Styles to try, in order, until the game fits in the terminal.
+    pub fn candidates(&self) -> Vec<CellStyle> {
+        match self {
+            CellStyle::Auto => vec![CellStyle::Blocks, CellStyle::HalfBlock],
+            CellStyle::HalfBlock | CellStyle::Braille => vec![*self],
+            _ => vec![*self, CellStyle::HalfBlock],
+        }
+    }
+
+    /// Whether a terminal cell holds two board cells, one above the other.
+    fn is_compact(&self) -> bool {
+        matches!(self, CellStyle::HalfBlock | CellStyle::Braille)
+    }
+
+    /// Terminal columns and rows needed to draw `width` by `height` cells.
+    pub fn size(&self, width: usize, height: usize) -> (u16, u16) {
+        let (columns, rows) = if self.is_compact() {
+            (width, height.div_ceil(2))
+        } else {
+            (width * 2, height)
+        };
+        (
+            columns.try_into().unwrap_or(u16::MAX),
+            rows.try_into().unwrap_or(u16::MAX),
+        )
+    }
+}
+
+impl FromStr for CellStyle {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        CellStyle::ALL
+            .into_iter()
+            .find(|style| style.name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "cell style must be one of {}",
+                    CellStyle::ALL.map(|style| style.name()).join(", ")
+                )
+            })
+    }
+}
+
+/// Surface of the cells of the styles that are two columns wide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CellTexture {
+    #[default]
+    Flat,
+    /// lighter top edge and darker bottom edge
+    Bevel,
+    /// shaded blocks
+    Shaded,
+}
+
+impl CellTexture {
+    pub const ALL: [CellTexture; 3] = [CellTexture::Flat, CellTexture::Bevel, CellTexture::Shaded];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CellTexture::Flat => "flat",
+            CellTexture::Bevel => "bevel",
+            CellTexture::Shaded => "shaded",
+        }
+    }
+}
+
+impl FromStr for CellTexture {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        CellTexture::ALL
+            .into_iter()
+            .find(|texture| texture.name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "cell texture must be one of {}",
+                    CellTexture::ALL.map(|texture| texture.name()).join(", ")
+                )
+            })
+    }
+}
+
+/// Marks that tell the shapes apart without relying on their colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ShapeMarks {
+    #[default]
+    Off,
+    /// the letter of the shape in each cell
+    Letters,
+    /// a different fill character for each shape
+    Patterns,
+}
+
+impl ShapeMarks {
+    pub const ALL: [ShapeMarks; 3] = [ShapeMarks::Off, ShapeMarks::Letters, ShapeMarks::Patterns];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShapeMarks::Off => "off",
+            ShapeMarks::Letters => "letters",
+            ShapeMarks::Patterns => "patterns",
+        }
+    }
+}
+
+impl FromStr for ShapeMarks {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        ShapeMarks::ALL
+            .into_iter()
+            .find(|marks| marks.name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "shape marks must be one of {}",
+                    ShapeMarks::ALL.map(|marks| marks.name()).join(", ")
+                )
+            })
+    }
+}
+
+/// A colored cell, and the shape it belongs to if it is part of a piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub color: Color,
+    pub shape: Option<TetrominoShape>,
+}
+
+impl Tile {
+    pub fn plain(color: Color) -> Tile {
+        Tile { color, shape: None }
+    }
+
+    pub fn piece(color: Color, shape: TetrominoShape) -> Tile {
+        Tile {
+            color,
+            shape: Some(shape),
+        }
+    }
+}
+
+/// Mixes an rgb color with white for positive `amount`s and with black for
+/// negative ones, other colors can't be mixed and are returned as they are.
+pub fn shade(color: Color, amount: f32) -> Color {
+    let Color::Rgb(r, g, b) = color else {
+        return color;
+    };
+    let mix = |channel: u8| {
+        let channel = channel as f32;
+        let target = if amount > 0.0 { 255.0 } else { 0.0 };
+        (channel + (target - channel) * amount.abs()).round() as u8
+    };
+    Color::Rgb(mix(r), mix(g), mix(b))
+}
+
+/// Black or white, whichever is easier to read on `color`.
+fn contrasting(color: Color) -> Color {
+    match color {
+        Color::Rgb(r, g, b)
+            if 299 * u32::from(r) + 587 * u32::from(g) + 114 * u32::from(b) < 140_000 =>
+        {
+            Color::White
+        }
+        _ => Color::Black,
+    }
+}
+
+/// Draws a grid of colored cells, top row first. Empty cells are left as they are.
+/// Shapes are only marked in the styles that are two columns wide, the
+/// compact styles have no room for it.
+pub struct CellGrid<'a> {
+    pub cells: &'a [Vec<Option<Tile>>],
+    pub look: CellLook,
+}
+
+/// Everything about how cells are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellLook {
+    pub style: CellStyle,
+    pub texture: CellTexture,
+    pub marks: ShapeMarks,
+}
+
+impl CellGrid<'_> {
+    /// The two halves of a cell of the styles that are two columns wide.
+    fn wide_cell(&self, tile: Tile) -> [(&'static str, Style); 2] {
+        let color = tile.color;
+        let solid = Style::default().fg(color);
+        match (tile.shape, self.look.marks) {
+            (Some(shape), ShapeMarks::Letters) => {
+                let letter = match shape {
+                    TetrominoShape::I => "I",
+                    TetrominoShape::J => "J",
+                    TetrominoShape::L => "L",
+                    TetrominoShape::O => "O",
+                    TetrominoShape::S => "S",
+                    TetrominoShape::T => "T",
+                    TetrominoShape::Z => "Z",
+                };
+                let style = Style::default().fg(contrasting(color)).bg(color);
+                return [(letter, style), (" ", style)];
+            }
+            (Some(shape), ShapeMarks::Patterns) => {
+                let fill = match (shape, self.look.style) {
+                    (TetrominoShape::I, CellStyle::Ascii) => "#",
+                    (TetrominoShape::J, CellStyle::Ascii) => "=",
+                    (TetrominoShape::L, CellStyle::Ascii) => "+",
+                    (TetrominoShape::O, CellStyle::Ascii) => "@",
+                    (TetrominoShape::S, CellStyle::Ascii) => "/",
+                    (TetrominoShape::T, CellStyle::Ascii) => "%",
+                    (TetrominoShape::Z, CellStyle::Ascii) => "\\",
+                    (TetrominoShape::I, _) => "█",
+                    (TetrominoShape::J, _) => "▚",
+                    (TetrominoShape::L, _) => "▞",
+                    (TetrominoShape::O, _) => "▓",
+                    (TetrominoShape::S, _) => "╱",
+                    (TetrominoShape::T, _) => "▒",
+                    (TetrominoShape::Z, _) => "╲",
+                };
+                return [(fill, solid), (fill, solid)];
+            }
+            _ => {}
+        }
+        match (self.look.style, self.look.texture) {
+            (CellStyle::Ascii, _) => [("[", solid), ("]", solid)],
+            (CellStyle::Bordered, _) => {
+                let edge = Style::default().fg(shade(color, -0.5)).bg(color);
+                [("▏", edge), ("▕", edge)]
+            }
+            (_, CellTexture::Flat) => [("█", solid), ("█", solid)],
+            (_, CellTexture::Bevel) => [
+                ("▔", Style::default().fg(shade(color, 0.4)).bg(color)),
+                ("▁", Style::default().fg(shade(color, -0.4)).bg(color)),
+            ],
+            (_, CellTexture::Shaded) => [("▓", solid), ("▓", solid)],
+        }
+    }
+}
+
+impl Widget for CellGrid<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut paint = |column: usize, row: usize, symbol: &str, style: Style| {
+            let (Ok(column), Ok(row)) = (u16::try_from(column), u16::try_from(row)) else {
+                return;
+            };
+            if column < area.width && row < area.height {
+                buf.get_mut(area.x + column, area.y + row)
+                    .set_symbol(symbol)
+                    .set_style(style);
+            }
+        };
+
+        if !self.look.style.is_compact() {
+            for (y, row) in self.cells.iter().enumerate() {
+                for (x, tile) in row.iter().enumerate() {
+                    if let Some(tile) = tile {
+                        let [left, right] = self.wide_cell(*tile);
+                        paint(x * 2, y, left.0, left.1);
+                        paint(x * 2 + 1, y, right.0, right.1);
+                    }
+                }
+            }
+            return;
+        }
+
+        for (y, rows) in self.cells.chunks(2).enumerate() {
+            for x in 0..rows[0].len() {
+                let top = rows[0][x].map(|tile| tile.color);
+                let bottom = rows.get(1).and_then(|row| row[x]).map(|tile| tile.color);
+                if self.look.style == CellStyle::Braille {
+                    // dots 1, 2, 4 and 5 are the top half, 3, 6, 7 and 8 the bottom one,
+                    // a braille character only has one color so the top cell's wins
+                    let dots = u32::from(top.is_some()) * 0x1b + u32::from(bottom.is_some()) * 0xe4;
+                    if let (Some(symbol), Some(color)) =
+                        (char::from_u32(0x2800 + dots), top.or(bottom))
+                    {
+                        if dots != 0 {
+                            paint(x, y, &symbol.to_string(), Style::default().fg(color));
+                        }
+                    }
+                    continue;
+                }
+                match (top, bottom) {
+                    (Some(top), Some(bottom)) => {
+                        paint(x, y, "▀", Style::default().fg(top).bg(bottom))
+                    }
+                    (Some(top), None) => paint(x, y, "▀", Style::default().fg(top)),
+                    (None, Some(bottom)) => paint(x, y, "▄", Style::default().fg(bottom)),
+                    (None, None) => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(
+        cells: &[Vec<Option<Tile>>],
+        style: CellStyle,
+        texture: CellTexture,
+        marks: ShapeMarks,
+    ) -> Buffer {
+        let (width, height) = style.size(cells[0].len(), cells.len());
+        let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+        CellGrid {
+            cells,
+            look: CellLook {
+                style,
+                texture,
+                marks,
+            },
+        }
+        .render(buf.area, &mut buf);
+        buf
+    }
+
+    #[test]
+    fn test_render_styles() {
+        let cells = vec![
+            vec![Some(Tile::plain(Color::Red)), None],
+            vec![
+                Some(Tile::plain(Color::Blue)),
+                Some(Tile::plain(Color::Green)),
+            ],
+        ];
+
+        let buf = render(
+            &cells,
+            CellStyle::HalfBlock,
+            CellTexture::Flat,
+            ShapeMarks::Off,
+        );
+        assert_eq!(buf.area.height, 1);
+        assert_eq!(buf.get(0, 0).symbol(), "▀");
+        assert_eq!(buf.get(0, 0).fg, Color::Red);
+        assert_eq!(buf.get(0, 0).bg, Color::Blue);
+        assert_eq!(buf.get(1, 0).symbol(), "▄");
+        assert_eq!(buf.get(1, 0).fg, Color::Green);
+
+        let buf = render(
+            &cells,
+            CellStyle::Braille,
+            CellTexture::Flat,
+            ShapeMarks::Off,
+        );
+        assert_eq!(buf.get(0, 0).symbol(), "⣿");
+        assert_eq!(buf.get(1, 0).symbol(), "⣤");
+
+        let buf = render(
+            &cells,
+            CellStyle::Blocks,
+            CellTexture::Flat,
+            ShapeMarks::Off,
+        );
+        assert_eq!(buf.get(1, 0).symbol(), "█");
+        assert_eq!(buf.get(2, 0).symbol(), " ");
+
+        let buf = render(
+            &cells,
+            CellStyle::Ascii,
+            CellTexture::Bevel,
+            ShapeMarks::Off,
+        );
+        assert_eq!(buf.get(2, 1).symbol(), "[");
+        assert_eq!(buf.get(3, 1).symbol(), "]");
+    }
+
+    #[test]
+    fn test_render_shape_marks() {
+        let cells = vec![vec![
+            Some(Tile::piece(Color::Rgb(0, 255, 0), TetrominoShape::S)),
+            Some(Tile::piece(Color::Rgb(128, 0, 0), TetrominoShape::Z)),
+            Some(Tile::plain(Color::Gray)),
+        ]];
+
+        let buf = render(
+            &cells,
+            CellStyle::Blocks,
+            CellTexture::Flat,
+            ShapeMarks::Letters,
+        );
+        assert_eq!(buf.get(0, 0).symbol(), "S");
+        assert_eq!(buf.get(0, 0).fg, Color::Black);
+        assert_eq!(buf.get(0, 0).bg, Color::Rgb(0, 255, 0));
+        assert_eq!(buf.get(2, 0).symbol(), "Z");
+        assert_eq!(buf.get(2, 0).fg, Color::White);
+        // cells that aren't part of a piece aren't marked
+        assert_eq!(buf.get(4, 0).symbol(), "█");
+
+        let buf = render(
+            &cells,
+            CellStyle::Ascii,
+            CellTexture::Flat,
+            ShapeMarks::Patterns,
+        );
+        assert_eq!(buf.get(1, 0).symbol(), "/");
+        assert_eq!(buf.get(3, 0).symbol(), "\\");
+    }
+
+    #[test]
+    fn test_shade() {
+        assert_eq!(
+            shade(Color::Rgb(100, 0, 200), 0.5),
+            Color::Rgb(178, 128, 228)
+        );
+        assert_eq!(shade(Color::Rgb(100, 0, 200), -0.5), Color::Rgb(50, 0, 100));
+        assert_eq!(shade(Color::Red, 0.5), Color::Red);
+    }
+}
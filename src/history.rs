@@ -0,0 +1,314 @@
+use anyhow::Result;
+use ratatui::{
+    prelude::{Buffer, Constraint, Direction, Layout, Rect},
+    symbols::Marker,
+    text::{Line, Span},
+    widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph, Sparkline, Widget},
+};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::clock::format_duration;
+use crate::config::find_config_dir;
+use crate::mode::GameMode;
+use crate::stats::GameSummary;
+
+const HISTORY_FILE_NAME: &str = "history.jsonl";
+/// Number of games averaged for the trends.
+const ROLLING_GAMES: usize = 10;
+/// Height of the report of a single mode.
+const REPORT_HEIGHT: u16 = 20;
+
+/// A game that ended, as a line of the history file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// when the game ended, in seconds since the unix epoch
+    pub timestamp: u64,
+    /// was the goal of the mode reached, as opposed to topping out
+    pub finished: bool,
+    #[serde(flatten)]
+    pub summary: GameSummary,
+}
+
+impl HistoryEntry {
+    pub fn new(summary: GameSummary, finished: bool) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            finished,
+            summary,
+        }
+    }
+
+    /// Adds the entry to the end of the history file.
+    pub fn append(&self) -> Result<()> {
+        std::fs::create_dir_all(find_config_dir())?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(find_config_dir() + HISTORY_FILE_NAME)?;
+        writeln!(file, "{}", serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Every game played, oldest first.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Reads the history file, skipping lines that can't be read.
+    pub fn load() -> History {
+        let text =
+            std::fs::read_to_string(find_config_dir() + HISTORY_FILE_NAME).unwrap_or_default();
+        History::parse(&text)
+    }
+
+    fn parse(text: &str) -> History {
+        History {
+            entries: text
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect(),
+        }
+    }
+
+    pub fn entries(&self, mode: GameMode) -> Vec<&HistoryEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.summary.mode == mode)
+            .collect()
+    }
+
+    /// Personal bests, averages and trends of the games of `mode`, drawn
+    /// `width` columns wide, None if none were played.
+    pub fn report(&self, mode: GameMode, width: u16) -> Option<Vec<String>> {
+        let entries = self.entries(mode);
+        if entries.is_empty() {
+            return None;
+        }
+        let area = Rect::new(0, 0, width, REPORT_HEIGHT);
+        let mut buf = Buffer::empty(area);
+        let [text, chart, sparkline] = <[Rect; 3]>::try_from(
+            Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(4),
+                    Constraint::Min(8),
+                    Constraint::Length(5),
+                ])
+                .split(area)
+                .as_ref(),
+        )
+        .expect("three constraints make three areas");
+
+        Paragraph::new(summary_lines(mode, &entries)).render(text, &mut buf);
+
+        // pieces per second of every game, and their rolling average
+        let pps = entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (i as f64 + 1.0, entry.summary.pps))
+            .collect::<Vec<_>>();
+        let average = rolling_average(&pps.iter().map(|(_, pps)| *pps).collect::<Vec<_>>())
+            .into_iter()
+            .enumerate()
+            .map(|(i, pps)| (i as f64 + 1.0, pps))
+            .collect::<Vec<_>>();
+        let games = entries.len() as f64;
+        let max_pps = pps.iter().map(|(_, pps)| *pps).fold(0.0, f64::max).max(0.1);
+        Chart::new(vec![
+            Dataset::default()
+                .name("pps")
+                .marker(Marker::Dot)
+                .graph_type(GraphType::Scatter)
+                .data(&pps),
+            Dataset::default()
+                .name(format!("average of {ROLLING_GAMES}"))
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .data(&average),
+        ])
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("pieces per second"),
+        )
+        .x_axis(
+            Axis::default()
+                .title("game")
+                .bounds([1.0, games.max(2.0)])
+                .labels(vec![Span::raw("1"), Span::raw(entries.len().to_string())]),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([0.0, max_pps * 1.1])
+                .labels(vec![Span::raw("0"), Span::raw(format!("{max_pps:.1}"))]),
+        )
+        .render(chart, &mut buf);
+
+        // the last games that fit, by what the mode ranks
+        let (title, values): (_, Vec<u64>) = if mode.ranks_by_time() {
+            (
+                "seconds per finished game",
+                entries
+                    .iter()
+                    .filter(|entry| entry.finished)
+                    .map(|entry| entry.summary.time.round() as u64)
+                    .collect(),
+            )
+        } else {
+            (
+                "score per game",
+                entries.iter().map(|entry| entry.summary.score).collect(),
+            )
+        };
+        let shown = usize::from(sparkline.width.saturating_sub(2));
+        Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .data(&values[values.len().saturating_sub(shown)..])
+            .render(sparkline, &mut buf);
+
+        Some(
+            (0..area.height)
+                .map(|y| {
+                    let line = (0..area.width)
+                        .map(|x| buf.get(x, y).symbol())
+                        .collect::<String>();
+                    line.trim_end().to_string()
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Games played, personal bests and how the last games compare to the ones before them.
+fn summary_lines(mode: GameMode, entries: &[&HistoryEntry]) -> Vec<Line<'static>> {
+    let summaries = entries
+        .iter()
+        .map(|entry| &entry.summary)
+        .collect::<Vec<_>>();
+    let best = |value: fn(&GameSummary) -> f64| {
+        summaries
+            .iter()
+            .map(|summary| value(summary))
+            .fold(0.0, f64::max)
+    };
+    let best_result = if mode.ranks_by_time() {
+        entries
+            .iter()
+            .filter(|entry| entry.finished)
+            .map(|entry| entry.summary.time)
+            .min_by(f64::total_cmp)
+            .map_or("no finished game".to_string(), |time| {
+                format!(
+                    "best time {}",
+                    format_duration(Duration::from_secs_f64(time))
+                )
+            })
+    } else {
+        format!(
+            "best score {}",
+            summaries
+                .iter()
+                .map(|summary| summary.score)
+                .max()
+                .unwrap_or(0)
+        )
+    };
+    vec![
+        format!("{}, {} games", mode.name(), entries.len()).into(),
+        format!(
+            "{best_result}, best pps {:.2}, best apm {:.1}",
+            best(|summary| summary.pps),
+            best(|summary| summary.apm)
+        )
+        .into(),
+        format!(
+            "last {ROLLING_GAMES}: pps {}, apm {}, kpp {}",
+            trend(&summaries, |summary| summary.pps, 2),
+            trend(&summaries, |summary| summary.apm, 1),
+            trend(&summaries, |summary| summary.kpp, 2),
+        )
+        .into(),
+        format!(
+            "last {ROLLING_GAMES}: finesse faults per game {}",
+            trend(&summaries, |summary| summary.stats.finesse_faults as f64, 1),
+        )
+        .into(),
+    ]
+}
+
+/// Average of the last games, and how much it changed from the games before them.
+fn trend(summaries: &[&GameSummary], value: fn(&GameSummary) -> f64, precision: usize) -> String {
+    let values = summaries
+        .iter()
+        .map(|summary| value(summary))
+        .collect::<Vec<_>>();
+    let split = values.len().saturating_sub(ROLLING_GAMES);
+    let (before, last) = values.split_at(split);
+    let last_average = mean(last);
+    let before = &before[before.len().saturating_sub(ROLLING_GAMES)..];
+    if before.is_empty() {
+        return format!("{last_average:.precision$}");
+    }
+    format!(
+        "{last_average:.precision$} ({:+.precision$})",
+        last_average - mean(before)
+    )
+}
+
+/// Average of each value with up to [`ROLLING_GAMES`] - 1 values before it.
+fn rolling_average(values: &[f64]) -> Vec<f64> {
+    (0..values.len())
+        .map(|i| mean(&values[(i + 1).saturating_sub(ROLLING_GAMES)..=i]))
+        .collect()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len().max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::GameStats;
+
+    fn entry(mode: GameMode, pps: f64) -> HistoryEntry {
+        let mut stats = GameStats::default();
+        stats.pieces = (pps * 60.0).round() as u64;
+        HistoryEntry {
+            timestamp: 0,
+            finished: true,
+            summary: GameSummary::new(&stats, mode, 0, 1000, 1, Duration::from_secs(60)),
+        }
+    }
+
+    #[test]
+    fn test_history_lines() {
+        let line = serde_json::to_string(&entry(GameMode::Sprint, 1.5)).expect("failed to write");
+        let history = History::parse(&format!("{line}\nnot json\n{line}\n"));
+        assert_eq!(history.entries(GameMode::Sprint).len(), 2);
+        assert!(history.entries(GameMode::Marathon).is_empty());
+    }
+
+    #[test]
+    fn test_report() {
+        let history = History {
+            entries: (0..20)
+                .map(|i| entry(GameMode::Marathon, 1.0 + f64::from(i) / 10.0))
+                .collect(),
+        };
+        assert_eq!(history.report(GameMode::Sprint, 60), None);
+        let report = history.report(GameMode::Marathon, 60).expect("no report");
+        assert_eq!(report.len(), usize::from(REPORT_HEIGHT));
+        assert_eq!(report[0], "marathon, 20 games");
+        // the last ten games average 2.45, the ten before them 1.45
+        assert!(report[2].starts_with("last 10: pps 2.45 (+1.00)"));
+        assert_eq!(rolling_average(&[1.0, 2.0, 3.0]), [1.0, 1.5, 2.0]);
+    }
+}
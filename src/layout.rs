@@ -0,0 +1,258 @@
+use ratatui::prelude::Rect;
+
+use crate::grid::CellStyle;
+
+/// Where the parts of the game screen go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameLayout {
+    pub style: CellStyle,
+    pub board: Rect,
+    /// the panels are left out when there is no room for them
+    pub hold: Option<Rect>,
+    pub stats: Option<Rect>,
+    /// as many preview boxes as fit, up to the number asked for
+    pub previews: Vec<Rect>,
+    /// where the names of clears are shown, the board itself if there is no room beside it
+    pub action_text: Rect,
+}
+
+impl GameLayout {
+    /// Lays out the game in `area`, using the first of the candidates of
+    /// `cell_style` that leaves room for the hold, stats and at least one
+    /// preview, returns None if not even the board fits.
+    pub fn new(
+        area: Rect,
+        cell_style: CellStyle,
+        board_size: (usize, usize),
+        number_of_previews: usize,
+        stats_size: (u16, u16),
+    ) -> Option<GameLayout> {
+        let candidates = cell_style.candidates();
+        candidates
+            .iter()
+            .find_map(|&style| {
+                Self::with_panels(area, style, board_size, number_of_previews, stats_size)
+            })
+            .or_else(|| {
+                // the last candidate is always one of the compact styles
+                let style = candidates.last().copied().unwrap_or(CellStyle::HalfBlock);
+                Self::board_only(area, style, board_size)
+            })
+    }
+
+    /// Smallest terminal size the board can be shown in.
+    pub fn min_size(board_size: (usize, usize)) -> (u16, u16) {
+        board_rect_size(CellStyle::HalfBlock, board_size)
+    }
+
+    fn with_panels(
+        area: Rect,
+        style: CellStyle,
+        board_size: (usize, usize),
+        number_of_previews: usize,
+        (stats_width, stats_height): (u16, u16),
+    ) -> Option<GameLayout> {
+        let (board_width, board_height) = board_rect_size(style, board_size);
+        let (box_width, box_height) = preview_box_size(style);
+        let left_width = box_width.max(stats_width);
+        let right_width = if number_of_previews > 0 { box_width } else { 0 };
+        if u32::from(left_width) + u32::from(board_width) + u32::from(right_width)
+            > u32::from(area.width)
+            || board_height > area.height
+            || box_height > board_height
+        {
+            return None;
+        }
+
+        // centered, unless a side needs more room than the other
+        let centered_x = area.x + (area.width - board_width) / 2;
+        let board_x = centered_x
+            .max(area.x + left_width)
+            .min(area.right() - right_width - board_width);
+        let board = Rect::new(
+            board_x,
+            area.y + (area.height - board_height) / 2,
+            board_width,
+            board_height,
+        );
+
+        // hold at the top left of the board, stats at the bottom left if there
+        // is room for both, otherwise below the hold
+        let hold = Rect::new(board.x - box_width, board.y, box_width, box_height);
+        let stats_y = (board.bottom().saturating_sub(stats_height)).max(hold.bottom());
+        let stats = (stats_y + stats_height <= area.bottom())
+            .then(|| Rect::new(board.x - stats_width, stats_y, stats_width, stats_height));
+
+        // names of clears between the hold and the stats
+        let text_bottom = stats.map_or(board.bottom(), |stats| stats.y);
+        let action_text = if text_bottom > hold.bottom() {
+            Rect::new(
+                area.x,
+                hold.bottom(),
+                board.x - area.x,
+                text_bottom - hold.bottom(),
+            )
+        } else {
+            board
+        };
+
+        // previews in columns to the right of the board, top to bottom
+        let mut previews = Vec::new();
+        let mut x = board.right();
+        while x + box_width <= area.right() && previews.len() < number_of_previews {
+            let mut y = board.y;
+            while y + box_height <= area.bottom() && previews.len() < number_of_previews {
+                previews.push(Rect::new(x, y, box_width, box_height));
+                y += box_height;
+            }
+            x += box_width;
+        }
+
+        Some(GameLayout {
+            style,
+            board,
+            hold: Some(hold),
+            stats,
+            previews,
+            action_text,
+        })
+    }
+
+    /// The cell of a board of `board_size` drawn at the terminal `column` and
+    /// `row`, as (x, y) from the top left. Compact styles draw two rows in a
+    /// terminal row, the top one is picked.
+    pub fn board_cell(
+        &self,
+        column: u16,
+        row: u16,
+        (width, height): (usize, usize),
+    ) -> Option<(usize, usize)> {
+        let column = usize::from(column.checked_sub(self.board.x)?);
+        let row = usize::from(row.checked_sub(self.board.y)?);
+        let (x, y) = match self.style {
+            CellStyle::HalfBlock | CellStyle::Braille => (column, row * 2),
+            _ => (column / 2, row),
+        };
+        // leave out the border on the left
+        let x = x.checked_sub(1)?;
+        (x < width && y < height).then_some((x, y))
+    }
+
+    fn board_only(area: Rect, style: CellStyle, board_size: (usize, usize)) -> Option<GameLayout> {
+        let (width, height) = board_rect_size(style, board_size);
+        if width > area.width || height > area.height {
+            return None;
+        }
+        let board = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        Some(GameLayout {
+            style,
+            board,
+            hold: None,
+            stats: None,
+            previews: Vec::new(),
+            action_text: board,
+        })
+    }
+}
+
+/// Size of the board along with its border on the sides and below.
+fn board_rect_size(style: CellStyle, (width, height): (usize, usize)) -> (u16, u16) {
+    style.size(width + 2, height + 1)
+}
+
+/// Size of a hold or next piece box, including its border.
+fn preview_box_size(style: CellStyle) -> (u16, u16) {
+    let (width, height) = style.size(4, 2);
+    (width + 2, height + 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_fits_any_size() {
+        for width in 0..80 {
+            for height in 0..40 {
+                let area = Rect::new(3, 2, width, height);
+                let Some(layout) = GameLayout::new(area, CellStyle::Auto, (10, 24), 5, (18, 6))
+                else {
+                    assert!(
+                        width < 12 || height < 13,
+                        "nothing fits in {width}x{height}"
+                    );
+                    continue;
+                };
+                let mut rects = vec![layout.board];
+                rects.extend(layout.hold);
+                rects.extend(layout.stats);
+                rects.extend(&layout.previews);
+                assert_eq!(area.intersection(layout.action_text), layout.action_text);
+                for (i, rect) in rects.iter().enumerate() {
+                    assert_eq!(
+                        area.intersection(*rect),
+                        *rect,
+                        "outside of {width}x{height}"
+                    );
+                    for other in &rects[i + 1..] {
+                        assert!(!rect.intersects(*other), "overlap in {width}x{height}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_cell() {
+        let layout = GameLayout::board_only(Rect::new(0, 0, 24, 25), CellStyle::Blocks, (10, 24))
+            .expect("board doesn't fit");
+        assert_eq!(layout.board_cell(2, 0, (10, 24)), Some((0, 0)));
+        assert_eq!(layout.board_cell(21, 23, (10, 24)), Some((9, 23)));
+        // the border
+        assert_eq!(layout.board_cell(1, 5, (10, 24)), None);
+        assert_eq!(layout.board_cell(22, 5, (10, 24)), None);
+        assert_eq!(layout.board_cell(5, 24, (10, 24)), None);
+    }
+
+    #[test]
+    fn test_layout_styles() {
+        let layout = GameLayout::new(
+            Rect::new(0, 0, 120, 40),
+            CellStyle::Auto,
+            (10, 24),
+            5,
+            (18, 6),
+        )
+        .expect("board doesn't fit");
+        assert_eq!(layout.style, CellStyle::Blocks);
+        assert_eq!(layout.previews.len(), 5);
+
+        let layout = GameLayout::new(
+            Rect::new(0, 0, 60, 20),
+            CellStyle::Auto,
+            (10, 24),
+            5,
+            (18, 6),
+        )
+        .expect("board doesn't fit");
+        assert_eq!(layout.style, CellStyle::HalfBlock);
+        assert!(layout.hold.is_some());
+
+        let layout = GameLayout::new(
+            Rect::new(0, 0, 20, 14),
+            CellStyle::Braille,
+            (10, 24),
+            5,
+            (18, 6),
+        )
+        .expect("board doesn't fit");
+        assert!(layout.hold.is_none());
+        assert_eq!(layout.style, CellStyle::Braille);
+        assert_eq!(GameLayout::min_size((10, 24)), (12, 13));
+    }
+}
@@ -1,23 +1,123 @@
+pub mod animation;
 pub mod app;
 pub mod board;
+pub mod cli;
+pub mod clock;
+pub mod color;
 pub mod config;
 #[cfg(debug_assertions)]
 pub mod debug;
+pub mod editor;
+pub mod fumen;
+pub mod grid;
+pub mod history;
+pub mod layout;
+pub mod master;
+pub mod menu;
+pub mod mode;
+pub mod practice;
+pub mod puzzle;
+pub mod replay;
+pub mod scores;
+pub mod settings;
+pub mod stats;
 pub mod tetromino;
+pub mod theme;
 pub mod tui;
 pub mod ui;
 pub mod update;
 
 use anyhow::Result;
 use app::App;
+use clap::Parser;
+use cli::{Cli, Command, ConfigCommand, PlayArgs};
+use config::ConfigWatcher;
+use layout::GameLayout;
 use ratatui::{backend::CrosstermBackend, Terminal};
-use std::time::{Duration, Instant};
+use replay::Replay;
+use std::time::Instant;
+use theme::Theme;
 use tui::Tui;
-use update::{key_event_update, update};
+use update::{key_event_update, mouse_event_update, update};
 
 fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    // refuse to start with an invalid config, before the terminal is taken over
+    let config_source = cli.config.source();
+    let (config, warnings) = match config_source.load() {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    };
+    for warning in &warnings {
+        eprintln!("{}: {warning}", config_source.path);
+    }
+
     // Create the application.
-    let mut app = App::new();
+    let mut app = App::new(config_source, config);
+    if let Some(warning) = warnings.first() {
+        app.set_notice(warning.clone());
+    }
+    match cli.command {
+        None => apply_play_args(&mut app, &cli.play),
+        Some(Command::Play(play)) => {
+            apply_play_args(&mut app, &play);
+            if !app.mode.is_available() {
+                eprintln!("{} mode is not available yet", app.mode.name());
+                std::process::exit(1);
+            }
+            app.play();
+        }
+        Some(Command::Replay { file }) => app.open_replay(Replay::load(&file)?),
+        Some(Command::Scores { mode }) => {
+            cli::print_scores(mode);
+            return Ok(());
+        }
+        Some(Command::Stats { mode }) => {
+            cli::print_stats(mode);
+            return Ok(());
+        }
+        Some(Command::Config(ConfigCommand::Dump)) => {
+            print!("{}", app.config.to_ron()?);
+            return Ok(());
+        }
+        Some(Command::Config(ConfigCommand::Check)) => {
+            println!("{} is valid", app.config_source.path);
+            return Ok(());
+        }
+        Some(Command::Config(ConfigCommand::Theme { name })) => {
+            let name = name.unwrap_or_else(|| app.config.theme.clone());
+            match Theme::load(&name) {
+                Ok(theme) => print!("{}", theme.to_ron()?),
+                Err(problem) => {
+                    eprintln!("{problem}");
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Bench { games }) => {
+            cli::bench(&app.config, games);
+            return Ok(());
+        }
+    }
+    run(app)
+}
+
+/// Uses the command line's play options, exits if they can't be used.
+fn apply_play_args(app: &mut App, play: &PlayArgs) {
+    if let Err(problem) = app.apply_play_args(play) {
+        eprintln!("{problem}");
+        std::process::exit(1);
+    }
+}
+
+/// Runs the terminal user interface until the application quits.
+fn run(mut app: App) -> Result<()> {
+    let mut config_watcher = ConfigWatcher::new(&app.config_source.path);
 
     // Initialize the terminal user interface.
     let backend = CrosstermBackend::new(std::io::stderr());
@@ -25,14 +125,30 @@ fn main() -> Result<()> {
     let mut tui = Tui::new(terminal);
     tui.enter()?;
 
-    // Start the main loop.
+    // Start the main loop. The game runs in frames of a fixed length, see
+    // App::run_frames, and the screen is drawn at its own rate whenever
+    // something changed.
     let mut poll_time = Instant::now(); // initialize for tick delay
-    while !app.should_quit {
+    let mut drawn_at: Option<Instant> = None;
+    let mut changed = true;
+    while !app.should_quit() {
         // Render the user interface.
-        tui.draw(&mut app)?;
-        // Handle events.
-        let delay_duration = Duration::from_millis(app.board.tick_delay);
-        if crossterm::event::poll(delay_duration).expect("failed to poll event") {
+        let render_interval = app.render_interval();
+        if changed && drawn_at.is_none_or(|at| at.elapsed() >= render_interval) {
+            tui.draw(&mut app)?;
+            drawn_at = Some(Instant::now());
+            changed = false;
+        }
+        // Handle events, waking up for the next update or draw.
+        let delay_duration = app.tick_delay();
+        let mut timeout = delay_duration.saturating_sub(poll_time.elapsed());
+        if let (true, Some(at)) = (changed, drawn_at) {
+            timeout = timeout.min(render_interval.saturating_sub(at.elapsed()));
+        }
+        if crossterm::event::poll(timeout).expect("failed to poll event") {
+            // the frames that were due before the event come first
+            app.run_frames();
+            changed = true;
             match crossterm::event::read().expect("failed to read event") {
                 crossterm::event::Event::FocusGained => {
                     app.pause(false);
@@ -40,32 +156,49 @@ fn main() -> Result<()> {
                 crossterm::event::Event::FocusLost => {
                     app.pause(true);
                 }
-                crossterm::event::Event::Resize(_width, _height) => {
-                    // TODO: change board size
+                crossterm::event::Event::Resize(width, height) => {
+                    // the next draw lays the game out for the new size, pause
+                    // if the board doesn't fit anymore
+                    let (min_width, min_height) =
+                        GameLayout::min_size(app.board.config().board_size);
+                    if width < min_width || height < min_height {
+                        app.pause(true);
+                    }
                 }
                 crossterm::event::Event::Key(key_event) => key_event_update(&mut app, key_event),
+                crossterm::event::Event::Mouse(mouse_event) => {
+                    mouse_event_update(&mut app, mouse_event)
+                }
                 _ => {}
             };
         }
 
+        // apply changes made to the config file while running
+        if config_watcher.changed() {
+            app.reload_config();
+            changed = true;
+        }
+
         // make sure enough time has passed for update
         if poll_time.elapsed() >= delay_duration {
-            if !app.paused {
-                update(&mut app);
-            }
+            update(&mut app);
             poll_time = Instant::now();
+            changed = true;
         }
     }
 
     // Exit the user interface.
     tui.exit()?;
 
-    println!(
-        "score: {}\nlevel: {}\nlines cleared: {}",
-        app.board.get_score(),
-        app.board.get_level(),
-        app.board.get_lines_cleared()
-    );
+    // print the stats of the last game, if one was played
+    if !app.replay.actions().is_empty() {
+        println!(
+            "score: {}\nlevel: {}\nlines cleared: {}",
+            app.score(),
+            app.board.get_level(),
+            app.board.get_lines_cleared()
+        );
+    }
 
     Ok(())
 }
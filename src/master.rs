@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use crate::board::{frames_to_millis, BoardEvent, BoardRules, Gravity, TWENTY_G};
+use crate::clock::format_duration;
+
+/// Level that ends a master game.
+pub const MASTER_LEVELS: u16 = 999;
+/// Levels of a section, the level stops before the end of a section until a
+/// line is cleared.
+const SECTION_LEVELS: u16 = 100;
+/// Gravity from a level on, in 1/256 rows per frame, as in TGM.
+const GRAVITY: [(u16, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, TWENTY_G),
+];
+/// Entry delay and lock delay in frames from a level on.
+const DELAYS: [(u16, u8, u8); 4] = [(0, 25, 30), (700, 16, 30), (800, 12, 30), (900, 12, 17)];
+/// Score needed for each grade, from the lowest.
+const GRADES: [(u32, &str); 18] = [
+    (0, "9"),
+    (400, "8"),
+    (800, "7"),
+    (1_400, "6"),
+    (2_000, "5"),
+    (3_500, "4"),
+    (5_500, "3"),
+    (8_000, "2"),
+    (12_000, "1"),
+    (16_000, "S1"),
+    (22_000, "S2"),
+    (30_000, "S3"),
+    (40_000, "S4"),
+    (52_000, "S5"),
+    (66_000, "S6"),
+    (82_000, "S7"),
+    (100_000, "S8"),
+    (120_000, "S9"),
+];
+/// Longest game that earns the grand master grade with an S9 score.
+const GRAND_MASTER_TIME: Duration = Duration::from_secs(13 * 60 + 30);
+
+/// Progress of a master game: a level from 0 to 999 that rises with every
+/// piece and line, the gravity and delays that come with it, and a score
+/// that decides the grade.
+#[derive(Debug, Clone, Default)]
+pub struct Master {
+    level: u16,
+    score: u32,
+    /// points of the last clear, perfect clears multiply them
+    last_points: u32,
+    /// time each finished section ended at
+    section_times: Vec<Duration>,
+}
+
+impl Master {
+    pub fn new() -> Master {
+        Master::default()
+    }
+
+    /// How the board plays at the current level.
+    pub fn rules(&self) -> BoardRules {
+        let level_value = |table: &[(u16, u32)]| {
+            table
+                .iter()
+                .rev()
+                .find(|(from, _)| *from <= self.level)
+                .map_or(0, |(_, value)| *value)
+        };
+        let (_, entry_delay, lock_delay) = DELAYS
+            .iter()
+            .rev()
+            .find(|(from, ..)| *from <= self.level)
+            .copied()
+            .unwrap_or(DELAYS[0]);
+        BoardRules {
+            gravity: Gravity::PerFrame(level_value(&GRAVITY)),
+            // the grade comes from the master score instead
+            scoring: false,
+            lock_delay: Some(lock_delay),
+            entry_delay: Some(frames_to_millis(entry_delay.into())),
+            line_entry_delay: Some(frames_to_millis(entry_delay.into())),
+            ..BoardRules::default()
+        }
+    }
+
+    /// Counts what happened on the board `elapsed` into the game, returns
+    /// whether the level changed.
+    pub fn record(&mut self, event: &BoardEvent, elapsed: Duration) -> bool {
+        let level = self.level;
+        match event {
+            // the level waits at the end of a section for a line clear
+            BoardEvent::PieceLocked { .. }
+                if !(self.level + 1).is_multiple_of(SECTION_LEVELS)
+                    && self.level + 1 < MASTER_LEVELS =>
+            {
+                self.level += 1;
+            }
+            BoardEvent::LinesCleared { count, combo, .. } => {
+                let count = u16::from(*count);
+                self.last_points =
+                    u32::from((self.level + count).div_ceil(4) * count) * (u32::from(*combo) + 1);
+                self.score += self.last_points;
+                self.level = (self.level + count).min(MASTER_LEVELS);
+            }
+            BoardEvent::PerfectClear => self.score += self.last_points * 3,
+            _ => {}
+        }
+        if self.level / SECTION_LEVELS > level / SECTION_LEVELS
+            || (self.is_finished() && level < MASTER_LEVELS)
+        {
+            self.section_times.push(elapsed);
+        }
+        self.level != level
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.level >= MASTER_LEVELS
+    }
+
+    pub fn level(&self) -> u16 {
+        self.level
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// The grade of the score so far, grand master for an S9 that reaches
+    /// the last level in time.
+    pub fn grade(&self, time: Duration) -> &'static str {
+        let grade = GRADES
+            .iter()
+            .rev()
+            .find(|(score, _)| *score <= self.score)
+            .map_or(GRADES[0].1, |(_, grade)| *grade);
+        if grade == "S9" && self.is_finished() && time <= GRAND_MASTER_TIME {
+            return "GM";
+        }
+        grade
+    }
+
+    /// Lines for the stats panel.
+    pub fn lines(&self, elapsed: Duration) -> Vec<String> {
+        let section_end = ((self.level / SECTION_LEVELS + 1) * SECTION_LEVELS).min(MASTER_LEVELS);
+        let mut lines = vec![
+            format!("level: {}/{section_end}", self.level),
+            format!("grade: {}", self.grade(elapsed)),
+        ];
+        let mut start = Duration::ZERO;
+        for (section, end) in self.section_times.iter().enumerate() {
+            lines.push(format!(
+                "section {}: {}",
+                section + 1,
+                format_duration(end.saturating_sub(start))
+            ));
+            start = *end;
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_and_sections() {
+        let mut master = Master::new();
+        let locked = BoardEvent::PieceLocked {
+            shape: crate::tetromino::TetrominoShape::I,
+            cells: [(0, 0); 4],
+        };
+        for _ in 0..150 {
+            master.record(&locked, Duration::from_secs(60));
+        }
+        // stuck at the end of the first section
+        assert_eq!(master.level(), 99);
+        assert!(master.section_times.is_empty());
+
+        let tetris = BoardEvent::LinesCleared {
+            rows: vec![20, 21, 22, 23],
+            count: 4,
+            tspin: false,
+            b2b: 0,
+            combo: 0,
+        };
+        assert!(master.record(&tetris, Duration::from_secs(61)));
+        assert_eq!(master.level(), 103);
+        assert_eq!(master.score(), 26 * 4);
+        assert_eq!(master.section_times, [Duration::from_secs(61)]);
+        assert_eq!(master.rules().gravity, Gravity::PerFrame(80));
+        assert_eq!(master.grade(Duration::ZERO), "9");
+    }
+
+    #[test]
+    fn test_rules_by_level() {
+        let mut master = Master::new();
+        assert_eq!(master.rules().entry_delay, Some(416));
+        master.level = 500;
+        assert_eq!(master.rules().gravity, Gravity::PerFrame(TWENTY_G));
+        master.level = 950;
+        assert_eq!(master.rules().lock_delay, Some(17));
+        assert_eq!(master.rules().entry_delay, Some(200));
+    }
+}
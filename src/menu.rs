@@ -0,0 +1,69 @@
+/// Entries of the main menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    Mode,
+    Level,
+    Play,
+    Editor,
+    HighScores,
+    Settings,
+    Quit,
+}
+impl MenuItem {
+    pub const ALL: [MenuItem; 7] = [
+        MenuItem::Mode,
+        MenuItem::Level,
+        MenuItem::Play,
+        MenuItem::Editor,
+        MenuItem::HighScores,
+        MenuItem::Settings,
+        MenuItem::Quit,
+    ];
+}
+
+/// State of the main menu.
+#[derive(Debug, Default)]
+pub struct Menu {
+    /// index into [`MenuItem::ALL`]
+    pub selected: usize,
+}
+impl Menu {
+    /// Starts with "play" selected so enter starts a game right away.
+    pub fn new() -> Self {
+        Self {
+            selected: MenuItem::ALL
+                .iter()
+                .position(|item| *item == MenuItem::Play)
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn selected_item(&self) -> MenuItem {
+        MenuItem::ALL[self.selected]
+    }
+
+    pub fn select(&mut self, down: bool) {
+        self.selected = cycle(self.selected, MenuItem::ALL.len(), down);
+    }
+}
+
+/// Moves an index one step forward or backward through `len` items, wrapping around.
+pub fn cycle(index: usize, len: usize, forward: bool) -> usize {
+    if forward {
+        (index + 1) % len
+    } else {
+        (index + len - 1) % len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycle() {
+        assert_eq!(cycle(0, 3, false), 2);
+        assert_eq!(cycle(2, 3, true), 0);
+        assert_eq!(cycle(1, 3, true), 2);
+    }
+}
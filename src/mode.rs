@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::board::{Board, BoardRules, Gravity};
+use crate::config::Config;
+use crate::master::Master;
+
+/// Lines to clear to finish a sprint.
+pub const SPRINT_LINES: u128 = 40;
+/// Length of an ultra game.
+pub const ULTRA_TIME: Duration = Duration::from_secs(120);
+/// Rows of garbage a dig game starts with.
+pub const DIG_GARBAGE_ROWS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Marathon,
+    Sprint,
+    Ultra,
+    Dig,
+    Versus,
+    Puzzle,
+    Practice,
+    Zen,
+    Master,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 9] = [
+        GameMode::Marathon,
+        GameMode::Sprint,
+        GameMode::Ultra,
+        GameMode::Dig,
+        GameMode::Versus,
+        GameMode::Puzzle,
+        GameMode::Practice,
+        GameMode::Zen,
+        GameMode::Master,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Marathon => "marathon",
+            GameMode::Sprint => "sprint",
+            GameMode::Ultra => "ultra",
+            GameMode::Dig => "dig",
+            GameMode::Versus => "versus",
+            GameMode::Puzzle => "puzzle",
+            GameMode::Practice => "practice",
+            GameMode::Zen => "zen",
+            GameMode::Master => "master",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            GameMode::Marathon => "play until you top out",
+            GameMode::Sprint => "clear 40 lines as fast as you can",
+            GameMode::Ultra => "score as much as you can in 2 minutes",
+            GameMode::Dig => "clear 10 rows of garbage as fast as you can",
+            GameMode::Versus => "not available yet",
+            GameMode::Puzzle => "solve set boards with the pieces given",
+            GameMode::Practice => "undo and redo placements as you play",
+            GameMode::Zen => "stack at your own pace, topping out clears the board",
+            GameMode::Master => "reach level 999 as gravity rises to 20G, for a grade",
+        }
+    }
+
+    /// Whether the mode can be played yet.
+    pub fn is_available(&self) -> bool {
+        *self != GameMode::Versus
+    }
+
+    /// Whether games are ranked by how fast the goal was reached instead of by score.
+    pub fn ranks_by_time(&self) -> bool {
+        matches!(self, GameMode::Sprint | GameMode::Dig)
+    }
+
+    /// How the board plays in this mode.
+    pub fn rules(&self, config: &Config) -> BoardRules {
+        match self {
+            GameMode::Zen => BoardRules {
+                gravity: config.zen_tick_delay.map_or(Gravity::Off, Gravity::Fixed),
+                top_out_clears: true,
+                scoring: config.zen_scoring,
+                garbage_interval: config.zen_garbage,
+                ..BoardRules::default()
+            },
+            GameMode::Master => Master::new().rules(),
+            _ => BoardRules {
+                gravity: config.gravity.map_or(Gravity::Levels, Gravity::from_g),
+                ..BoardRules::default()
+            },
+        }
+    }
+
+    /// Constructs the starting board of a game in this mode.
+    pub fn new_board(&self, config: &Config, seed: u64, starting_level: u8) -> Board {
+        let mut board = Board::new(config, seed, starting_level);
+        board.set_rules(self.rules(config));
+        if *self == GameMode::Dig {
+            board.add_garbage(DIG_GARBAGE_ROWS);
+        }
+        board
+    }
+
+    /// Whether the goal of the mode has been reached.
+    pub fn is_finished(&self, board: &Board, elapsed: Duration) -> bool {
+        match self {
+            GameMode::Sprint => board.get_lines_cleared() >= SPRINT_LINES,
+            GameMode::Ultra => elapsed >= ULTRA_TIME,
+            GameMode::Dig => board.garbage_rows() == 0,
+            GameMode::Marathon
+            | GameMode::Versus
+            | GameMode::Puzzle
+            | GameMode::Practice
+            | GameMode::Zen
+            | GameMode::Master => false,
+        }
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        GameMode::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "mode must be one of {}",
+                    GameMode::ALL.map(|mode| mode.name()).join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dig_finished() {
+        let config = Config::default();
+        let board = GameMode::Dig.new_board(&config, 0, 1);
+        assert!(!GameMode::Dig.is_finished(&board, Duration::ZERO));
+        assert!(GameMode::Dig.is_finished(&Board::new(&config, 0, 1), Duration::ZERO));
+    }
+
+    #[test]
+    fn test_zen_rules() {
+        let config = Config {
+            zen_tick_delay: Some(1000),
+            ..Config::default()
+        };
+        let board = GameMode::Zen.new_board(&config, 0, 1);
+        assert_eq!(board.rules().gravity, Gravity::Fixed(1000));
+        assert!(board.rules().top_out_clears);
+        assert_eq!(GameMode::Marathon.rules(&config), BoardRules::default());
+    }
+}
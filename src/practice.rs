@@ -0,0 +1,224 @@
+use crate::board::{Board, BoardEvent};
+use crate::replay::{RecordedAction, Replay};
+use crate::stats::GameStats;
+
+/// Most placements that can be undone.
+const MAX_UNDO: usize = 1000;
+
+/// The game as it was when a piece spawned.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// the board with its bag and random state, so the same pieces follow
+    pub board: Board,
+    pub stats: GameStats,
+    /// number of replay actions that lead to the board
+    pub actions: usize,
+    /// frames the game had run
+    pub frames: u64,
+}
+
+/// Placements of a practice game that can be undone and redone.
+#[derive(Debug)]
+pub struct Practice {
+    /// start of the falling piece, none between a lock and the next spawn
+    start: Option<Snapshot>,
+    undo: Vec<Snapshot>,
+    /// starts of the undone pieces with the actions that placed the ones before
+    redo: Vec<(Snapshot, Vec<RecordedAction>)>,
+}
+
+impl Practice {
+    pub fn new(start: Snapshot) -> Practice {
+        Practice {
+            start: Some(start),
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Notes what happened on the board, returns whether the next piece just
+    /// spawned and its start should be given with [`Practice::start_piece`].
+    pub fn record(&mut self, event: &BoardEvent) -> bool {
+        match event {
+            BoardEvent::PieceLocked { .. } => {
+                if let Some(start) = self.start.take() {
+                    self.undo.push(start);
+                    if self.undo.len() > MAX_UNDO {
+                        self.undo.remove(0);
+                    }
+                    self.redo.clear();
+                }
+                false
+            }
+            BoardEvent::PieceSpawned { .. } => self.start.is_none(),
+            _ => false,
+        }
+    }
+
+    pub fn start_piece(&mut self, start: Snapshot) {
+        self.start = Some(start);
+    }
+
+    /// Goes back to the start of the last placed piece, taking the actions
+    /// since then out of `replay`.
+    pub fn undo(&mut self, replay: &mut Replay) -> Option<Snapshot> {
+        let start = self.start.as_ref()?;
+        let previous = self.undo.pop()?;
+        let mut undone = replay.rewind(previous.actions);
+        undone.truncate(start.actions - previous.actions);
+        let start = self.start.replace(previous.clone());
+        self.redo.extend(start.map(|start| (start, undone)));
+        Some(previous)
+    }
+
+    /// Places the last undone piece again the way it was placed, putting its
+    /// actions back into `replay`.
+    pub fn redo(&mut self, replay: &mut Replay) -> Option<Snapshot> {
+        let start = self.start.as_ref()?;
+        let (next, actions) = self.redo.pop()?;
+        replay.rewind(start.actions);
+        replay.extend(actions);
+        self.undo.extend(self.start.replace(next.clone()));
+        Some(next)
+    }
+
+    /// Lines for the stats panel.
+    pub fn lines(&self) -> Vec<String> {
+        vec![
+            format!("undo: ctrl+z ({})", self.undo.len()),
+            format!("redo: ctrl+y ({})", self.redo.len()),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Action;
+    use crate::config::Config;
+    use crate::mode::GameMode;
+    use crate::tetromino::Direction;
+    use std::time::Duration;
+
+    /// Plays `action` like the app does, taking a snapshot when a piece spawns.
+    fn act(board: &mut Board, practice: &mut Practice, replay: &mut Replay, action: Action) {
+        replay.record(Duration::ZERO, 0, action, true);
+        board.apply(action).expect("failed to apply action");
+        let mut spawned = false;
+        for event in board.take_events() {
+            spawned |= practice.record(&event);
+        }
+        if spawned {
+            practice.start_piece(Snapshot {
+                board: board.clone(),
+                stats: GameStats::default(),
+                actions: replay.actions().len(),
+                frames: 0,
+            });
+        }
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let config = Config {
+            line_clear_delay: 0,
+            ..Config::default()
+        };
+        let mut replay = Replay::new(config, 7, GameMode::Practice, 1);
+        let mut board = replay.new_board();
+        board.take_events();
+        let mut practice = Practice::new(Snapshot {
+            board: board.clone(),
+            stats: GameStats::default(),
+            actions: 0,
+            frames: 0,
+        });
+        let first = board.grid().to_vec();
+        act(&mut board, &mut practice, &mut replay, Action::HardDrop);
+        let second = board.grid().to_vec();
+        let next = board.calc_next_piece(1);
+        act(
+            &mut board,
+            &mut practice,
+            &mut replay,
+            Action::Move(Direction::Left),
+        );
+        act(&mut board, &mut practice, &mut replay, Action::HardDrop);
+        act(
+            &mut board,
+            &mut practice,
+            &mut replay,
+            Action::Move(Direction::Right),
+        );
+
+        let mut snapshot = practice.undo(&mut replay).expect("nothing to undo");
+        assert_eq!(snapshot.board.grid(), second);
+        assert_eq!(snapshot.board.calc_next_piece(1), next);
+        assert_eq!(replay.actions().len(), 1);
+        snapshot = practice.undo(&mut replay).expect("nothing to undo");
+        assert_eq!(snapshot.board.grid(), first);
+        assert!(practice.undo(&mut replay).is_none());
+
+        practice.redo(&mut replay).expect("nothing to redo");
+        snapshot = practice.redo(&mut replay).expect("nothing to redo");
+        assert!(practice.redo(&mut replay).is_none());
+        // the replay leads to the redone board
+        let mut replayed = replay.new_board();
+        for step in replay.steps() {
+            replayed.apply(step.action).expect("failed to replay");
+        }
+        assert_eq!(replayed.grid(), snapshot.board.grid());
+        assert_eq!(replay.actions().len(), 3);
+    }
+
+    #[test]
+    fn test_new_placement_drops_redo() {
+        let mut replay = Replay::new(Config::default(), 7, GameMode::Practice, 1);
+        let mut board = replay.new_board();
+        board.take_events();
+        let mut practice = Practice::new(Snapshot {
+            board: board.clone(),
+            stats: GameStats::default(),
+            actions: 0,
+            frames: 0,
+        });
+        act(&mut board, &mut practice, &mut replay, Action::HardDrop);
+        act(
+            &mut board,
+            &mut practice,
+            &mut replay,
+            Action::Move(Direction::Left),
+        );
+        act(&mut board, &mut practice, &mut replay, Action::HardDrop);
+
+        // undoing the second piece takes its actions out of the replay
+        board = practice.undo(&mut replay).expect("nothing to undo").board;
+        assert_eq!(replay.actions().len(), 1);
+        assert_eq!(practice.redo.len(), 1);
+
+        // placing it somewhere else leaves nothing to redo
+        act(
+            &mut board,
+            &mut practice,
+            &mut replay,
+            Action::Move(Direction::Right),
+        );
+        assert_eq!(practice.redo.len(), 1);
+        act(&mut board, &mut practice, &mut replay, Action::HardDrop);
+        assert!(practice.redo.is_empty());
+        assert!(practice.redo(&mut replay).is_none());
+        let actions = replay
+            .actions()
+            .iter()
+            .map(|recorded| recorded.action)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            [
+                Action::HardDrop,
+                Action::Move(Direction::Right),
+                Action::HardDrop
+            ]
+        );
+    }
+}
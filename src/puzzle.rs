@@ -0,0 +1,343 @@
+use anyhow::Result;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::board::{BoardEvent, BoardSetup};
+use crate::config::find_config_dir;
+use crate::menu::cycle;
+use crate::tetromino::TetrominoShape;
+
+const SOLVED_FILE_NAME: &str = "solved.ron";
+/// Puzzle packs that come with the game, as (name, file contents).
+const BUNDLED_PACKS: [(&str, &str); 1] = [("basics", include_str!("../puzzles/basics.ron"))];
+
+/// What has to be done to solve a puzzle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PuzzleGoal {
+    /// clear this many lines in total
+    ClearLines(u32),
+    /// clear two lines with a T-spin
+    TSpinDouble,
+    /// clear every cell of the board
+    PerfectClear,
+    /// lock this many pieces without topping out
+    Survive(u32),
+}
+
+impl PuzzleGoal {
+    pub fn description(&self) -> String {
+        match self {
+            PuzzleGoal::ClearLines(1) => "clear a line".to_string(),
+            PuzzleGoal::ClearLines(lines) => format!("clear {lines} lines"),
+            PuzzleGoal::TSpinDouble => "perform a T-spin double".to_string(),
+            PuzzleGoal::PerfectClear => "perfect clear".to_string(),
+            PuzzleGoal::Survive(pieces) => format!("survive {pieces} pieces"),
+        }
+    }
+}
+
+/// A starting board, the pieces to play it with and a goal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Puzzle {
+    pub name: String,
+    /// rows of the stack, `.` for empty cells, `#` for garbage and the
+    /// letter of a shape for its color, the last one is the bottom row
+    pub board: Vec<String>,
+    pub queue: Vec<TetrominoShape>,
+    #[serde(default)]
+    pub hold: Option<TetrominoShape>,
+    /// do random bags follow the queue, or does the puzzle fail when it runs out
+    #[serde(default)]
+    pub then_random: bool,
+    pub goal: PuzzleGoal,
+}
+
+impl Puzzle {
+    /// How the board of the puzzle starts.
+    pub fn setup(&self) -> BoardSetup {
+        BoardSetup {
+            rows: self.board.clone(),
+            queue: self.queue.clone(),
+            hold: self.hold,
+            then_random: self.then_random,
+        }
+    }
+}
+
+/// Puzzles that are played in order, as they are written in a pack file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PuzzlePack {
+    pub name: String,
+    pub puzzles: Vec<Puzzle>,
+}
+
+impl PuzzlePack {
+    /// Reads a pack, checking that its puzzles fit on a board of `board_size`.
+    pub fn parse(text: &str, board_size: (usize, usize)) -> Result<PuzzlePack, String> {
+        let pack = ron::from_str::<PuzzlePack>(text).map_err(|error| error.to_string())?;
+        for puzzle in &pack.puzzles {
+            puzzle
+                .setup()
+                .validate(board_size)
+                .map_err(|problem| format!("puzzle `{}` {problem}", puzzle.name))?;
+        }
+        Ok(pack)
+    }
+
+    /// The bundled packs followed by the ones in the puzzles directory, and
+    /// what is wrong with the files that couldn't be read.
+    pub fn load_all(board_size: (usize, usize)) -> (Vec<PuzzlePack>, Vec<String>) {
+        let mut packs = Vec::new();
+        let mut problems = Vec::new();
+        for (name, text) in BUNDLED_PACKS {
+            match PuzzlePack::parse(text, board_size) {
+                Ok(pack) => packs.push(pack),
+                Err(problem) => problems.push(format!("bundled pack {name}: {problem}")),
+            }
+        }
+        let mut paths = std::fs::read_dir(puzzles_dir())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            let pack = std::fs::read_to_string(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|text| PuzzlePack::parse(&text, board_size));
+            match pack {
+                Ok(pack) => packs.push(pack),
+                Err(problem) => problems.push(format!("{}: {problem}", path.display())),
+            }
+        }
+        (packs, problems)
+    }
+}
+
+pub fn puzzles_dir() -> String {
+    find_config_dir() + "puzzles/"
+}
+
+/// Names of the solved puzzles, as `pack/puzzle`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SolvedPuzzles {
+    solved: BTreeSet<String>,
+}
+
+impl SolvedPuzzles {
+    /// Reads the solved puzzles file, starting with none solved if there is none.
+    pub fn load() -> Self {
+        std::fs::read_to_string(find_config_dir() + SOLVED_FILE_NAME)
+            .ok()
+            .and_then(|solved| ron::from_str(&solved).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::create_dir_all(find_config_dir())?;
+        std::fs::write(
+            find_config_dir() + SOLVED_FILE_NAME,
+            to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    pub fn is_solved(&self, pack: &str, puzzle: &str) -> bool {
+        self.solved.contains(&solved_key(pack, puzzle))
+    }
+
+    pub fn insert(&mut self, pack: &str, puzzle: &str) {
+        self.solved.insert(solved_key(pack, puzzle));
+    }
+}
+
+fn solved_key(pack: &str, puzzle: &str) -> String {
+    format!("{pack}/{puzzle}")
+}
+
+/// State of the puzzle selection, every puzzle of every pack in one list.
+#[derive(Debug)]
+pub struct PuzzleMenu {
+    pub packs: Vec<PuzzlePack>,
+    pub solved: SolvedPuzzles,
+    /// index into the puzzles of all packs
+    pub selected: usize,
+}
+
+impl PuzzleMenu {
+    pub fn new(packs: Vec<PuzzlePack>) -> Self {
+        Self {
+            packs,
+            solved: SolvedPuzzles::load(),
+            selected: 0,
+        }
+    }
+
+    /// Every puzzle with the name of its pack.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Puzzle)> {
+        self.packs.iter().flat_map(|pack| {
+            pack.puzzles
+                .iter()
+                .map(move |puzzle| (pack.name.as_str(), puzzle))
+        })
+    }
+
+    pub fn select(&mut self, down: bool) {
+        let len = self.entries().count();
+        if len > 0 {
+            self.selected = cycle(self.selected, len, down);
+        }
+    }
+
+    /// Selects the puzzle after `run` if it was solved, `run` again if it
+    /// wasn't, or the first unsolved one.
+    pub fn select_from(&mut self, run: Option<&PuzzleRun>) {
+        let entries = self.entries().collect::<Vec<_>>();
+        let position = match run {
+            Some(run) => entries
+                .iter()
+                .position(|(pack, puzzle)| *pack == run.pack && puzzle.name == run.puzzle.name)
+                .map(|index| (index + usize::from(run.is_solved())) % entries.len()),
+            None => entries
+                .iter()
+                .position(|(pack, puzzle)| !self.solved.is_solved(pack, &puzzle.name)),
+        };
+        self.selected = position.unwrap_or(0);
+    }
+
+    /// A run of the selected puzzle.
+    pub fn start(&self) -> Option<PuzzleRun> {
+        let (pack, puzzle) = self.entries().nth(self.selected)?;
+        Some(PuzzleRun::new(pack.to_string(), puzzle.clone()))
+    }
+}
+
+/// A puzzle being played, and how far along its goal is.
+#[derive(Debug, Clone)]
+pub struct PuzzleRun {
+    pub pack: String,
+    pub puzzle: Puzzle,
+    lines: u32,
+    pieces: u32,
+    solved: bool,
+}
+
+impl PuzzleRun {
+    pub fn new(pack: String, puzzle: Puzzle) -> PuzzleRun {
+        PuzzleRun {
+            pack,
+            puzzle,
+            lines: 0,
+            pieces: 0,
+            solved: false,
+        }
+    }
+
+    /// Counts something that happened on the board towards the goal.
+    pub fn record(&mut self, event: &BoardEvent) {
+        match (event, self.puzzle.goal) {
+            (BoardEvent::PieceLocked { .. }, PuzzleGoal::Survive(pieces)) => {
+                self.pieces += 1;
+                self.solved |= self.pieces >= pieces;
+            }
+            (BoardEvent::LinesCleared { count, .. }, PuzzleGoal::ClearLines(lines)) => {
+                self.lines += u32::from(*count);
+                self.solved |= self.lines >= lines;
+            }
+            (
+                BoardEvent::LinesCleared {
+                    count: 2,
+                    tspin: true,
+                    ..
+                },
+                PuzzleGoal::TSpinDouble,
+            )
+            | (BoardEvent::PerfectClear, PuzzleGoal::PerfectClear) => self.solved = true,
+            _ => {}
+        }
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.solved
+    }
+
+    /// Lines for the stats panel.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("puzzle: {}", self.puzzle.name),
+            format!("goal: {}", self.puzzle.goal.description()),
+        ];
+        match self.puzzle.goal {
+            PuzzleGoal::ClearLines(goal) => lines.push(format!("progress: {}/{goal}", self.lines)),
+            PuzzleGoal::Survive(goal) => lines.push(format!("progress: {}/{goal}", self.pieces)),
+            PuzzleGoal::TSpinDouble | PuzzleGoal::PerfectClear => {}
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{Action, Board, TetrominoPositionError};
+    use crate::config::Config;
+
+    #[test]
+    fn test_bundled_packs() {
+        let (packs, problems) = PuzzlePack::load_all(Config::default().board_size);
+        assert_eq!(problems, Vec::<String>::new());
+        assert!(packs[0].puzzles.len() >= 4);
+    }
+
+    #[test]
+    fn test_parse_pack() {
+        let pack = PuzzlePack::parse(
+            r#"(name: "test", puzzles: [
+                (name: "o", board: ["x........."], queue: [O], goal: PerfectClear),
+            ])"#,
+            (10, 20),
+        );
+        assert_eq!(
+            pack,
+            Err(
+                "puzzle `o` `x` in row `x.........` is not `.`, `#` or the letter of a shape"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn test_solve_puzzle() {
+        let puzzle = Puzzle {
+            name: "o".to_string(),
+            board: vec!["########..".to_string(), "IIIIJJJ#..".to_string()],
+            queue: vec![TetrominoShape::O, TetrominoShape::T],
+            hold: None,
+            then_random: false,
+            goal: PuzzleGoal::PerfectClear,
+        };
+        let config = Config {
+            line_clear_delay: 0,
+            ..Config::default()
+        };
+        let mut board = Board::from_setup(&config, 0, &puzzle.setup());
+        let mut run = PuzzleRun::new("test".to_string(), puzzle);
+        for _ in 0..4 {
+            board
+                .apply(Action::Move(crate::tetromino::Direction::Right))
+                .expect("failed to move");
+        }
+        board.apply(Action::HardDrop).expect("failed to drop");
+        for event in board.take_events() {
+            run.record(&event);
+        }
+        assert!(run.is_solved());
+        // the T is the last piece of the queue
+        assert!(matches!(
+            board.apply(Action::HardDrop),
+            Err(TetrominoPositionError::OutOfPieces)
+        ));
+    }
+}
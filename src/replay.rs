@@ -0,0 +1,314 @@
+use anyhow::Result;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    board::{Action, Board, BoardSetup, FRAME},
+    clock::Stopwatch,
+    config::{find_config_dir, Config},
+    mode::GameMode,
+};
+
+const REPLAYS_DIR_NAME: &str = "replays/";
+
+/// An action of a recorded game and when it was applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedAction {
+    /// in-game time of the action, to play it back at the same pace
+    pub time: Duration,
+    /// frames the game had run before the action, frames only run gravity so
+    /// they aren't recorded themselves
+    pub frame: u64,
+    pub action: Action,
+    /// was the key pressed, as opposed to repeating while held down, see
+    /// [`crate::app::App::act_held`]
+    pub pressed: bool,
+}
+
+/// A recorded game: how its board was set up and every action applied to it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    /// configuration the game was played with
+    config: Config,
+    seed: u64,
+    mode: GameMode,
+    starting_level: u8,
+    actions: Vec<RecordedAction>,
+    /// frames the game ran for, pieces can still fall and lock after the last action
+    #[serde(default)]
+    frames: u64,
+    /// in-game time the game ended at
+    #[serde(default)]
+    end: Duration,
+    /// board the game started from instead of the mode's, for puzzles
+    #[serde(default)]
+    setup: Option<BoardSetup>,
+}
+
+impl Replay {
+    pub fn new(config: Config, seed: u64, mode: GameMode, starting_level: u8) -> Self {
+        Self {
+            config,
+            seed,
+            mode,
+            starting_level,
+            actions: Vec::new(),
+            frames: 0,
+            end: Duration::ZERO,
+            setup: None,
+        }
+    }
+
+    /// Starts the game from `setup` instead of the starting board of the mode.
+    pub fn with_setup(self, setup: BoardSetup) -> Self {
+        Self {
+            setup: Some(setup),
+            ..self
+        }
+    }
+
+    /// Constructs the board the recorded game started with.
+    pub fn new_board(&self) -> Board {
+        match &self.setup {
+            Some(setup) => {
+                let mut board = Board::from_setup(&self.config, self.seed, setup);
+                board.set_rules(self.mode.rules(&self.config));
+                board
+            }
+            None => self
+                .mode
+                .new_board(&self.config, self.seed, self.starting_level),
+        }
+    }
+
+    /// Records `action`, applied `time` into the game after its first `frame`
+    /// frames, and whether its key was `pressed` or held down.
+    pub fn record(&mut self, time: Duration, frame: u64, action: Action, pressed: bool) {
+        self.actions.push(RecordedAction {
+            time,
+            frame,
+            action,
+            pressed,
+        });
+    }
+
+    /// Notes that the game ended `time` into it, after `frames` frames.
+    pub fn finish(&mut self, frames: u64, time: Duration) {
+        self.frames = frames;
+        self.end = time;
+    }
+
+    pub fn actions(&self) -> &[RecordedAction] {
+        &self.actions
+    }
+
+    /// Takes out the actions after the first `len`, to undo them.
+    pub fn rewind(&mut self, len: usize) -> Vec<RecordedAction> {
+        self.actions.split_off(len.min(self.actions.len()))
+    }
+
+    /// Puts actions taken out with [`Replay::rewind`] back.
+    pub fn extend(&mut self, actions: Vec<RecordedAction>) {
+        self.actions.extend(actions);
+    }
+
+    /// Every action of the game in the order it was applied, with a gravity
+    /// action for each frame, see [`Playback`] to play them back over time.
+    pub fn steps(&self) -> impl Iterator<Item = RecordedAction> + '_ {
+        let mut cursor = Cursor::default();
+        std::iter::from_fn(move || {
+            let step = cursor.peek(self)?;
+            cursor.advance(&step);
+            Some(step)
+        })
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn starting_level(&self) -> u8 {
+        self.starting_level
+    }
+
+    /// In-game time the game ended at.
+    pub fn duration(&self) -> Duration {
+        self.actions
+            .last()
+            .map_or(self.end, |last| last.time.max(self.end))
+    }
+
+    /// Reads a replay saved with [`Replay::save`].
+    pub fn load(path: &str) -> Result<Self> {
+        Ok(ron::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Writes the replay to a new file in the replays directory, returns its path.
+    pub fn save(&self) -> Result<String> {
+        let dir = find_config_dir() + REPLAYS_DIR_NAME;
+        std::fs::create_dir_all(&dir)?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let path = format!("{dir}{}-{timestamp}.ron", self.mode.name());
+        std::fs::write(&path, to_string_pretty(self, PrettyConfig::default())?)?;
+        Ok(path)
+    }
+}
+
+/// How far a replay has been played: the actions applied and the frames run.
+#[derive(Debug, Clone, Copy, Default)]
+struct Cursor {
+    next_action: usize,
+    frame: u64,
+}
+
+impl Cursor {
+    /// The next step of `replay`: its next action once the frames before it
+    /// have run, otherwise a gravity action for the next frame.
+    fn peek(&self, replay: &Replay) -> Option<RecordedAction> {
+        match replay.actions.get(self.next_action) {
+            Some(recorded) if recorded.frame <= self.frame => Some(*recorded),
+            next if next.is_some() || self.frame < replay.frames => Some(RecordedAction {
+                time: FRAME * (self.frame + 1) as u32,
+                frame: self.frame,
+                action: Action::Gravity,
+                pressed: false,
+            }),
+            _ => None,
+        }
+    }
+
+    fn advance(&mut self, step: &RecordedAction) {
+        if step.action == Action::Gravity {
+            self.frame += 1;
+        } else {
+            self.next_action += 1;
+        }
+    }
+}
+
+/// Plays back a [`Replay`] at the speed it was recorded.
+#[derive(Debug)]
+pub struct Playback {
+    cursor: Cursor,
+    clock: Stopwatch,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Playback {
+    pub fn new() -> Self {
+        Self {
+            cursor: Cursor::default(),
+            clock: Stopwatch::started(),
+        }
+    }
+
+    /// Returns the next step of `replay` whose time has passed, if there is
+    /// one, see [`Replay::steps`].
+    pub fn next_due_action(&mut self, replay: &Replay) -> Option<RecordedAction> {
+        let step = self.cursor.peek(replay)?;
+        if step.time > self.clock.elapsed() {
+            return None;
+        }
+        self.cursor.advance(&step);
+        Some(step)
+    }
+
+    /// How far into the recorded game the playback is.
+    pub fn elapsed(&self) -> Duration {
+        self.clock.elapsed()
+    }
+
+    pub fn is_finished(&self, replay: &Replay) -> bool {
+        self.cursor.peek(replay).is_none()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        !self.clock.is_running()
+    }
+
+    pub fn pause(&mut self, paused: bool) {
+        if paused {
+            self.clock.stop();
+        } else {
+            self.clock.start();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetromino::Direction;
+
+    #[test]
+    fn test_playback_order() {
+        let mut replay = Replay::new(Config::default(), 0, GameMode::Marathon, 1);
+        replay.record(Duration::ZERO, 0, Action::Move(Direction::Left), true);
+        replay.record(Duration::ZERO, 0, Action::HardDrop, true);
+        replay.record(Duration::from_secs(3600), 2, Action::Hold, true);
+
+        let mut playback = Playback::new();
+        let mut next = || playback.next_due_action(&replay).map(|step| step.action);
+        assert_eq!(next(), Some(Action::Move(Direction::Left)));
+        assert_eq!(next(), Some(Action::HardDrop));
+        assert_eq!(next(), None);
+        assert!(!playback.is_finished(&replay));
+    }
+
+    #[test]
+    fn test_steps_run_frames() {
+        let mut replay = Replay::new(Config::default(), 0, GameMode::Marathon, 1);
+        replay.record(Duration::ZERO, 0, Action::Move(Direction::Left), true);
+        replay.record(FRAME * 2, 2, Action::HardDrop, true);
+        replay.finish(3, FRAME * 3);
+        let steps = replay
+            .steps()
+            .map(|step| (step.frame, step.action))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            [
+                (0, Action::Move(Direction::Left)),
+                (0, Action::Gravity),
+                (1, Action::Gravity),
+                (2, Action::HardDrop),
+                (2, Action::Gravity),
+            ]
+        );
+        assert_eq!(replay.duration(), FRAME * 3);
+    }
+
+    #[test]
+    fn test_serialize_roundtrip() {
+        let mut replay = Replay::new(Config::default(), 7, GameMode::Sprint, 3);
+        replay.record(Duration::from_millis(250), 15, Action::Rotate(true), true);
+        replay.record(
+            Duration::from_millis(900),
+            54,
+            Action::SoftDrop(false),
+            true,
+        );
+        replay.record(
+            Duration::from_millis(950),
+            57,
+            Action::Move(Direction::Left),
+            false,
+        );
+        let text = to_string_pretty(&replay, PrettyConfig::default()).expect("failed to serialize");
+        assert_eq!(
+            ron::from_str::<Replay>(&text).expect("failed to parse"),
+            replay
+        );
+        assert_eq!(replay.duration(), Duration::from_millis(950));
+    }
+}
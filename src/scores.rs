@@ -0,0 +1,144 @@
+use anyhow::Result;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{config::find_config_dir, mode::GameMode};
+
+const SCORES_FILE_NAME: &str = "scores.ron";
+const MAX_ENTRIES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    #[serde(default)]
+    pub mode: GameMode,
+    pub score: u128,
+    pub level: u8,
+    pub lines_cleared: u128,
+    pub time: Duration,
+}
+impl ScoreEntry {
+    fn is_better_than(&self, other: &ScoreEntry) -> bool {
+        if self.mode.ranks_by_time() {
+            self.time < other.time
+        } else {
+            self.score > other.score
+        }
+    }
+}
+
+/// The best finished games of each mode, best first.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HighScores {
+    entries: Vec<ScoreEntry>,
+}
+
+impl HighScores {
+    /// Reads the high scores file, starting with an empty table if there is none.
+    pub fn load() -> Self {
+        std::fs::read_to_string(find_config_dir() + SCORES_FILE_NAME)
+            .ok()
+            .and_then(|scores| ron::from_str(&scores).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::create_dir_all(find_config_dir())?;
+        std::fs::write(
+            find_config_dir() + SCORES_FILE_NAME,
+            to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    pub fn entries(&self, mode: GameMode) -> impl Iterator<Item = &ScoreEntry> {
+        self.entries.iter().filter(move |entry| entry.mode == mode)
+    }
+
+    pub fn best(&self, mode: GameMode) -> Option<&ScoreEntry> {
+        self.entries(mode).next()
+    }
+
+    /// Adds a finished game to the table, returns true if it is a new personal best.
+    pub fn submit(&mut self, entry: ScoreEntry) -> bool {
+        let mode = entry.mode;
+        let personal_best = self
+            .best(mode)
+            .is_none_or(|best| entry.is_better_than(best));
+        let index = self
+            .entries
+            .iter()
+            .position(|existing| existing.mode == mode && entry.is_better_than(existing))
+            .unwrap_or(self.entries.len());
+        self.entries.insert(index, entry);
+
+        // keep only the best entries of the mode
+        let mut kept = 0;
+        self.entries.retain(|entry| {
+            if entry.mode != mode {
+                return true;
+            }
+            kept += 1;
+            kept <= MAX_ENTRIES
+        });
+        personal_best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: u128) -> ScoreEntry {
+        ScoreEntry {
+            mode: GameMode::Marathon,
+            score,
+            level: 1,
+            lines_cleared: 0,
+            time: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_submit() {
+        let mut scores = HighScores::default();
+        assert!(scores.submit(entry(100)));
+        assert!(!scores.submit(entry(50)));
+        assert!(!scores.submit(entry(100)));
+        assert!(scores.submit(entry(200)));
+        assert_eq!(scores.best(GameMode::Marathon), Some(&entry(200)));
+
+        for _ in 0..MAX_ENTRIES {
+            scores.submit(entry(10));
+        }
+        assert_eq!(scores.entries.len(), MAX_ENTRIES);
+        assert_eq!(scores.entries.last(), Some(&entry(10)));
+    }
+
+    #[test]
+    fn test_submit_by_time() {
+        let sprint = |secs| ScoreEntry {
+            mode: GameMode::Sprint,
+            time: Duration::from_secs(secs),
+            ..entry(0)
+        };
+        let mut scores = HighScores::default();
+        scores.submit(entry(100));
+        assert!(scores.submit(sprint(90)));
+        assert!(scores.submit(sprint(60)));
+        assert!(!scores.submit(sprint(120)));
+        assert_eq!(scores.best(GameMode::Sprint), Some(&sprint(60)));
+        assert_eq!(scores.best(GameMode::Marathon), Some(&entry(100)));
+    }
+
+    #[test]
+    fn test_serialize_roundtrip() {
+        let mut scores = HighScores::default();
+        scores.submit(entry(u128::MAX));
+        let text = to_string_pretty(&scores, PrettyConfig::default()).expect("failed to serialize");
+        assert_eq!(
+            ron::from_str::<HighScores>(&text).expect("failed to parse"),
+            scores
+        );
+    }
+}
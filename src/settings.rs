@@ -0,0 +1,627 @@
+use ratatui::style::Color;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::animation::ClearAnimation;
+use crate::color::ColorDepth;
+use crate::config::{BagType, Config, MAX_GRAVITY, MIN_BOARD_HEIGHT, MIN_BOARD_WIDTH};
+use crate::grid::{CellStyle, CellTexture, ShapeMarks};
+use crate::menu::cycle;
+use crate::tetromino::TetrominoShape;
+use crate::theme::{Theme, BUNDLED_THEMES};
+
+/// Value of the color depth field that detects it from the terminal.
+const AUTO_COLOR_DEPTH: &str = "auto";
+/// Value of the color fields that use the color of the theme.
+const FROM_THEME: &str = "theme";
+/// Value of the gravity field when the gravity of the levels is used.
+const LEVEL_GRAVITY: &str = "off";
+/// Value of the zen gravity field when pieces only fall while soft dropped.
+const NO_GRAVITY: &str = "off";
+/// Value of the soft drop factor field when soft dropping is instant.
+const INSTANT_SOFT_DROP: &str = "inf";
+
+/// Keys of the [`Config`] that have no fields and are only changed in the config file.
+pub const FILE_ONLY_KEYS: [&str; 1] = ["palettes"];
+
+/// A single editable value of the [`Config`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsField {
+    BoardWidth,
+    BoardHeight,
+    NumberOfPreviews,
+    LockDelay,
+    SoftDropFactor,
+    Das,
+    Arr,
+    LineClearDelay,
+    EntryDelay,
+    LineEntryDelay,
+    Gravity,
+    BagType,
+    CellStyle,
+    CellTexture,
+    ShapeMarks,
+    ClearAnimation,
+    ActionText,
+    RenderRate,
+    ColorDepth,
+    Theme,
+    ZenGravity,
+    ZenScoring,
+    ZenGarbage,
+    /// gravity delay starting at a level
+    TickDelay(u8),
+    TetrominoColor(TetrominoShape),
+    GhostColor(TetrominoShape),
+    BorderColor(TetrominoShape),
+}
+
+impl SettingsField {
+    /// Every field of `config`, in the order they are shown.
+    pub fn all(config: &Config) -> Vec<SettingsField> {
+        let mut fields = vec![
+            SettingsField::BoardWidth,
+            SettingsField::BoardHeight,
+            SettingsField::NumberOfPreviews,
+            SettingsField::LockDelay,
+            SettingsField::SoftDropFactor,
+            SettingsField::Das,
+            SettingsField::Arr,
+            SettingsField::LineClearDelay,
+            SettingsField::EntryDelay,
+            SettingsField::LineEntryDelay,
+            SettingsField::Gravity,
+            SettingsField::BagType,
+            SettingsField::CellStyle,
+            SettingsField::CellTexture,
+            SettingsField::ShapeMarks,
+            SettingsField::ClearAnimation,
+            SettingsField::ActionText,
+            SettingsField::RenderRate,
+            SettingsField::ColorDepth,
+            SettingsField::Theme,
+            SettingsField::ZenGravity,
+            SettingsField::ZenScoring,
+            SettingsField::ZenGarbage,
+        ];
+        fields.extend(
+            config
+                .tick_delay
+                .keys()
+                .map(|level| SettingsField::TickDelay(*level)),
+        );
+        fields.extend(TetrominoShape::ALL.map(SettingsField::TetrominoColor));
+        fields.extend(TetrominoShape::ALL.map(SettingsField::GhostColor));
+        fields.extend(TetrominoShape::ALL.map(SettingsField::BorderColor));
+        fields
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            SettingsField::BoardWidth => "board width".to_string(),
+            SettingsField::BoardHeight => "board height".to_string(),
+            SettingsField::NumberOfPreviews => "number of previews".to_string(),
+            SettingsField::LockDelay => "lock delay (frames)".to_string(),
+            SettingsField::SoftDropFactor => "soft drop factor".to_string(),
+            SettingsField::Das => "DAS (frames)".to_string(),
+            SettingsField::Arr => "ARR (frames)".to_string(),
+            SettingsField::LineClearDelay => "line clear delay (ms)".to_string(),
+            SettingsField::EntryDelay => "entry delay (ms)".to_string(),
+            SettingsField::LineEntryDelay => "line entry delay (ms)".to_string(),
+            SettingsField::Gravity => "gravity (G)".to_string(),
+            SettingsField::BagType => "bag type".to_string(),
+            SettingsField::CellStyle => "cell style".to_string(),
+            SettingsField::CellTexture => "cell texture".to_string(),
+            SettingsField::ShapeMarks => "shape marks".to_string(),
+            SettingsField::ClearAnimation => "clear animation".to_string(),
+            SettingsField::ActionText => "action text".to_string(),
+            SettingsField::RenderRate => "render rate (fps)".to_string(),
+            SettingsField::ColorDepth => "colors".to_string(),
+            SettingsField::Theme => "theme".to_string(),
+            SettingsField::ZenGravity => "zen gravity (ms)".to_string(),
+            SettingsField::ZenScoring => "zen scoring".to_string(),
+            SettingsField::ZenGarbage => "zen garbage every (pieces)".to_string(),
+            SettingsField::TickDelay(level) => format!("gravity from level {level} (ms)"),
+            SettingsField::TetrominoColor(shape) => format!("{shape:?} color"),
+            SettingsField::GhostColor(shape) => format!("{shape:?} ghost color"),
+            SettingsField::BorderColor(shape) => format!("{shape:?} border color"),
+        }
+    }
+
+    pub fn value(&self, config: &Config) -> String {
+        match self {
+            SettingsField::BoardWidth => config.board_size.0.to_string(),
+            SettingsField::BoardHeight => config.board_size.1.to_string(),
+            SettingsField::NumberOfPreviews => config.number_of_previews.to_string(),
+            SettingsField::LockDelay => config.lock_delay_frames.to_string(),
+            SettingsField::SoftDropFactor => config
+                .soft_drop_factor
+                .map_or(INSTANT_SOFT_DROP.to_string(), |factor| factor.to_string()),
+            SettingsField::Das => config.das_frames.to_string(),
+            SettingsField::Arr => config.arr_frames.to_string(),
+            SettingsField::LineClearDelay => config.line_clear_delay.to_string(),
+            SettingsField::EntryDelay => config.entry_delay.to_string(),
+            SettingsField::LineEntryDelay => config.line_entry_delay.to_string(),
+            SettingsField::Gravity => config
+                .gravity
+                .map_or(LEVEL_GRAVITY.to_string(), |gravity| gravity.to_string()),
+            SettingsField::BagType => config.bag_type.name().to_string(),
+            SettingsField::CellStyle => config.cell_style.name().to_string(),
+            SettingsField::CellTexture => config.cell_texture.name().to_string(),
+            SettingsField::ShapeMarks => config.shape_marks.name().to_string(),
+            SettingsField::ClearAnimation => config.clear_animation.name().to_string(),
+            SettingsField::ActionText => on_off(config.action_text).to_string(),
+            SettingsField::RenderRate => config.render_rate.to_string(),
+            SettingsField::ColorDepth => config
+                .color_depth
+                .map_or(AUTO_COLOR_DEPTH, |depth| depth.name())
+                .to_string(),
+            SettingsField::Theme => config.theme.clone(),
+            SettingsField::ZenGravity => config
+                .zen_tick_delay
+                .map_or(NO_GRAVITY.to_string(), |delay| delay.to_string()),
+            SettingsField::ZenScoring => on_off(config.zen_scoring).to_string(),
+            SettingsField::ZenGarbage => config.zen_garbage.to_string(),
+            SettingsField::TickDelay(level) => config.tick_delay[level].to_string(),
+            _ => self
+                .color(config)
+                .map_or(FROM_THEME.to_string(), |color| color.to_string()),
+        }
+    }
+
+    /// The color the field is set to, for color fields that replace the theme's color.
+    pub fn color(&self, config: &Config) -> Option<Color> {
+        match self {
+            SettingsField::TetrominoColor(shape) => config.tetromino_color.get(shape).copied(),
+            SettingsField::GhostColor(shape) => config.ghost_color.get(shape).copied(),
+            SettingsField::BorderColor(shape) => config.border_color.get(shape).copied(),
+            _ => None,
+        }
+    }
+
+    /// Parses and validates `value`, only changing `config` if it is valid.
+    pub fn set(&self, config: &mut Config, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        match self {
+            SettingsField::BoardWidth => {
+                config.board_size.0 = parse_in_range(value, MIN_BOARD_WIDTH, 100)?
+            }
+            SettingsField::BoardHeight => {
+                config.board_size.1 = parse_in_range(value, MIN_BOARD_HEIGHT, 100)?
+            }
+            SettingsField::NumberOfPreviews => {
+                config.number_of_previews = parse_in_range(value, 0, config.bag_type.size())?
+            }
+            SettingsField::LockDelay => {
+                config.lock_delay_frames = parse_in_range(value, 1, u8::MAX)?
+            }
+            SettingsField::SoftDropFactor => {
+                config.soft_drop_factor = if value.eq_ignore_ascii_case(INSTANT_SOFT_DROP) {
+                    None
+                } else {
+                    Some(parse_in_range(value, 1, 100)?)
+                }
+            }
+            SettingsField::Das => config.das_frames = parse_in_range(value, 0, u8::MAX)?,
+            SettingsField::Arr => config.arr_frames = parse_in_range(value, 0, u8::MAX)?,
+            SettingsField::LineClearDelay => {
+                config.line_clear_delay = parse_in_range(value, 0, 10_000)?
+            }
+            SettingsField::EntryDelay => config.entry_delay = parse_in_range(value, 0, 10_000)?,
+            SettingsField::LineEntryDelay => {
+                config.line_entry_delay = parse_in_range(value, 0, 10_000)?
+            }
+            SettingsField::Gravity => {
+                config.gravity = if value.eq_ignore_ascii_case(LEVEL_GRAVITY) {
+                    None
+                } else {
+                    match value.parse::<f32>() {
+                        Ok(gravity) if gravity > 0.0 && gravity <= MAX_GRAVITY => Some(gravity),
+                        _ => {
+                            return Err(format!(
+                                "must be {LEVEL_GRAVITY} or more than 0 and at most {MAX_GRAVITY} G"
+                            ))
+                        }
+                    }
+                }
+            }
+            SettingsField::BagType => {
+                let bag_type: BagType = value.parse()?;
+                config.bag_type = bag_type;
+                config.number_of_previews = config.number_of_previews.min(bag_type.size());
+            }
+            SettingsField::CellStyle => config.cell_style = value.parse()?,
+            SettingsField::CellTexture => config.cell_texture = value.parse()?,
+            SettingsField::ShapeMarks => config.shape_marks = value.parse()?,
+            SettingsField::ClearAnimation => config.clear_animation = value.parse()?,
+            SettingsField::ActionText => config.action_text = parse_on_off(value)?,
+            SettingsField::RenderRate => config.render_rate = parse_in_range(value, 1, 240)?,
+            SettingsField::ColorDepth => {
+                config.color_depth = if value.eq_ignore_ascii_case(AUTO_COLOR_DEPTH) {
+                    None
+                } else {
+                    Some(value.parse()?)
+                }
+            }
+            SettingsField::Theme => {
+                Theme::load(value)?;
+                config.theme = value.to_string();
+            }
+            SettingsField::ZenGravity => {
+                config.zen_tick_delay = if value.eq_ignore_ascii_case(NO_GRAVITY) {
+                    None
+                } else {
+                    Some(parse_in_range(value, 1, 10_000)?)
+                }
+            }
+            SettingsField::ZenScoring => config.zen_scoring = parse_on_off(value)?,
+            SettingsField::ZenGarbage => config.zen_garbage = parse_in_range(value, 0, 100)?,
+            SettingsField::TickDelay(level) => {
+                config
+                    .tick_delay
+                    .insert(*level, parse_in_range(value, 1, 10_000)?);
+            }
+            SettingsField::TetrominoColor(shape) => {
+                set_color(&mut config.tetromino_color, *shape, value)?
+            }
+            SettingsField::GhostColor(shape) => set_color(&mut config.ghost_color, *shape, value)?,
+            SettingsField::BorderColor(shape) => {
+                set_color(&mut config.border_color, *shape, value)?
+            }
+        }
+        Ok(())
+    }
+
+    /// Increments or decrements numbers and cycles through bag types and cell looks.
+    pub fn step(&self, config: &mut Config, forward: bool) -> Result<(), String> {
+        match self {
+            SettingsField::BagType => {
+                let index = BagType::ALL
+                    .iter()
+                    .position(|bag_type| *bag_type == config.bag_type)
+                    .unwrap_or(0);
+                let bag_type = BagType::ALL[cycle(index, BagType::ALL.len(), forward)];
+                return self.set(config, bag_type.name());
+            }
+            SettingsField::CellStyle => {
+                let index = CellStyle::ALL
+                    .iter()
+                    .position(|style| *style == config.cell_style)
+                    .unwrap_or(0);
+                config.cell_style = CellStyle::ALL[cycle(index, CellStyle::ALL.len(), forward)];
+                return Ok(());
+            }
+            SettingsField::CellTexture => {
+                let index = CellTexture::ALL
+                    .iter()
+                    .position(|texture| *texture == config.cell_texture)
+                    .unwrap_or(0);
+                config.cell_texture =
+                    CellTexture::ALL[cycle(index, CellTexture::ALL.len(), forward)];
+                return Ok(());
+            }
+            SettingsField::ShapeMarks => {
+                let index = ShapeMarks::ALL
+                    .iter()
+                    .position(|marks| *marks == config.shape_marks)
+                    .unwrap_or(0);
+                config.shape_marks = ShapeMarks::ALL[cycle(index, ShapeMarks::ALL.len(), forward)];
+                return Ok(());
+            }
+            SettingsField::ClearAnimation => {
+                let index = ClearAnimation::ALL
+                    .iter()
+                    .position(|animation| *animation == config.clear_animation)
+                    .unwrap_or(0);
+                config.clear_animation =
+                    ClearAnimation::ALL[cycle(index, ClearAnimation::ALL.len(), forward)];
+                return Ok(());
+            }
+            SettingsField::ActionText => {
+                config.action_text = !config.action_text;
+                return Ok(());
+            }
+            SettingsField::ZenScoring => {
+                config.zen_scoring = !config.zen_scoring;
+                return Ok(());
+            }
+            SettingsField::Theme => {
+                let index = BUNDLED_THEMES
+                    .iter()
+                    .position(|theme| *theme == config.theme)
+                    .unwrap_or(0);
+                config.theme =
+                    BUNDLED_THEMES[cycle(index, BUNDLED_THEMES.len(), forward)].to_string();
+                return Ok(());
+            }
+            SettingsField::ColorDepth => {
+                // auto first, then the depths
+                let depths = std::iter::once(None)
+                    .chain(ColorDepth::ALL.map(Some))
+                    .collect::<Vec<_>>();
+                let index = depths
+                    .iter()
+                    .position(|depth| *depth == config.color_depth)
+                    .unwrap_or(0);
+                config.color_depth = depths[cycle(index, depths.len(), forward)];
+                return Ok(());
+            }
+            _ => {}
+        }
+        let Ok(value) = self.value(config).parse::<i64>() else {
+            return Err("press enter to type a new value".to_string());
+        };
+        self.set(config, &(value + if forward { 1 } else { -1 }).to_string())
+    }
+}
+
+fn parse_in_range<T>(value: &str, min: T, max: T) -> Result<T, String>
+where
+    T: FromStr + PartialOrd + std::fmt::Display + Copy,
+{
+    match value.parse::<T>() {
+        Ok(number) if number >= min && number <= max => Ok(number),
+        _ => Err(format!("must be a number from {min} to {max}")),
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn parse_on_off(value: &str) -> Result<bool, String> {
+    [true, false]
+        .into_iter()
+        .find(|on| on_off(*on).eq_ignore_ascii_case(value))
+        .ok_or_else(|| "must be on or off".to_string())
+}
+
+fn parse_color(value: &str) -> Result<Color, String> {
+    Color::from_str(value)
+        .map_err(|_| format!("must be a color name, a palette index, #rrggbb or {FROM_THEME}"))
+}
+
+/// Replaces the theme's color of `shape`, or goes back to it.
+fn set_color(
+    colors: &mut BTreeMap<TetrominoShape, Color>,
+    shape: TetrominoShape,
+    value: &str,
+) -> Result<(), String> {
+    if value.is_empty() || value.eq_ignore_ascii_case(FROM_THEME) {
+        colors.remove(&shape);
+    } else {
+        colors.insert(shape, parse_color(value)?);
+    }
+    Ok(())
+}
+
+/// State of the settings editor.
+#[derive(Debug)]
+pub struct Settings {
+    /// the edited configuration, only written to disk when saved
+    pub config: Config,
+    pub fields: Vec<SettingsField>,
+    pub selected: usize,
+    /// text typed for the selected field, if it is being edited
+    pub input: Option<String>,
+    /// result of the last action, shown below the fields
+    pub message: Option<String>,
+    /// are there changes that were not saved
+    pub unsaved: bool,
+    /// was the user told that leaving discards unsaved changes
+    discard_warned: bool,
+}
+
+impl Settings {
+    pub fn new(config: Config) -> Self {
+        Self {
+            fields: SettingsField::all(&config),
+            config,
+            selected: 0,
+            input: None,
+            message: None,
+            unsaved: false,
+            discard_warned: false,
+        }
+    }
+
+    pub fn selected_field(&self) -> SettingsField {
+        self.fields[self.selected]
+    }
+
+    pub fn select(&mut self, down: bool) {
+        self.selected = cycle(self.selected, self.fields.len(), down);
+        self.message = None;
+    }
+
+    pub fn step(&mut self, forward: bool) {
+        let result = self.selected_field().step(&mut self.config, forward);
+        self.handle_result(result);
+    }
+
+    /// Starts typing a new value for the selected field.
+    pub fn start_input(&mut self) {
+        self.input = Some(self.selected_field().value(&self.config));
+        self.message = None;
+    }
+
+    pub fn cancel_input(&mut self) {
+        self.input = None;
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        if let Some(input) = &mut self.input {
+            input.push(c);
+        }
+    }
+
+    pub fn delete_char(&mut self) {
+        if let Some(input) = &mut self.input {
+            input.pop();
+        }
+    }
+
+    /// Applies the typed value, keeps the input open if it is invalid.
+    pub fn submit_input(&mut self) {
+        let Some(input) = &self.input else {
+            return;
+        };
+        let result = self.selected_field().set(&mut self.config, input);
+        if result.is_ok() {
+            self.input = None;
+        }
+        self.handle_result(result);
+    }
+
+    fn handle_result(&mut self, result: Result<(), String>) {
+        match result {
+            Ok(()) => {
+                self.unsaved = true;
+                self.discard_warned = false;
+                self.message = None;
+            }
+            Err(error) => self.message = Some(error),
+        }
+    }
+
+    /// Returns true if the editor can be closed, warns once about unsaved changes first.
+    pub fn leave(&mut self) -> bool {
+        if !self.unsaved || self.discard_warned {
+            return true;
+        }
+        self.discard_warned = true;
+        self.message = Some("unsaved changes, press q again to discard them".to_string());
+        false
+    }
+
+    /// Writes the edited configuration to the config file at `path`, returns true if it was saved.
+    pub fn save(&mut self, path: &str) -> bool {
+        match self.config.save(path) {
+            Ok(()) => {
+                self.unsaved = false;
+                self.message = Some(
+                    "saved, board size, bag type and delays apply to the next game".to_string(),
+                );
+                true
+            }
+            Err(error) => {
+                self.message = Some(format!("failed to save: {error}"));
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_validates() {
+        let mut config = Config::default();
+        assert!(SettingsField::BoardWidth.set(&mut config, "3").is_err());
+        assert!(SettingsField::BoardWidth.set(&mut config, "12").is_ok());
+        assert_eq!(config.board_size.0, 12);
+
+        assert!(SettingsField::NumberOfPreviews
+            .set(&mut config, "8")
+            .is_err());
+        SettingsField::BagType
+            .step(&mut config, true)
+            .expect("failed to change bag type");
+        assert_eq!(config.bag_type, BagType::Fourteen);
+        assert!(SettingsField::NumberOfPreviews
+            .set(&mut config, "8")
+            .is_ok());
+
+        let field = SettingsField::GhostColor(TetrominoShape::T);
+        assert!(field.set(&mut config, "not a color").is_err());
+        field.set(&mut config, "#102030").expect("valid color");
+        assert_eq!(field.value(&config), "#102030");
+        field.set(&mut config, "theme").expect("valid color");
+        assert_eq!(field.color(&config), None);
+
+        assert!(SettingsField::Theme.set(&mut config, "plaid").is_err());
+        SettingsField::Theme
+            .step(&mut config, false)
+            .expect("failed to change theme");
+        assert_eq!(config.theme, "tritanopia");
+
+        assert!(SettingsField::ActionText.set(&mut config, "maybe").is_err());
+        SettingsField::ActionText
+            .set(&mut config, "OFF")
+            .expect("valid value");
+        assert!(!config.action_text);
+
+        SettingsField::ZenGravity
+            .set(&mut config, "500")
+            .expect("valid delay");
+        assert_eq!(config.zen_tick_delay, Some(500));
+        SettingsField::ZenGravity
+            .set(&mut config, "off")
+            .expect("valid delay");
+        assert_eq!(config.zen_tick_delay, None);
+
+        SettingsField::SoftDropFactor
+            .step(&mut config, true)
+            .expect("valid factor");
+        assert_eq!(config.soft_drop_factor, Some(9));
+        SettingsField::SoftDropFactor
+            .set(&mut config, "inf")
+            .expect("valid factor");
+        assert_eq!(SettingsField::SoftDropFactor.value(&config), "inf");
+
+        SettingsField::Arr
+            .step(&mut config, false)
+            .expect("valid ARR");
+        assert_eq!(config.arr_frames, 1);
+        assert!(SettingsField::Das.set(&mut config, "256").is_err());
+    }
+
+    #[test]
+    fn test_file_only_keys() {
+        let ron = Config::default().to_ron().expect("failed to serialize");
+        for key in FILE_ONLY_KEYS {
+            assert!(ron.contains(&format!("\n    {key}: ")), "unknown key {key}");
+        }
+    }
+
+    #[test]
+    fn test_edit_gravity() {
+        let mut settings = Settings::new(Config::default());
+        settings.selected = settings
+            .fields
+            .iter()
+            .position(|field| *field == SettingsField::Gravity)
+            .expect("no gravity field");
+        assert_eq!(settings.selected_field().value(&settings.config), "off");
+        for invalid in ["0", "-1", "20.5", "fast"] {
+            assert!(SettingsField::Gravity
+                .set(&mut settings.config, invalid)
+                .is_err());
+        }
+        settings.start_input();
+        settings.input = Some("0.05".to_string());
+        settings.submit_input();
+        assert_eq!(settings.message, None);
+        assert_eq!(settings.config.gravity, Some(0.05));
+
+        let dir = std::env::temp_dir().join(format!("termtris-gravity-{}", std::process::id()));
+        let path = dir.join("config.ron").to_string_lossy().to_string();
+        assert!(settings.save(&path));
+        let text = std::fs::read_to_string(&path).expect("failed to read saved config");
+        let (saved, _) = Config::from_ron(&text).expect("invalid saved config");
+        assert_eq!(saved.gravity, Some(0.05));
+        std::fs::remove_dir_all(&dir).expect("failed to remove dir");
+
+        SettingsField::Gravity
+            .set(&mut settings.config, "OFF")
+            .expect("valid gravity");
+        assert_eq!(settings.config.gravity, None);
+    }
+}
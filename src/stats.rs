@@ -0,0 +1,331 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::board::{Action, BoardEvent};
+use crate::config::find_config_dir;
+use crate::mode::GameMode;
+use crate::tetromino::{Direction, Tetromino, TetrominoShape};
+
+const STATS_DIR_NAME: &str = "stats/";
+
+/// Lines sent by clears of 1 to 4 lines.
+const LINE_ATTACK: [u64; 4] = [0, 1, 2, 4];
+/// Lines sent by T-spin clears of 1 to 3 lines.
+const TSPIN_ATTACK: [u64; 3] = [2, 4, 6];
+/// Extra lines sent by a combo, by the number of clears in a row before it.
+const COMBO_ATTACK: [u64; 11] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+const PERFECT_CLEAR_ATTACK: u64 = 10;
+
+/// Counts of a game, kept up to date from the events of its board and the keys pressed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameStats {
+    /// pieces locked
+    pub pieces: u64,
+    /// keys pressed, keys held down count once
+    pub keys: u64,
+    pub lines: u64,
+    /// clears of 1 to 4 lines
+    pub clears: [u64; 4],
+    /// T-spin clears of 1 to 3 lines
+    pub tspins: [u64; 3],
+    /// garbage lines the clears would send to an opponent
+    pub attack: u64,
+    pub max_combo: u8,
+    /// the current back to back chain
+    pub b2b: u32,
+    pub max_b2b: u32,
+    pub perfect_clears: u64,
+    /// pieces placed with more moves and rotations than needed
+    pub finesse_faults: u64,
+    /// moves and rotations of the current piece
+    #[serde(skip)]
+    piece_keys: u32,
+    #[serde(skip)]
+    board_width: usize,
+}
+
+impl GameStats {
+    pub fn new(board_width: usize) -> GameStats {
+        GameStats {
+            board_width,
+            ..GameStats::default()
+        }
+    }
+
+    /// Counts a key press that applied `action`.
+    pub fn record_key(&mut self, action: Action) {
+        match action {
+            Action::Move(_) | Action::MovePressed(_) | Action::Rotate(_) => {
+                self.keys += 1;
+                self.piece_keys += 1;
+            }
+            Action::Hold | Action::HardDrop | Action::SonicDrop | Action::SoftDrop(true) => {
+                self.keys += 1
+            }
+            Action::Gravity
+            | Action::SoftDrop(false)
+            | Action::MoveReleased(_)
+            | Action::FinishLineClear
+            | Action::FinishEntry => {}
+        }
+    }
+
+    /// Counts something that happened on the board.
+    pub fn record(&mut self, event: &BoardEvent) {
+        match event {
+            BoardEvent::PieceSpawned { .. } => self.piece_keys = 0,
+            BoardEvent::PieceLocked { shape, cells } => {
+                self.pieces += 1;
+                let needed = finesse_keys(*shape, self.board_width, cells);
+                if needed.is_some_and(|needed| self.piece_keys > needed) {
+                    self.finesse_faults += 1;
+                }
+            }
+            BoardEvent::LinesCleared {
+                count,
+                tspin,
+                b2b,
+                combo,
+                ..
+            } => {
+                let count = usize::from(*count).clamp(1, 4);
+                self.lines += count as u64;
+                self.clears[count - 1] += 1;
+                self.attack += match tspin {
+                    true if count <= 3 => {
+                        self.tspins[count - 1] += 1;
+                        TSPIN_ATTACK[count - 1]
+                    }
+                    _ => LINE_ATTACK[count - 1],
+                };
+                if *b2b > 0 {
+                    self.attack += 1;
+                }
+                self.attack += COMBO_ATTACK[usize::from(*combo).min(COMBO_ATTACK.len() - 1)];
+                self.b2b = *b2b;
+                self.max_b2b = self.max_b2b.max(*b2b);
+                self.max_combo = self.max_combo.max(*combo);
+            }
+            BoardEvent::PerfectClear => {
+                self.perfect_clears += 1;
+                self.attack += PERFECT_CLEAR_ATTACK;
+            }
+            BoardEvent::Hold { .. }
+            | BoardEvent::LevelUp { .. }
+            | BoardEvent::TopOut
+            | BoardEvent::GarbageReceived { .. } => {}
+        }
+    }
+
+    /// Pieces per second.
+    pub fn pps(&self, time: Duration) -> f64 {
+        self.pieces as f64 / time.as_secs_f64().max(1.0)
+    }
+
+    /// Attack per minute.
+    pub fn apm(&self, time: Duration) -> f64 {
+        self.attack as f64 * 60.0 / time.as_secs_f64().max(1.0)
+    }
+
+    /// Keys per piece.
+    pub fn kpp(&self) -> f64 {
+        self.keys as f64 / self.pieces.max(1) as f64
+    }
+
+    /// Percentage of the lines cleared by tetrises.
+    pub fn tetris_rate(&self) -> f64 {
+        (self.clears[3] * 4) as f64 * 100.0 / self.lines.max(1) as f64
+    }
+
+    /// Lines for the stats panel, `time` being how long the game has been going.
+    pub fn lines(&self, time: Duration) -> Vec<String> {
+        vec![
+            format!("pieces: {}", self.pieces),
+            format!("pps: {:.2}", self.pps(time)),
+            format!("kpp: {:.2}", self.kpp()),
+            format!("apm: {:.1}", self.apm(time)),
+            format!("max combo: {}", self.max_combo),
+            format!("b2b: {} (max {})", self.b2b, self.max_b2b),
+            format!(
+                "t-spins: {}/{}/{}",
+                self.tspins[0], self.tspins[1], self.tspins[2]
+            ),
+            format!("tetris rate: {:.0}%", self.tetris_rate()),
+            format!("perfect clears: {}", self.perfect_clears),
+            format!("finesse faults: {}", self.finesse_faults),
+        ]
+    }
+}
+
+/// Everything about a finished game, as it is exported.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub mode: GameMode,
+    pub seed: u64,
+    /// saturated to u64, which is all JSON readers can be counted on to read
+    pub score: u64,
+    pub level: u8,
+    /// seconds
+    pub time: f64,
+    pub pps: f64,
+    pub apm: f64,
+    pub kpp: f64,
+    pub tetris_rate: f64,
+    #[serde(flatten)]
+    pub stats: GameStats,
+}
+
+impl GameSummary {
+    pub fn new(
+        stats: &GameStats,
+        mode: GameMode,
+        seed: u64,
+        score: u128,
+        level: u8,
+        time: Duration,
+    ) -> GameSummary {
+        GameSummary {
+            mode,
+            seed,
+            score: u64::try_from(score).unwrap_or(u64::MAX),
+            level,
+            time: time.as_secs_f64(),
+            pps: stats.pps(time),
+            apm: stats.apm(time),
+            kpp: stats.kpp(),
+            tetris_rate: stats.tetris_rate(),
+            stats: stats.clone(),
+        }
+    }
+
+    /// Writes the summary to a new JSON file in the stats directory, returns its path.
+    pub fn export(&self) -> Result<String> {
+        let dir = find_config_dir() + STATS_DIR_NAME;
+        std::fs::create_dir_all(&dir)?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let path = format!("{dir}{}-{timestamp}.json", self.mode.name());
+        std::fs::write(&path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(path)
+    }
+}
+
+/// Fewest moves and rotations that take a piece from where it spawns to
+/// above `cells` on an empty board `width` wide, or None if it can't get there.
+fn finesse_keys(shape: TetrominoShape, width: usize, cells: &[(usize, usize); 4]) -> Option<u32> {
+    let target = footprint(cells);
+    let start = Tetromino::new(shape, width, 1);
+    let mut seen = HashSet::from([footprint(&start.get_full_position().ok()?)]);
+    let mut queue = VecDeque::from([(start, 0)]);
+    while let Some((tetromino, keys)) = queue.pop_front() {
+        let position = tetromino.get_full_position().ok()?;
+        if footprint(&position) == target {
+            return Some(keys);
+        }
+        let mut next = Vec::new();
+        for direction in [Direction::Left, Direction::Right] {
+            let moved = tetromino.calc_horizontal_move((direction.into(), 0));
+            if moved.is_ok_and(|cells| cells.iter().all(|(x, _)| *x < width)) {
+                let mut tetromino = tetromino.clone();
+                tetromino.horizontal_move(direction);
+                next.push(tetromino);
+            }
+        }
+        for clockwise in [true, false] {
+            let mut tetromino = tetromino.clone();
+            // the first kick that stays on the board, like the board does
+            let kick = (0..5).find(|offset| {
+                tetromino
+                    .calc_rotate(clockwise, *offset)
+                    .is_ok_and(|cells| cells.iter().all(|(x, _)| *x < width))
+            });
+            if let Some(offset) = kick {
+                if tetromino.rotate(clockwise, offset).is_ok() {
+                    next.push(tetromino);
+                }
+            }
+        }
+        for tetromino in next {
+            let position = tetromino.get_full_position().ok()?;
+            if seen.insert(footprint(&position)) {
+                queue.push_back((tetromino, keys + 1));
+            }
+        }
+    }
+    None
+}
+
+/// Cells of a piece moved up to the top row, so pieces in the same columns compare equal.
+fn footprint(cells: &[(usize, usize); 4]) -> Vec<(usize, usize)> {
+    let top = cells.iter().map(|(_, y)| *y).min().unwrap_or(0);
+    let mut cells = cells.map(|(x, y)| (x, y - top)).to_vec();
+    cells.sort();
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finesse_keys() {
+        // an O piece spawns over columns 4 and 5 of a board 10 wide
+        let o_cells = |x: usize| [(x, 20), (x + 1, 20), (x, 21), (x + 1, 21)];
+        assert_eq!(finesse_keys(TetrominoShape::O, 10, &o_cells(4)), Some(0));
+        assert_eq!(finesse_keys(TetrominoShape::O, 10, &o_cells(0)), Some(4));
+        assert_eq!(finesse_keys(TetrominoShape::O, 10, &o_cells(8)), Some(4));
+        // a vertical I turns into the fifth column, then moves against the left wall
+        let i_cells = [(0, 18), (0, 19), (0, 20), (0, 21)];
+        assert_eq!(finesse_keys(TetrominoShape::I, 10, &i_cells), Some(5));
+    }
+
+    #[test]
+    fn test_record_events() {
+        let mut stats = GameStats::new(10);
+        let cells = [(4, 20), (5, 20), (4, 21), (5, 21)];
+        stats.record(&BoardEvent::PieceSpawned {
+            shape: TetrominoShape::O,
+        });
+        stats.record_key(Action::Move(Direction::Left));
+        stats.record_key(Action::Move(Direction::Right));
+        stats.record_key(Action::HardDrop);
+        stats.record(&BoardEvent::PieceLocked {
+            shape: TetrominoShape::O,
+            cells,
+        });
+        stats.record(&BoardEvent::LinesCleared {
+            rows: vec![20, 21, 22, 23],
+            count: 4,
+            tspin: false,
+            b2b: 1,
+            combo: 2,
+        });
+        stats.record(&BoardEvent::PerfectClear);
+
+        assert_eq!(stats.pieces, 1);
+        assert_eq!(stats.keys, 3);
+        assert_eq!(stats.finesse_faults, 1);
+        assert_eq!(stats.clears, [0, 0, 0, 1]);
+        // 4 for the tetris, 1 for back to back, 1 for the combo and 10 for the perfect clear
+        assert_eq!(stats.attack, 16);
+        assert_eq!(stats.tetris_rate(), 100.0);
+        assert_eq!(stats.apm(Duration::from_secs(30)), 32.0);
+
+        let summary =
+            GameSummary::new(&stats, GameMode::Sprint, 7, 800, 1, Duration::from_secs(30));
+        let json = serde_json::to_string(&summary).expect("failed to export");
+        assert!(json.contains("\"finesse_faults\":1"));
+        assert_eq!(
+            serde_json::from_str::<GameSummary>(&json).expect("failed to read back"),
+            GameSummary {
+                stats: GameStats {
+                    piece_keys: 0,
+                    board_width: 0,
+                    ..stats
+                },
+                ..summary
+            }
+        );
+    }
+}
@@ -1,16 +1,10 @@
-use ratatui::{
-    prelude::{Buffer, Rect},
-    symbols::Marker,
-    widgets::{
-        canvas::{Canvas, Painter, Shape},
-        Block, BorderType, Borders, Widget,
-    },
-};
-use serde::Deserialize;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
 
 use std::ops::{Add, AddAssign};
 
-use crate::{board::TetrominoPositionError, config::CONFIG};
+use crate::board::TetrominoPositionError;
+use crate::grid::Tile;
 
 // y component is inverted because (0, 0) is in the top left
 const O_ROTATION_OFFSETS: [[(isize, isize); 5]; 4] = [
@@ -33,34 +27,40 @@ const JLSTZ_ROTATION_OFFSETS: [[(isize, isize); 5]; 4] = [
     [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
 ];
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum Direction {
     Left,
     Right,
 }
-impl Into<isize> for Direction {
-    fn into(self) -> isize {
-        match self {
+impl From<Direction> for isize {
+    fn from(direction: Direction) -> isize {
+        match direction {
             Direction::Left => -1,
             Direction::Right => 1,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum TetrominoShape {
+    I,
     J,
     L,
-    S,
-    Z,
     O,
+    S,
     T,
-    I,
+    Z,
 }
-impl Into<Tetromino> for TetrominoShape {
-    fn into(self) -> Tetromino {
-        Tetromino::new(self)
-    }
+impl TetrominoShape {
+    pub const ALL: [TetrominoShape; 7] = [
+        TetrominoShape::I,
+        TetrominoShape::J,
+        TetrominoShape::L,
+        TetrominoShape::O,
+        TetrominoShape::S,
+        TetrominoShape::T,
+        TetrominoShape::Z,
+    ];
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -97,57 +97,58 @@ pub struct Tetromino {
     lock_delay: u8,
 }
 impl Tetromino {
-    pub fn new(shape: TetrominoShape) -> Tetromino {
-        let initial_position = Position::new(CONFIG.board_size.0 / 2 - 1, 2);
+    /// Constructs a tetromino at the spawn position of a board `board_width` cells wide.
+    pub fn new(shape: TetrominoShape, board_width: usize, lock_delay: u8) -> Tetromino {
+        let initial_position = Position::new(board_width / 2 - 1, 2);
         match shape {
             TetrominoShape::J => Tetromino {
                 shape,
                 pos: initial_position,
                 orientation: [(-1, -1), (-1, 0), (0, 0), (1, 0)],
                 rotation_index: 0,
-                lock_delay: CONFIG.lock_delay,
+                lock_delay,
             },
             TetrominoShape::L => Tetromino {
                 shape,
                 pos: initial_position,
                 orientation: [(-1, 0), (0, 0), (1, 0), (1, -1)],
                 rotation_index: 0,
-                lock_delay: CONFIG.lock_delay,
+                lock_delay,
             },
             TetrominoShape::S => Tetromino {
                 shape,
                 pos: initial_position,
                 orientation: [(-1, 0), (0, 0), (0, -1), (1, -1)],
                 rotation_index: 0,
-                lock_delay: CONFIG.lock_delay,
+                lock_delay,
             },
             TetrominoShape::Z => Tetromino {
                 shape,
                 pos: initial_position,
                 orientation: [(-1, -1), (0, -1), (0, 0), (1, 0)],
                 rotation_index: 0,
-                lock_delay: CONFIG.lock_delay,
+                lock_delay,
             },
             TetrominoShape::O => Tetromino {
                 shape,
                 pos: initial_position,
                 orientation: [(0, -1), (0, 0), (1, -1), (1, 0)],
                 rotation_index: 0,
-                lock_delay: CONFIG.lock_delay,
+                lock_delay,
             },
             TetrominoShape::T => Tetromino {
                 shape,
                 pos: initial_position,
                 orientation: [(-1, 0), (0, 0), (0, -1), (1, 0)],
                 rotation_index: 0,
-                lock_delay: CONFIG.lock_delay,
+                lock_delay,
             },
             TetrominoShape::I => Tetromino {
                 shape,
                 pos: initial_position,
                 orientation: [(-1, 0), (0, 0), (1, 0), (2, 0)],
                 rotation_index: 0,
-                lock_delay: CONFIG.lock_delay,
+                lock_delay,
             },
         }
     }
@@ -160,6 +161,11 @@ impl Tetromino {
         self.pos
     }
 
+    /// 0 where it spawns, 1 turned clockwise, 2 upside down, 3 turned counterclockwise.
+    pub fn get_rotation_index(&self) -> usize {
+        self.rotation_index
+    }
+
     pub fn calc_horizontal_move(
         &self,
         diff: (isize, isize),
@@ -171,7 +177,7 @@ impl Tetromino {
                 usize::try_from(self.pos.y as isize + y + diff.1)?,
             )
         }
-        return Ok(full_position);
+        Ok(full_position)
     }
 
     pub fn get_full_position(&self) -> Result<[(usize, usize); 4], TetrominoPositionError> {
@@ -182,11 +188,16 @@ impl Tetromino {
                 usize::try_from(self.pos.y as isize + y)?,
             )
         }
-        return Ok(full_position);
+        Ok(full_position)
     }
 
-    pub fn update(&mut self) {
-        self.pos.y += 1;
+    /// Moves the tetromino `rows` down.
+    pub fn fall(&mut self, rows: usize) {
+        self.pos.y += rows;
+    }
+
+    pub fn set_lock_delay(&mut self, lock_delay: u8) {
+        self.lock_delay = lock_delay;
     }
 
     pub fn update_lock_delay(&mut self) -> bool {
@@ -207,21 +218,16 @@ impl Tetromino {
         let original_orientation = self.orientation;
         let original_rotation_index = self.rotation_index;
 
-        let return_value;
-        match self.rotate(clockwise, offset_index) {
-            Ok(_) => {
-                return_value = self.get_full_position();
-            }
-            Err(_) => {
-                return_value = Err(TetrominoPositionError::NegativePosition);
-            }
-        }
+        let return_value = match self.rotate(clockwise, offset_index) {
+            Ok(_) => self.get_full_position(),
+            Err(_) => Err(TetrominoPositionError::NegativePosition),
+        };
 
         self.pos = original_pos;
         self.orientation = original_orientation;
         self.rotation_index = original_rotation_index;
 
-        return return_value;
+        return_value
     }
 
     pub fn rotate(
@@ -260,30 +266,29 @@ impl Tetromino {
         }
     }
 }
-impl Shape for Tetromino {
-    fn draw(&self, painter: &mut Painter) {
-        for (x, y) in self.orientation {
-            painter.paint(
-                (x + 1) as usize, // add 1 because of the border in previews
-                (y + 1) as usize,
-                CONFIG.tetromino_color[&self.shape],
-            );
+
+/// A tetromino in its spawn orientation, as shown in the hold and next previews.
+#[derive(Debug, Clone)]
+pub struct Preview {
+    tetromino: Tetromino,
+    color: Color,
+}
+impl Preview {
+    pub fn new(shape: TetrominoShape, color: Color) -> Preview {
+        Preview {
+            tetromino: Tetromino::new(shape, 4, 0), // only the orientation is drawn
+            color,
         }
     }
-}
-impl Widget for Tetromino {
-    fn render(self, area: Rect, buf: &mut Buffer) {
-        Canvas::default()
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .border_type(BorderType::Rounded),
-            )
-            .x_bounds([0.0, 4.0])
-            .y_bounds([0.0, 4.0])
-            .marker(Marker::HalfBlock)
-            .paint(|ctx| ctx.draw(&self))
-            .render(area, buf);
+
+    /// Colors of the cells of the preview, top row first. Empty cells are None.
+    pub fn cells(&self) -> Vec<Vec<Option<Tile>>> {
+        let mut cells = vec![vec![None; 4]; 2];
+        for (x, y) in self.tetromino.orientation {
+            cells[(y + 1) as usize][(x + 1) as usize] =
+                Some(Tile::piece(self.color, self.tetromino.get_shape()));
+        }
+        cells
     }
 }
 
@@ -293,54 +298,54 @@ mod tests {
 
     #[test]
     fn test_rotate() {
-        let mut tetromino = Tetromino::new(TetrominoShape::Z);
+        let mut tetromino = Tetromino::new(TetrominoShape::Z, 10, 3);
         tetromino.rotate(false, 0).expect("failed to rotate Z");
         assert_eq!(tetromino.orientation, [(-1, 1), (-1, 0), (0, 0), (0, -1)]);
 
-        let mut tetromino = Tetromino::new(TetrominoShape::O);
+        let mut tetromino = Tetromino::new(TetrominoShape::O, 10, 3);
         tetromino.rotate(false, 0).expect("failed to rotate O");
         assert_eq!(tetromino.orientation, [(-1, 0), (0, 0), (-1, -1), (0, -1)]);
     }
 
     #[test]
     fn test_get_full_position() {
-        let tetromino = Tetromino::new(TetrominoShape::I);
+        let tetromino = Tetromino::new(TetrominoShape::I, 10, 3);
         let full_position = tetromino
             .get_full_position()
             .expect("failed to get I full position");
         assert_eq!(full_position, [(3, 2), (4, 2), (5, 2), (6, 2)]);
 
-        let tetromino = Tetromino::new(TetrominoShape::J);
+        let tetromino = Tetromino::new(TetrominoShape::J, 10, 3);
         let full_position = tetromino
             .get_full_position()
             .expect("failed to get J full position");
         assert_eq!(full_position, [(3, 1), (3, 2), (4, 2), (5, 2)]);
 
-        let tetromino = Tetromino::new(TetrominoShape::T);
+        let tetromino = Tetromino::new(TetrominoShape::T, 10, 3);
         let full_position = tetromino
             .get_full_position()
             .expect("failed to get T full position");
         assert_eq!(full_position, [(3, 2), (4, 2), (4, 1), (5, 2)]);
 
-        let tetromino = Tetromino::new(TetrominoShape::Z);
+        let tetromino = Tetromino::new(TetrominoShape::Z, 10, 3);
         let full_position = tetromino
             .get_full_position()
             .expect("failed to get Z full position");
         assert_eq!(full_position, [(3, 1), (4, 1), (4, 2), (5, 2)]);
 
-        let tetromino = Tetromino::new(TetrominoShape::L);
+        let tetromino = Tetromino::new(TetrominoShape::L, 10, 3);
         let full_position = tetromino
             .get_full_position()
             .expect("failed to get L full position");
         assert_eq!(full_position, [(3, 2), (4, 2), (5, 2), (5, 1)]);
 
-        let tetromino = Tetromino::new(TetrominoShape::S);
+        let tetromino = Tetromino::new(TetrominoShape::S, 10, 3);
         let full_position = tetromino
             .get_full_position()
             .expect("failed to get S full position");
         assert_eq!(full_position, [(3, 2), (4, 2), (4, 1), (5, 1)]);
 
-        let tetromino = Tetromino::new(TetrominoShape::O);
+        let tetromino = Tetromino::new(TetrominoShape::O, 10, 3);
         let full_position = tetromino
             .get_full_position()
             .expect("failed to get O full position");
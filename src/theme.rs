@@ -0,0 +1,388 @@
+use anyhow::Result;
+use ratatui::style::Color;
+use ron::{
+    from_str,
+    ser::{to_string_pretty, PrettyConfig},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config::{find_config_dir, Config};
+use crate::grid::shade;
+use crate::tetromino::TetrominoShape;
+
+/// Themes that come with termtris, the first is the default.
+pub const BUNDLED_THEMES: [&str; 8] = [
+    "guideline",
+    "tgm",
+    "monochrome",
+    "solarized",
+    "high-contrast",
+    "deuteranopia",
+    "protanopia",
+    "tritanopia",
+];
+
+/// Colors of everything on the screen. Theme files are written like this
+/// struct, piece colors left out of them are the guideline ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    /// behind everything, the terminal's own background when not set
+    #[serde(default)]
+    pub background: Option<Color>,
+    #[serde(default)]
+    pub text: Color,
+    /// borders of the hold, preview, stats and menu boxes
+    #[serde(default)]
+    pub panel_border: Color,
+    #[serde(default = "default_garbage")]
+    pub garbage: Color,
+    /// selected menu entries
+    #[serde(default)]
+    pub highlight: Color,
+    #[serde(default)]
+    pub tetromino_color: BTreeMap<TetrominoShape, Color>,
+    /// derived from the piece colors for shapes left out
+    #[serde(default)]
+    pub ghost_color: BTreeMap<TetrominoShape, Color>,
+    /// derived from the piece colors for shapes left out
+    #[serde(default)]
+    pub border_color: BTreeMap<TetrominoShape, Color>,
+}
+
+impl Theme {
+    /// The theme named by the config, with the config's colors on top of it.
+    /// Ghost and border colors are derived from the piece colors the config
+    /// sets unless the theme or the config give them.
+    pub fn from_config(config: &Config) -> Result<Theme, String> {
+        let mut theme = Theme::load_uncompleted(&config.theme)?;
+        theme.tetromino_color.extend(&config.tetromino_color);
+        theme.ghost_color.extend(&config.ghost_color);
+        theme.border_color.extend(&config.border_color);
+        Ok(theme.completed())
+    }
+
+    /// A bundled theme, or a theme file given by its path or by its name in
+    /// the themes directory.
+    pub fn load(name: &str) -> Result<Theme, String> {
+        Theme::load_uncompleted(name).map(Theme::completed)
+    }
+
+    /// The theme with only the colors it gives, see [`Theme::completed`].
+    fn load_uncompleted(name: &str) -> Result<Theme, String> {
+        if let Some(theme) = Theme::bundled(name) {
+            return Ok(theme);
+        }
+        let path = if name.ends_with(".ron") || name.contains('/') {
+            name.to_string()
+        } else {
+            format!("{}{name}.ron", themes_dir())
+        };
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return Err(format!(
+                "`{name}` is neither a bundled theme ({}) nor a theme file, looked for {path}",
+                BUNDLED_THEMES.join(", ")
+            ));
+        };
+        from_str::<Theme>(&text).map_err(|error| format!("invalid theme file {path}: {error}"))
+    }
+
+    /// A bundled theme with only the colors it gives.
+    fn bundled(name: &str) -> Option<Theme> {
+        let theme = match name {
+            "guideline" => Theme::default(),
+            "tgm" => Theme {
+                background: Some(Color::Rgb(0, 0, 0)),
+                text: Color::Rgb(230, 230, 230),
+                panel_border: Color::Rgb(120, 120, 120),
+                garbage: Color::Rgb(128, 128, 128),
+                highlight: Color::Rgb(240, 220, 40),
+                tetromino_color: colors([
+                    (230, 40, 40),
+                    (40, 80, 230),
+                    (240, 140, 30),
+                    (240, 220, 40),
+                    (200, 50, 200),
+                    (40, 200, 230),
+                    (60, 200, 60),
+                ]),
+                ghost_color: BTreeMap::new(),
+                border_color: BTreeMap::new(),
+            },
+            "monochrome" => Theme {
+                background: None,
+                text: Color::Reset,
+                panel_border: Color::Reset,
+                garbage: Color::Rgb(100, 100, 100),
+                highlight: Color::Rgb(255, 255, 255),
+                tetromino_color: colors([
+                    (230, 230, 230),
+                    (150, 150, 150),
+                    (190, 190, 190),
+                    (250, 250, 250),
+                    (170, 170, 170),
+                    (210, 210, 210),
+                    (130, 130, 130),
+                ]),
+                ghost_color: BTreeMap::new(),
+                border_color: BTreeMap::new(),
+            },
+            "solarized" => Theme {
+                background: Some(Color::Rgb(0, 43, 54)),
+                text: Color::Rgb(131, 148, 150),
+                panel_border: Color::Rgb(88, 110, 117),
+                garbage: Color::Rgb(88, 110, 117),
+                highlight: Color::Rgb(181, 137, 0),
+                tetromino_color: colors([
+                    (42, 161, 152),
+                    (38, 139, 210),
+                    (203, 75, 22),
+                    (181, 137, 0),
+                    (133, 153, 0),
+                    (108, 113, 196),
+                    (220, 50, 47),
+                ]),
+                ghost_color: BTreeMap::new(),
+                border_color: BTreeMap::new(),
+            },
+            "high-contrast" => Theme {
+                background: Some(Color::Rgb(0, 0, 0)),
+                text: Color::Rgb(255, 255, 255),
+                panel_border: Color::Rgb(255, 255, 255),
+                garbage: Color::Rgb(160, 160, 160),
+                highlight: Color::Rgb(255, 255, 0),
+                tetromino_color: colors([
+                    (0, 255, 255),
+                    (0, 90, 255),
+                    (255, 140, 0),
+                    (255, 255, 0),
+                    (0, 255, 0),
+                    (255, 0, 255),
+                    (255, 0, 0),
+                ]),
+                ghost_color: BTreeMap::new(),
+                border_color: BTreeMap::new(),
+            },
+            // the colors of the color blind themes differ in brightness as
+            // well, S and Z most of all
+            "deuteranopia" => Theme {
+                highlight: Color::Rgb(240, 228, 66),
+                tetromino_color: colors([
+                    (86, 180, 233),
+                    (0, 114, 178),
+                    (230, 159, 0),
+                    (240, 228, 66),
+                    (0, 158, 115),
+                    (204, 121, 167),
+                    (213, 94, 0),
+                ]),
+                ..Theme::colorless()
+            },
+            "protanopia" => Theme {
+                highlight: Color::Rgb(240, 228, 66),
+                tetromino_color: colors([
+                    (86, 180, 233),
+                    (0, 90, 160),
+                    (230, 159, 0),
+                    (240, 228, 66),
+                    (170, 220, 240),
+                    (180, 140, 200),
+                    (140, 70, 0),
+                ]),
+                ..Theme::colorless()
+            },
+            "tritanopia" => Theme {
+                highlight: Color::Rgb(255, 150, 180),
+                tetromino_color: colors([
+                    (0, 170, 170),
+                    (60, 70, 110),
+                    (255, 150, 180),
+                    (240, 240, 240),
+                    (150, 150, 150),
+                    (130, 60, 140),
+                    (220, 40, 30),
+                ]),
+                ..Theme::colorless()
+            },
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    /// The default text and background, with the piece colors left to be filled in.
+    fn colorless() -> Theme {
+        Theme {
+            tetromino_color: BTreeMap::new(),
+            ghost_color: BTreeMap::new(),
+            border_color: BTreeMap::new(),
+            ..Theme::default()
+        }
+    }
+
+    pub fn to_ron(&self) -> Result<String> {
+        Ok(to_string_pretty(self, PrettyConfig::default())? + "\n")
+    }
+
+    /// Fills in the piece colors left out with the guideline ones, and derives
+    /// the ghost and border colors left out from the piece colors.
+    fn completed(mut self) -> Theme {
+        let guideline = guideline_colors();
+        for shape in TetrominoShape::ALL {
+            let color = *self
+                .tetromino_color
+                .entry(shape)
+                .or_insert(guideline[&shape]);
+            self.ghost_color
+                .entry(shape)
+                .or_insert_with(|| ghost_of(color));
+            self.border_color
+                .entry(shape)
+                .or_insert_with(|| border_of(color));
+        }
+        self
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: None,
+            text: Color::Reset,
+            panel_border: Color::Reset,
+            garbage: default_garbage(),
+            highlight: Color::Reset,
+            tetromino_color: guideline_colors(),
+            ghost_color: colors([
+                (0, 127, 128),
+                (0, 0, 128),
+                (128, 64, 0),
+                (128, 127, 0),
+                (0, 128, 0),
+                (61, 0, 61),
+                (128, 0, 0),
+            ]),
+            border_color: colors([
+                (64, 191, 191),
+                (64, 64, 191),
+                (191, 127, 64),
+                (191, 191, 64),
+                (64, 191, 64),
+                (96, 32, 96),
+                (191, 64, 64),
+            ]),
+        }
+    }
+}
+
+fn default_garbage() -> Color {
+    Color::Gray
+}
+
+pub fn themes_dir() -> String {
+    find_config_dir() + "themes/"
+}
+
+fn guideline_colors() -> BTreeMap<TetrominoShape, Color> {
+    colors([
+        (0, 255, 255),
+        (0, 0, 255),
+        (255, 127, 0),
+        (255, 255, 0),
+        (0, 255, 0),
+        (128, 0, 128),
+        (255, 0, 0),
+    ])
+}
+
+/// Colors of the shapes, in the order of [`TetrominoShape::ALL`].
+fn colors(rgb: [(u8, u8, u8); 7]) -> BTreeMap<TetrominoShape, Color> {
+    TetrominoShape::ALL
+        .into_iter()
+        .zip(rgb.map(|(r, g, b)| Color::Rgb(r, g, b)))
+        .collect()
+}
+
+/// A darker color for the ghost, named colors can't be darkened so their ghost is gray.
+fn ghost_of(color: Color) -> Color {
+    match color {
+        Color::Rgb(..) => shade(color, -0.5),
+        _ => Color::DarkGray,
+    }
+}
+
+/// A duller color for the board border, halfway to gray.
+fn border_of(color: Color) -> Color {
+    match color {
+        Color::Rgb(r, g, b) => {
+            let dull = |channel: u8| ((u16::from(channel) + 128) / 2) as u8;
+            Color::Rgb(dull(r), dull(g), dull(b))
+        }
+        _ => color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_themes() {
+        for name in BUNDLED_THEMES {
+            let theme = Theme::load(name).expect("missing bundled theme");
+            for shape in TetrominoShape::ALL {
+                assert!(theme.ghost_color.contains_key(&shape));
+                assert!(theme.border_color.contains_key(&shape));
+            }
+        }
+        assert_eq!(Theme::load("guideline"), Ok(Theme::default()));
+        assert!(Theme::load("no such theme").is_err());
+    }
+
+    #[test]
+    fn test_theme_from_config() {
+        let mut config = Config {
+            theme: "solarized".to_string(),
+            ..Config::default()
+        };
+        config
+            .tetromino_color
+            .insert(TetrominoShape::T, Color::Rgb(200, 100, 0));
+        let theme = Theme::from_config(&config).expect("invalid theme");
+        assert_eq!(theme.background, Some(Color::Rgb(0, 43, 54)));
+        assert_eq!(
+            theme.tetromino_color[&TetrominoShape::T],
+            Color::Rgb(200, 100, 0)
+        );
+        // the ghost and border follow the piece color the config sets
+        assert_eq!(
+            theme.ghost_color[&TetrominoShape::T],
+            ghost_of(Color::Rgb(200, 100, 0))
+        );
+        assert_eq!(
+            theme.border_color[&TetrominoShape::T],
+            border_of(Color::Rgb(200, 100, 0))
+        );
+        // unless the config gives them
+        config
+            .ghost_color
+            .insert(TetrominoShape::T, Color::Rgb(1, 2, 3));
+        let theme = Theme::from_config(&config).expect("invalid theme");
+        assert_eq!(theme.ghost_color[&TetrominoShape::T], Color::Rgb(1, 2, 3));
+
+        let theme: Theme = from_str("(tetromino_color: {I: Rgb(100, 200, 0)})")
+            .map(Theme::completed)
+            .expect("invalid theme file");
+        assert_eq!(
+            theme.ghost_color[&TetrominoShape::I],
+            Color::Rgb(50, 100, 0)
+        );
+        assert_eq!(
+            theme.border_color[&TetrominoShape::I],
+            Color::Rgb(114, 164, 64)
+        );
+        assert_eq!(
+            theme.tetromino_color[&TetrominoShape::O],
+            guideline_colors()[&TetrominoShape::O]
+        );
+    }
+}
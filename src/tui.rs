@@ -1,4 +1,7 @@
-use std::{io, panic};
+use std::{
+    io::{self, Write},
+    panic,
+};
 
 use anyhow::Result;
 use crossterm::{
@@ -89,3 +92,27 @@ impl Tui {
         Ok(())
     }
 }
+
+/// Asks the terminal to put `text` on the clipboard, with an OSC 52 escape
+/// sequence. Terminals that don't support it ignore it.
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in text.as_bytes().chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, byte)| bits | u32::from(*byte) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(BASE64[(bits >> (18 - 6 * i) & 63) as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    let mut stderr = io::stderr();
+    write!(stderr, "\x1b]52;c;{encoded}\x07")?;
+    stderr.flush()?;
+    Ok(())
+}
@@ -1,240 +1,681 @@
 use ratatui::{
-    prelude::{Alignment, Frame, Rect},
-    symbols::Marker,
-    widgets::{canvas, Block, BorderType, Borders, Paragraph},
+    prelude::{Alignment, Buffer, Frame, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
 };
 
-use crate::{app::App, config::CONFIG, tetromino::Tetromino};
+use std::time::{Duration, Instant};
+
+use crate::{
+    app::{App, GameOver, GameOverOption, Screen},
+    clock::format_duration,
+    color::{ColorMapper, Palette},
+    config::Config,
+    editor::Editor,
+    grid::{CellGrid, CellLook},
+    layout::GameLayout,
+    menu::{Menu, MenuItem},
+    mode::{GameMode, ULTRA_TIME},
+    puzzle::PuzzleMenu,
+    scores::HighScores,
+    settings::{Settings, FILE_ONLY_KEYS},
+    tetromino::{Preview, TetrominoShape},
+    theme::Theme,
+};
 
 pub fn render(app: &App, frame: &mut Frame) {
-    let bounds = [0.0, 4.0];
-    let marker = Marker::HalfBlock;
-    let borders = Borders::ALL;
-    let border_type = BorderType::Rounded;
+    render_screen(app, frame);
+    if let Some(notice) = app.notice() {
+        render_notice(frame, notice);
+    }
+    fill_unstyled(frame.buffer_mut(), &app.theme);
+    ColorMapper::new(app.color_depth()).apply(frame.buffer_mut());
+}
+
+/// Gives the cells drawn without colors the theme's text and background colors.
+fn fill_unstyled(buf: &mut Buffer, theme: &Theme) {
+    for cell in &mut buf.content {
+        if cell.fg == Color::Reset {
+            cell.fg = theme.text;
+        }
+        if let (Color::Reset, Some(background)) = (cell.bg, theme.background) {
+            cell.bg = background;
+        }
+    }
+}
+
+fn render_screen(app: &App, frame: &mut Frame) {
+    match &app.screen {
+        Screen::Menu(menu) => {
+            render_menu(app, menu, frame);
+            return;
+        }
+        Screen::HighScores(mode) => {
+            render_high_scores(&app.theme, &app.high_scores, *mode, frame);
+            return;
+        }
+        Screen::Puzzles(menu) => {
+            render_puzzles(&app.theme, menu, frame);
+            return;
+        }
+        Screen::Settings(settings) => {
+            render_settings(&app.theme, settings, frame);
+            return;
+        }
+        Screen::Paused => {
+            render_pause(&app.theme, frame);
+            return;
+        }
+        Screen::Editor(editor) => {
+            render_editor(app, editor, frame);
+            return;
+        }
+        Screen::Playing | Screen::GameOver(_) | Screen::Replay(..) | Screen::Quit => {}
+    }
+
+    let config = app.board.config();
+    let stats = stats_lines(app);
+    let Some(layout) = game_layout(config, &stats, frame.size()) else {
+        render_too_small(frame, GameLayout::min_size(config.board_size));
+        return;
+    };
+    let look = CellLook {
+        style: layout.style,
+        texture: config.cell_texture,
+        marks: config.shape_marks,
+    };
+    let cell_theme = Palette::cell_theme(&app.config, &app.theme, app.color_depth());
+    let mut cells = app.board.cells(&cell_theme);
+    let now = Instant::now();
+    app.animations.apply(
+        &mut cells,
+        app.board.is_clearing(),
+        Duration::from_millis(config.line_clear_delay),
+        config.clear_animation,
+        now,
+    );
+    frame.render_widget(
+        CellGrid {
+            cells: &cells,
+            look,
+        },
+        layout.board,
+    );
+    if let Some(area) = layout.stats {
+        render_stats(&app.theme, stats, frame, area);
+    }
+    for (i, area) in layout.previews.iter().enumerate() {
+        let i = i + 1; // show the first piece ahead, not current one
+        let preview = app
+            .board
+            .calc_next_piece(i)
+            .map(|shape| Preview::new(shape, cell_theme.tetromino_color[&shape]));
+        render_preview_box(&app.theme, frame, *area, &ordinal(i), preview, look);
+    }
+    if let Some((lines, progress)) = app.animations.text(now).filter(|_| config.action_text) {
+        render_action_text(&app.theme, lines, progress, frame, layout.action_text);
+    }
+    if let Some(area) = layout.hold {
+        let preview = app
+            .board
+            .get_held_tetromino()
+            .map(|shape| Preview::new(shape, cell_theme.tetromino_color[&shape]));
+        render_preview_box(&app.theme, frame, area, "hold", preview, look);
+    }
+
+    match &app.screen {
+        Screen::Replay(playback, _) => {
+            render_replay_notice(frame, layout.board, playback.is_paused())
+        }
+        Screen::GameOver(game_over) if game_over.animation_done => {
+            render_game_over(app, game_over, frame)
+        }
+        _ => {}
+    }
+}
+
+/// Where the board and panels of the game go, with `stats` in the stats panel.
+fn game_layout(config: &Config, stats: &[String], area: Rect) -> Option<GameLayout> {
+    let stats_size = (
+        stats.iter().map(|line| line.len()).max().unwrap_or(0) as u16 + 2, // + 2 for the border
+        stats.len() as u16 + 2,
+    );
+    GameLayout::new(
+        area,
+        config.cell_style,
+        config.board_size,
+        config.number_of_previews,
+        stats_size,
+    )
+}
 
-    if app.paused {
-        render_pause(frame);
+/// Where the editor draws its board in `area`, to find the cells under the mouse.
+pub fn editor_layout(editor: &Editor, config: &Config, area: Rect) -> Option<GameLayout> {
+    game_layout(config, &editor.lines(), area)
+}
+
+fn render_editor(app: &App, editor: &Editor, frame: &mut Frame) {
+    let Some(layout) = editor_layout(editor, &app.config, frame.size()) else {
+        render_too_small(frame, GameLayout::min_size(app.config.board_size));
         return;
+    };
+    let look = CellLook {
+        style: layout.style,
+        texture: app.config.cell_texture,
+        marks: app.config.shape_marks,
+    };
+    let cell_theme = Palette::cell_theme(&app.config, &app.theme, app.color_depth());
+    frame.render_widget(
+        CellGrid {
+            cells: &editor.cells(&cell_theme),
+            look,
+        },
+        layout.board,
+    );
+    if let Some(area) = layout.stats {
+        frame.render_widget(
+            Paragraph::new(
+                editor
+                    .lines()
+                    .into_iter()
+                    .map(Line::from)
+                    .collect::<Vec<_>>(),
+            )
+            .block(
+                panel(&app.theme)
+                    .title_alignment(Alignment::Center)
+                    .title("editor"),
+            )
+            .alignment(Alignment::Center),
+            area,
+        );
+    }
+    let preview = |shape: TetrominoShape| Preview::new(shape, cell_theme.tetromino_color[&shape]);
+    for (i, area) in layout.previews.iter().enumerate() {
+        let piece = editor.queue.get(i).copied().map(preview);
+        render_preview_box(&app.theme, frame, *area, &ordinal(i + 1), piece, look);
+    }
+    if let Some(area) = layout.hold {
+        render_preview_box(
+            &app.theme,
+            frame,
+            area,
+            "hold",
+            editor.hold.map(preview),
+            look,
+        );
     }
-    render_board(app, frame);
-    render_stats(app, frame);
-    render_next_previews(app, frame, bounds, marker, borders, border_type);
-    render_hold(app, frame, bounds, marker, borders, border_type);
 }
 
-fn render_pause(frame: &mut Frame) {
+fn render_menu(app: &App, menu: &Menu, frame: &mut Frame) {
+    let mut lines = Vec::new();
+    for (i, item) in MenuItem::ALL.iter().enumerate() {
+        let label = match item {
+            MenuItem::Mode => format!("mode: < {} >", app.mode.name()),
+            MenuItem::Level => format!("starting level: < {} >", app.starting_level),
+            MenuItem::Play => "play".to_string(),
+            MenuItem::Editor => "editor".to_string(),
+            MenuItem::HighScores => "high scores".to_string(),
+            MenuItem::Settings => "settings".to_string(),
+            MenuItem::Quit => "quit".to_string(),
+        };
+        lines.push(selectable_line(&app.theme, label, i == menu.selected));
+        if *item == MenuItem::Mode {
+            lines.push(
+                Line::styled(
+                    app.mode.description(),
+                    Style::default().add_modifier(Modifier::DIM),
+                )
+                .alignment(Alignment::Center),
+            );
+            lines.push(Line::from(""));
+        }
+    }
+    render_popup(&app.theme, frame, "termtris", lines);
+}
+
+fn render_puzzles(theme: &Theme, menu: &PuzzleMenu, frame: &mut Frame) {
+    // scroll so the selected puzzle stays in view
+    let shown = usize::from(frame.size().height.saturating_sub(6)).max(1);
+    let first = menu.selected.saturating_sub(shown - 1);
+    let mut lines = Vec::new();
+    for (i, (pack, puzzle)) in menu.entries().enumerate().skip(first).take(shown) {
+        let mark = if menu.solved.is_solved(pack, &puzzle.name) {
+            "✓"
+        } else {
+            " "
+        };
+        let label = format!(
+            "{mark} {pack}: {} ({})",
+            puzzle.name,
+            puzzle.goal.description()
+        );
+        lines.push(selectable_line(theme, label, i == menu.selected));
+    }
+    if lines.is_empty() {
+        lines.push(Line::from("no puzzles found").alignment(Alignment::Center));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from("enter: play, q: back").alignment(Alignment::Center));
+    render_popup(theme, frame, "puzzles", lines);
+}
+
+fn render_high_scores(theme: &Theme, high_scores: &HighScores, mode: GameMode, frame: &mut Frame) {
+    let mut lines = vec![Line::styled(
+        "  # |      score | lines | level |       time",
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+    for (i, entry) in high_scores.entries(mode).enumerate() {
+        lines.push(Line::from(format!(
+            "{:>3} | {:>10} | {:>5} | {:>5} | {:>10}",
+            i + 1,
+            entry.score,
+            entry.lines_cleared,
+            entry.level,
+            format_duration(entry.time)
+        )));
+    }
+    if lines.len() == 1 {
+        lines.push(Line::from("no games finished yet").alignment(Alignment::Center));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from("left/right: change mode, q: back").alignment(Alignment::Center));
+    render_popup(
+        theme,
+        frame,
+        &format!("high scores: < {} >", mode.name()),
+        lines,
+    );
+}
+
+fn render_settings(theme: &Theme, settings: &Settings, frame: &mut Frame) {
+    let label_width = settings
+        .fields
+        .iter()
+        .map(|field| field.label().len())
+        .max()
+        .unwrap_or(0);
+
+    // scroll so the selected field stays visible
+    let visible_fields = (frame.size().height as usize).saturating_sub(9).max(1);
+    let first_visible = settings
+        .selected
+        .saturating_sub(visible_fields / 2)
+        .min(settings.fields.len().saturating_sub(visible_fields));
+
+    let mut lines = Vec::new();
+    for (i, field) in settings
+        .fields
+        .iter()
+        .enumerate()
+        .skip(first_visible)
+        .take(visible_fields)
+    {
+        let selected = i == settings.selected;
+        let value = match (&settings.input, selected) {
+            (Some(input), true) => format!("{input}_"),
+            _ => field.value(&settings.config),
+        };
+        let style = if selected {
+            Style::default()
+                .fg(theme.highlight)
+                .add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        let mut spans = vec![Span::styled(
+            format!("{:>label_width$}: {value}", field.label()),
+            style,
+        )];
+        if let Some(color) = field.color(&settings.config) {
+            spans.push(Span::styled(" ██", Style::default().fg(color)));
+        }
+        lines.push(Line::from(spans));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::styled(
+        settings.message.clone().unwrap_or_default(),
+        Style::default().add_modifier(Modifier::BOLD),
+    ));
+    lines.push(
+        Line::from(if settings.input.is_some() {
+            "enter: apply, esc: cancel"
+        } else {
+            "enter: type, left/right: change, ctrl+s: save, q: back"
+        })
+        .alignment(Alignment::Center),
+    );
+    lines.push(
+        Line::from(format!(
+            "edit {} in the config file",
+            FILE_ONLY_KEYS.join(", ")
+        ))
+        .alignment(Alignment::Center),
+    );
+    let title = if settings.unsaved {
+        "settings (unsaved)"
+    } else {
+        "settings"
+    };
+    render_popup(theme, frame, title, lines);
+}
+
+fn render_game_over(app: &App, game_over: &GameOver, frame: &mut Frame) {
+    let mut lines = vec![
+        Line::from(format!("score: {}", app.score())),
+        Line::from(match &app.master {
+            Some(master) => format!(
+                "level: {}, grade: {}",
+                master.level(),
+                master.grade(game_over.time)
+            ),
+            None => format!("level: {}", app.board.get_level()),
+        }),
+        Line::from(format!("lines cleared: {}", app.board.get_lines_cleared())),
+        Line::from(format!("time: {}", format_duration(game_over.time))),
+        Line::from(format!(
+            "pieces: {}, pps: {:.2}, kpp: {:.2}",
+            app.stats.pieces,
+            app.stats.pps(game_over.time),
+            app.stats.kpp()
+        )),
+        Line::from(format!(
+            "apm: {:.1}, max combo: {}, tetris rate: {:.0}%",
+            app.stats.apm(game_over.time),
+            app.stats.max_combo,
+            app.stats.tetris_rate()
+        )),
+        Line::from(match (game_over.personal_best, &game_over.best) {
+            (true, _) => "new personal best!".to_string(),
+            (false, Some(best)) if app.mode.ranks_by_time() => {
+                format!("personal best: {}", format_duration(best.time))
+            }
+            (false, Some(best)) => format!("personal best: {}", best.score),
+            (false, None) => String::new(),
+        }),
+        Line::from(""),
+    ];
+    for (i, option) in GameOverOption::ALL.iter().enumerate() {
+        lines.push(selectable_line(
+            &app.theme,
+            option.label().to_string(),
+            i == game_over.selected,
+        ));
+    }
+    let title = match (app.mode, game_over.finished) {
+        (GameMode::Puzzle, true) => "solved",
+        (GameMode::Puzzle, false) => "failed",
+        (_, true) => "finished",
+        (_, false) => "game over",
+    };
+    render_popup(&app.theme, frame, title, lines);
+}
+
+/// A centered menu entry, highlighted when selected.
+fn selectable_line<'a>(theme: &Theme, label: String, selected: bool) -> Line<'a> {
+    if selected {
+        Line::styled(
+            format!("> {} <", label),
+            Style::default()
+                .fg(theme.highlight)
+                .add_modifier(Modifier::BOLD),
+        )
+        .alignment(Alignment::Center)
+    } else {
+        Line::from(label).alignment(Alignment::Center)
+    }
+}
+
+/// Renders lines in a bordered box sized to fit them, in the middle of the frame.
+fn render_popup(theme: &Theme, frame: &mut Frame, title: &str, lines: Vec<Line>) {
+    let message_width = lines
+        .iter()
+        .map(|line| line.width())
+        .max()
+        .unwrap_or(0)
+        .max(title.len()) as u16;
+    let message_height = lines.len() as u16;
+    let area = centered_rect(frame.size(), message_width + 2, message_height + 2);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(panel(theme).title_alignment(Alignment::Center).title(title))
+            .alignment(Alignment::Left),
+        area,
+    );
+}
+
+/// Shows a short message on the bottom line of the screen.
+fn render_notice(frame: &mut Frame, notice: &str) {
+    let area = frame.size();
+    frame.render_widget(
+        Paragraph::new(notice).alignment(Alignment::Center),
+        Rect::new(
+            area.x,
+            area.bottom().saturating_sub(1),
+            area.width,
+            1.min(area.height),
+        ),
+    );
+}
+
+/// Shows that a replay is playing, right below the board.
+fn render_replay_notice(frame: &mut Frame, board: Rect, paused: bool) {
+    let notice = if paused {
+        "replay paused (q: skip, p: resume)"
+    } else {
+        "replay (q: skip, p: pause)"
+    };
+    let area = centered_rect(frame.size(), notice.len() as u16, 1);
+    frame.render_widget(
+        Paragraph::new(notice).alignment(Alignment::Center),
+        Rect::new(
+            area.x,
+            board.bottom().min(frame.size().bottom().saturating_sub(1)),
+            area.width,
+            area.height,
+        ),
+    );
+}
+
+/// Shown instead of the game when not even the board fits.
+fn render_too_small(frame: &mut Frame, (width, height): (u16, u16)) {
+    let area = frame.size();
+    let message = format!(
+        "terminal too small\n{}x{}, need {width}x{height}",
+        area.width, area.height
+    );
+    frame.render_widget(
+        Paragraph::new(message)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        centered_rect(area, area.width, 4),
+    );
+}
+
+/// A rectangle of the given size in the middle of `area`, clamped to fit inside it.
+fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+fn render_pause(theme: &Theme, frame: &mut Frame) {
     let paused_message = "\
-        exit: ESC, q, CONTROL + c\n\
+        main menu: ESC, q\n\
+        exit: CONTROL + c\n\
         restart: r,\n\
         hold: c,\n\
         move right: d, l, right arrow\n\
         move left: a, h, left arrow\n\
         rotate clockwise: w, k, up arrow\n\
-        rotate counter-clockwise: z, j, down arrow\n\
+        rotate counter-clockwise: z\n\
         hard drop: space\n\
         soft drop: s, j, down arrow\n\
-        pause: p\
+        sonic drop: x\n\
+        pause: p\n\
+        copy as fumen: f\n\
+        undo, redo placement (practice): CONTROL + z, y\
     ";
     let message_width = paused_message.lines().map(|line| line.len()).max().unwrap() as u16;
     let message_height = paused_message.lines().count() as u16;
     frame.render_widget(
         Paragraph::new(paused_message)
             .block(
-                Block::default()
+                panel(theme)
                     .title_alignment(Alignment::Center)
-                    .borders(Borders::ALL)
-                    .border_type(BorderType::Rounded)
                     .title("paused"),
             )
             .alignment(Alignment::Left),
-        ratatui::prelude::Rect::new(
-            (frame.size().width / 2) - message_width / 2 - 1,
-            (frame.size().height / 2) - message_height / 2 - 1,
-            message_width + 2,
-            message_height + 2,
-        ),
+        centered_rect(frame.size(), message_width + 2, message_height + 2),
     );
 }
 
-fn render_board(app: &App, frame: &mut Frame) {
+fn stats_lines(app: &App) -> Vec<String> {
+    let elapsed = app.game_time();
+    let time = match app.mode {
+        // count down the time that is left
+        GameMode::Ultra => ULTRA_TIME.saturating_sub(elapsed),
+        _ => elapsed,
+    };
+    let mut lines = vec![
+        format!("score: {}", app.score()),
+        format!("level: {}", app.board.get_level()),
+        format!("lines cleared: {}", app.board.get_lines_cleared()),
+        format!("time: {}", format_duration(time)),
+    ];
+    if let Some(master) = &app.master {
+        // the master level takes the place of the board's, which never rises
+        lines.remove(1);
+        lines.extend(master.lines(elapsed));
+    } else if !app.board.rules().scoring {
+        lines.remove(0);
+    }
+    if let (GameMode::Puzzle, Some(run)) = (app.mode, &app.puzzle) {
+        lines.extend(run.lines());
+    }
+    if let Some(practice) = &app.practice {
+        lines.extend(practice.lines());
+    }
+    if app.all_stats {
+        lines.extend(app.stats.lines(elapsed));
+    }
+    lines
+}
+
+fn render_stats(theme: &Theme, lines: Vec<String>, frame: &mut Frame, area: Rect) {
     frame.render_widget(
-        canvas::Canvas::default()
-            .block(Block::default())
-            .x_bounds([0.0, (CONFIG.board_size.0) as f64])
-            .y_bounds([0.0, (CONFIG.board_size.1) as f64])
-            .marker(Marker::HalfBlock)
-            .paint(|ctx| ctx.draw(&app.board)),
-        ratatui::prelude::Rect::new(
-            (frame.size().width / 2) - (CONFIG.board_size.0 / 2 + 1) as u16,
-            (frame.size().height / 2) - (CONFIG.board_size.1 / 4 + 1) as u16,
-            (CONFIG.board_size.0 + 2) as u16,
-            ((CONFIG.board_size.1 / 2) + 2) as u16,
-        ),
+        Paragraph::new(lines.into_iter().map(Line::from).collect::<Vec<_>>())
+            .block(
+                panel(theme)
+                    .title_alignment(Alignment::Center)
+                    .title("stats"),
+            )
+            .alignment(Alignment::Center),
+        area,
     );
 }
 
-fn render_stats(app: &App, frame: &mut Frame) {
-    let score_text = "score: ";
-    let level_text = "level: ";
-    let lines_cleared_text = "lines cleared: ";
-    let block_width = (num_of_digits(app.board.get_score()) + score_text.len() as u16)
-        .max(num_of_digits(app.board.get_lines_cleared()) + lines_cleared_text.len() as u16)
-        // calculating level text length is not needed since it's always lower than lines cleared
-        .max(16)
-        + 2; // + 2 for the border
-    let block_height = 5;
+/// Names of the last clear, rising and fading away as `progress` goes from 0 to 1.
+fn render_action_text(
+    theme: &Theme,
+    lines: &[String],
+    progress: f32,
+    frame: &mut Frame,
+    area: Rect,
+) {
+    let height = lines.len() as u16;
+    if height > area.height {
+        return;
+    }
+    // start in the middle and rise up to two rows
+    let rise = ((progress * 3.0) as u16).min(2);
+    let y = (area.y + (area.height - height) / 2)
+        .saturating_sub(rise)
+        .max(area.y);
+    let mut style = Style::default()
+        .fg(theme.highlight)
+        .add_modifier(Modifier::BOLD);
+    if progress > 0.7 {
+        style = style.add_modifier(Modifier::DIM);
+    }
     frame.render_widget(
-        Paragraph::new(format!(
-            "{}{}\n{}{}\n{}{}",
-            score_text,
-            app.board.get_score(),
-            level_text,
-            app.board.get_level(),
-            lines_cleared_text,
-            app.board.get_lines_cleared()
-        ))
-        .block(
-            Block::default()
-                .title_alignment(Alignment::Center)
-                .borders(Borders::ALL)
-                .border_type(BorderType::Rounded)
-                .title("stats"),
+        Paragraph::new(
+            lines
+                .iter()
+                .map(|line| Line::styled(line.clone(), style))
+                .collect::<Vec<_>>(),
         )
         .alignment(Alignment::Center),
-        ratatui::prelude::Rect::new(
-            (frame.size().width / 2) - (CONFIG.board_size.0 / 2) as u16 - block_width - 1,
-            (frame.size().height / 2) + (CONFIG.board_size.1 / 4) as u16 - block_height
-                + (((CONFIG.board_size.1 as u16 + 2) % 4) + 1) % 3, // keeps as close to bottom of board without passing it
-            block_width,
-            block_height,
-        ),
+        Rect::new(area.x, y, area.width, height),
     );
 }
 
-fn render_next_previews(
-    app: &App,
+/// A bordered box showing a single piece, for the hold and next pieces.
+fn render_preview_box(
+    theme: &Theme,
     frame: &mut Frame,
-    bounds: [f64; 2],
-    marker: Marker,
-    borders: Borders,
-    border_type: BorderType,
+    area: Rect,
+    title: &str,
+    preview: Option<Preview>,
+    look: CellLook,
 ) {
-    // next pieces
-    let piece_preview = canvas::Canvas::default()
-        .x_bounds(bounds)
-        .y_bounds(bounds)
-        .marker(marker);
-    let block_width = 6;
-    let block_height = 3;
-    let preview_positions = calculate_next_preview_positions(
-        CONFIG.number_of_previews,
-        frame.size(),
-        block_width,
-        block_height,
-    );
-    for (i, position) in preview_positions.iter().enumerate() {
-        let i = i + 1; // show the first piece ahead, not current one
-        let tetromino_to_preview = Tetromino::new(app.board.calc_next_piece(i));
+    let block = panel(theme).title(title);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    if let Some(preview) = preview {
         frame.render_widget(
-            piece_preview
-                .clone()
-                .block(
-                    Block::default()
-                        .borders(borders)
-                        .border_type(border_type)
-                        .title(match i {
-                            1 => {
-                                format!("{}st", i)
-                            }
-                            2 => {
-                                format!("{}nd", i)
-                            }
-                            3 => {
-                                format!("{}rd", i)
-                            }
-                            _ => {
-                                format!("{}th", i)
-                            }
-                        }),
-                )
-                .paint(move |ctx| ctx.draw(&tetromino_to_preview)),
-            ratatui::prelude::Rect::new(position.0, position.1, block_width, block_height),
+            CellGrid {
+                cells: &preview.cells(),
+                look,
+            },
+            inner,
         );
     }
 }
 
-fn calculate_next_preview_positions(
-    num_of_previews: usize,
-    frame_size: Rect,
-    block_width: u16,
-    block_height: u16,
-) -> Vec<(u16, u16)> {
-    let next_preview_base_x = 1 + (frame_size.width / 2) + (CONFIG.board_size.0 / 2) as u16;
-    let next_preview_base_y = (frame_size.height / 2) - (CONFIG.board_size.1 / 4) as u16;
-    let drawable_screen_height = frame_size.height - block_height;
-    let drawable_screen_after_base = drawable_screen_height - next_preview_base_y;
-    (1..num_of_previews + 1)
-        .map(|i| {
-            let spaces_for_previus = (i - 1) as u16 * block_height;
-            let y_before_wrap = next_preview_base_y + spaces_for_previus;
-            let how_many_wraps = spaces_for_previus / drawable_screen_after_base;
-            let y_before_wrap_adjustment =
-                (y_before_wrap + how_many_wraps * next_preview_base_y) % (drawable_screen_height);
-            (
-                next_preview_base_x + block_width * how_many_wraps,
-                y_before_wrap_adjustment
-                    - ((y_before_wrap_adjustment - next_preview_base_y) % block_height),
-            )
-        })
-        .collect()
-}
-
-fn render_hold(
-    app: &App,
-    frame: &mut Frame,
-    bounds: [f64; 2],
-    marker: Marker,
-    borders: Borders,
-    border_type: BorderType,
-) {
-    let block_width = 6;
-    let piece_preview = canvas::Canvas::default()
-        .block(
-            Block::default()
-                .borders(borders)
-                .border_type(border_type)
-                .title("hold"),
-        )
-        .x_bounds(bounds)
-        .y_bounds(bounds)
-        .marker(marker);
-    match app.board.get_held_tetromino() {
-        None => {}
-        Some(tetromino_shape) => {
-            let tetromino_to_preview = Tetromino::new(tetromino_shape);
-            frame.render_widget(
-                piece_preview
-                    .clone()
-                    .paint(|ctx| ctx.draw(&tetromino_to_preview)),
-                ratatui::prelude::Rect::new(
-                    (frame.size().width / 2) - (CONFIG.board_size.0 / 2) as u16 - block_width - 1,
-                    (frame.size().height / 2) - (CONFIG.board_size.1 / 4) as u16,
-                    block_width,
-                    3,
-                ),
-            )
-        }
-    };
+/// A box with rounded borders in the theme's panel color.
+fn panel<'a>(theme: &Theme) -> Block<'a> {
+    Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(theme.panel_border))
 }
 
-fn num_of_digits(num: u128) -> u16 {
-    num.checked_ilog10().unwrap_or(0) as u16 + 1
+fn ordinal(n: usize) -> String {
+    if (11..=13).contains(&(n % 100)) {
+        return format!("{n}th");
+    }
+    match n % 10 {
+        1 => format!("{n}st"),
+        2 => format!("{n}nd"),
+        3 => format!("{n}rd"),
+        _ => format!("{n}th"),
+    }
 }
 
+#[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
-    fn test_calculate_next_preview_positions() {
-        use super::{calculate_next_preview_positions, Rect};
-        calculate_next_preview_positions(7, Rect::new(0, 0, 40, 40), 4, 4);
+    fn test_ordinal() {
+        let names: Vec<_> = [1, 2, 3, 4, 11, 12, 13, 21, 22, 23, 111, 112]
+            .into_iter()
+            .map(ordinal)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "22nd", "23rd",
+                "111th", "112th"
+            ]
+        );
     }
 }
@@ -1,32 +1,71 @@
-use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::event::{
+    KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::prelude::Rect;
 
-use crate::app::App;
+use crate::app::{App, Screen};
+use crate::board::{Action, Cell};
 use crate::tetromino::Direction;
+use crate::ui::editor_layout;
 
 pub fn update(app: &mut App) {
-    match app.board.update() {
-        Ok(_) => {}
-        Err(_) => app.quit(),
-    };
+    match app.screen {
+        Screen::Playing => app.run_frames(),
+        Screen::Replay(..) => app.step_playback(),
+        Screen::GameOver(_) => app.animate_game_over(),
+        _ => {}
+    }
 }
 
 pub fn key_event_update(app: &mut App, key_event: KeyEvent) {
-    if app.paused {
-        paused_key_update(app, key_event);
+    // exit from anywhere
+    if let KeyEvent {
+        code: KeyCode::Char('c') | KeyCode::Char('C'),
+        modifiers: KeyModifiers::CONTROL,
+        ..
+    } = key_event
+    {
+        app.quit();
+        return;
+    }
+
+    if key_event.kind == KeyEventKind::Release {
+        app.key_releases = true;
+    }
+
+    // reload the config file from anywhere
+    if let KeyEvent {
+        code: KeyCode::F(5),
+        kind: KeyEventKind::Press,
+        ..
+    } = key_event
+    {
+        app.reload_config();
         return;
     }
 
+    match app.screen {
+        Screen::Menu(_) => menu_key_update(app, key_event),
+        Screen::HighScores(_) => high_scores_key_update(app, key_event),
+        Screen::Puzzles(_) => puzzles_key_update(app, key_event),
+        Screen::Settings(_) => settings_key_update(app, key_event),
+        Screen::Editor(_) => editor_key_update(app, key_event),
+        Screen::Playing => playing_key_update(app, key_event),
+        Screen::Paused => paused_key_update(app, key_event),
+        Screen::GameOver(_) => game_over_key_update(app, key_event),
+        Screen::Replay(..) => playback_key_update(app, key_event),
+        Screen::Quit => {}
+    }
+}
+
+fn playing_key_update(app: &mut App, key_event: KeyEvent) {
     match key_event {
-        // exit
+        // back to the menu
         KeyEvent {
             code: KeyCode::Esc | KeyCode::Char('q'),
+            kind: KeyEventKind::Press,
             ..
-        }
-        | KeyEvent {
-            code: KeyCode::Char('c') | KeyCode::Char('C'),
-            modifiers: KeyModifiers::CONTROL,
-            ..
-        } => app.quit(),
+        } => app.open_menu(),
 
         // restart
         KeyEvent {
@@ -35,59 +74,93 @@ pub fn key_event_update(app: &mut App, key_event: KeyEvent) {
             ..
         } => app.reset(),
 
+        // undo and redo placements in practice games
+        KeyEvent {
+            code: KeyCode::Char('z'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.undo_placement(false),
+        KeyEvent {
+            code: KeyCode::Char('y'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.undo_placement(true),
+
         // hold
         KeyEvent {
             code: KeyCode::Char('c'),
             kind: KeyEventKind::Press,
             ..
-        } => match app.board.hold() {
-            Ok(_) => {}
-            Err(_) => app.quit(),
-        },
+        } => app.act(Action::Hold),
 
-        // move to sides
+        // move to sides, the board auto shifts held directions on terminals
+        // that report releases, the others repeat the key
         KeyEvent {
             code: KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('l'),
-            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            kind: KeyEventKind::Press,
             ..
-        } => app.board.move_current_piece(Direction::Right),
+        } => app.act(press_direction(app, Direction::Right)),
         KeyEvent {
             code: KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('h'),
-            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            kind: KeyEventKind::Press,
+            ..
+        } => app.act(press_direction(app, Direction::Left)),
+        KeyEvent {
+            code: KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('l'),
+            kind: KeyEventKind::Repeat,
+            ..
+        } if !app.key_releases => app.act_held(Action::Move(Direction::Right)),
+        KeyEvent {
+            code: KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('h'),
+            kind: KeyEventKind::Repeat,
+            ..
+        } if !app.key_releases => app.act_held(Action::Move(Direction::Left)),
+        KeyEvent {
+            code: KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('l'),
+            kind: KeyEventKind::Release,
+            ..
+        } => app.act(Action::MoveReleased(Direction::Right)),
+        KeyEvent {
+            code: KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('h'),
+            kind: KeyEventKind::Release,
             ..
-        } => app.board.move_current_piece(Direction::Left),
+        } => app.act(Action::MoveReleased(Direction::Left)),
 
         // rotate
         KeyEvent {
             code: KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('k'),
             kind: KeyEventKind::Press,
             ..
-        } => app.board.rotate_current_piece(true),
+        } => app.act(Action::Rotate(true)),
         KeyEvent {
             code: KeyCode::Char('z'),
             kind: KeyEventKind::Press,
             ..
-        } => app.board.rotate_current_piece(false),
+        } => app.act(Action::Rotate(false)),
 
         // drop
         KeyEvent {
             code: KeyCode::Char(' '),
             kind: KeyEventKind::Press,
             ..
-        } => match app.board.hard_drop() {
-            Ok(_) => {}
-            Err(_) => app.quit(),
-        },
+        } => app.act(Action::HardDrop),
+        KeyEvent {
+            code: KeyCode::Char('x'),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.act(Action::SonicDrop),
         KeyEvent {
             code: KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('j'),
             kind: KeyEventKind::Press,
             ..
-        } => app.board.soft_drop(true),
+        } => app.act(Action::SoftDrop(true)),
         KeyEvent {
             code: KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('j'),
             kind: KeyEventKind::Release,
             ..
-        } => app.board.soft_drop(false),
+        } => app.act(Action::SoftDrop(false)),
 
         // pause
         KeyEvent {
@@ -96,19 +169,429 @@ pub fn key_event_update(app: &mut App, key_event: KeyEvent) {
             ..
         } => app.pause(true),
 
+        // show every statistic
+        KeyEvent {
+            code: KeyCode::Tab,
+            kind: KeyEventKind::Press,
+            ..
+        } => app.toggle_all_stats(),
+
+        // share the board
+        KeyEvent {
+            code: KeyCode::Char('f'),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.copy_fumen(),
+
         _ => {}
     };
+}
 
-    fn paused_key_update(app: &mut App, key_event: KeyEvent) {
+fn paused_key_update(app: &mut App, key_event: KeyEvent) {
+    match key_event {
         // unpause
+        KeyEvent {
+            code: KeyCode::Char('p'),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.pause(false),
+
+        // share the board
+        KeyEvent {
+            code: KeyCode::Char('f'),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.copy_fumen(),
+
+        // back to the menu
+        KeyEvent {
+            code: KeyCode::Esc | KeyCode::Char('q'),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.open_menu(),
+
+        _ => {}
+    }
+}
+
+fn menu_key_update(app: &mut App, key_event: KeyEvent) {
+    match key_event {
+        // exit
+        KeyEvent {
+            code: KeyCode::Esc | KeyCode::Char('q'),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.quit(),
+
+        // navigate items
+        KeyEvent {
+            code: KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('k'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.select_menu_item(false),
+        KeyEvent {
+            code: KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('j'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.select_menu_item(true),
+
+        // change values
+        KeyEvent {
+            code: KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('l'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.change_menu_value(true),
+        KeyEvent {
+            code: KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('h'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.change_menu_value(false),
+
+        KeyEvent {
+            code: KeyCode::Enter | KeyCode::Char(' '),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.confirm_menu_item(),
+
+        _ => {}
+    }
+}
+
+fn high_scores_key_update(app: &mut App, key_event: KeyEvent) {
+    match key_event {
+        // change mode
+        KeyEvent {
+            code: KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('l'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.cycle_high_scores_mode(true),
+        KeyEvent {
+            code: KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('h'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.cycle_high_scores_mode(false),
+
+        // back to the menu
+        KeyEvent {
+            code: KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter,
+            kind: KeyEventKind::Press,
+            ..
+        } => app.open_menu(),
+
+        _ => {}
+    }
+}
+
+fn puzzles_key_update(app: &mut App, key_event: KeyEvent) {
+    match key_event {
+        // change selection
+        KeyEvent {
+            code: KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('j'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.select_puzzle(true),
+        KeyEvent {
+            code: KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('k'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.select_puzzle(false),
+
+        // play the selected puzzle
+        KeyEvent {
+            code: KeyCode::Enter | KeyCode::Char(' '),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.start_puzzle(),
+
+        // back to the menu
+        KeyEvent {
+            code: KeyCode::Esc | KeyCode::Char('q'),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.open_menu(),
+
+        _ => {}
+    }
+}
+
+fn settings_key_update(app: &mut App, key_event: KeyEvent) {
+    let Screen::Settings(settings) = &mut app.screen else {
+        return;
+    };
+
+    // typing a value
+    if settings.input.is_some() {
         match key_event {
             KeyEvent {
-                code: KeyCode::Char('p'),
+                code: KeyCode::Enter,
                 kind: KeyEventKind::Press,
                 ..
-            } => app.pause(false),
-
+            } => settings.submit_input(),
+            KeyEvent {
+                code: KeyCode::Esc,
+                kind: KeyEventKind::Press,
+                ..
+            } => settings.cancel_input(),
+            KeyEvent {
+                code: KeyCode::Backspace,
+                kind: KeyEventKind::Press | KeyEventKind::Repeat,
+                ..
+            } => settings.delete_char(),
+            KeyEvent {
+                code: KeyCode::Char(c),
+                kind: KeyEventKind::Press | KeyEventKind::Repeat,
+                ..
+            } => settings.type_char(c),
             _ => {}
         }
+        return;
+    }
+
+    match key_event {
+        // save
+        KeyEvent {
+            code: KeyCode::Char('s'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            ..
+        } => app.save_settings(),
+
+        // back to the menu
+        KeyEvent {
+            code: KeyCode::Esc | KeyCode::Char('q'),
+            kind: KeyEventKind::Press,
+            ..
+        } if settings.leave() => app.open_menu(),
+
+        // navigate fields
+        KeyEvent {
+            code: KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('k'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => settings.select(false),
+        KeyEvent {
+            code: KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('j'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => settings.select(true),
+
+        // change values
+        KeyEvent {
+            code: KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('l'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => settings.step(true),
+        KeyEvent {
+            code: KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('h'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => settings.step(false),
+        KeyEvent {
+            code: KeyCode::Enter,
+            kind: KeyEventKind::Press,
+            ..
+        } => settings.start_input(),
+
+        _ => {}
+    }
+}
+
+/// The action of pressing a direction, charging DAS if its release will be reported.
+fn press_direction(app: &App, direction: Direction) -> Action {
+    if app.key_releases {
+        Action::MovePressed(direction)
+    } else {
+        Action::Move(direction)
+    }
+}
+
+fn editor_key_update(app: &mut App, key_event: KeyEvent) {
+    let Screen::Editor(editor) = &mut app.screen else {
+        return;
+    };
+    if key_event.kind == KeyEventKind::Release {
+        return;
+    }
+    match key_event {
+        // undo and redo
+        KeyEvent {
+            code: KeyCode::Char('z'),
+            modifiers: KeyModifiers::CONTROL,
+            ..
+        } => editor.undo(),
+        KeyEvent {
+            code: KeyCode::Char('y'),
+            modifiers: KeyModifiers::CONTROL,
+            ..
+        } => editor.redo(),
+
+        // play from the edited board, or go back to the menu
+        KeyEvent {
+            code: KeyCode::Enter,
+            ..
+        } => app.play_editor(),
+        KeyEvent {
+            code: KeyCode::Esc, ..
+        } => app.open_menu(),
+
+        // move the cursor
+        KeyEvent {
+            code: KeyCode::Up, ..
+        } => editor.move_cursor(0, -1),
+        KeyEvent {
+            code: KeyCode::Down,
+            ..
+        } => editor.move_cursor(0, 1),
+        KeyEvent {
+            code: KeyCode::Left,
+            ..
+        } => editor.move_cursor(-1, 0),
+        KeyEvent {
+            code: KeyCode::Right,
+            ..
+        } => editor.move_cursor(1, 0),
+
+        // paint and erase
+        KeyEvent {
+            code: KeyCode::Char(' '),
+            ..
+        } => editor.paint_cursor(editor.brush),
+        KeyEvent {
+            code: KeyCode::Char('x') | KeyCode::Backspace | KeyCode::Delete,
+            ..
+        } => editor.erase(),
+        KeyEvent {
+            code: KeyCode::Char('n'),
+            ..
+        } => editor.clear(),
+        KeyEvent {
+            code: KeyCode::Tab, ..
+        } => editor.cycle_focus(),
+
+        // pick the brush, or add a piece to the queue or hold
+        KeyEvent {
+            code: KeyCode::Char('g'),
+            ..
+        } => editor.brush = Cell::Garbage,
+        KeyEvent {
+            code: KeyCode::Char('e'),
+            ..
+        } => editor.brush = Cell::Empty,
+        KeyEvent {
+            code: KeyCode::Char(c),
+            ..
+        } => {
+            if let Some(Cell::Occupied(shape)) = Cell::from_char(c) {
+                editor.type_shape(shape);
+            }
+        }
+
+        _ => {}
+    }
+}
+
+/// Paints the editor's board with the mouse, the left button paints with the
+/// brush and the right one erases.
+pub fn mouse_event_update(app: &mut App, mouse_event: MouseEvent) {
+    let Screen::Editor(editor) = &mut app.screen else {
+        return;
+    };
+    let cell = match mouse_event.kind {
+        MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left) => {
+            editor.brush
+        }
+        MouseEventKind::Down(MouseButton::Right) | MouseEventKind::Drag(MouseButton::Right) => {
+            Cell::Empty
+        }
+        MouseEventKind::Up(_) => {
+            editor.end_stroke();
+            return;
+        }
+        _ => return,
+    };
+    if let MouseEventKind::Down(_) = mouse_event.kind {
+        editor.begin_stroke();
+    }
+    let Ok((width, height)) = crossterm::terminal::size() else {
+        return;
+    };
+    let position =
+        editor_layout(editor, &app.config, Rect::new(0, 0, width, height)).and_then(|layout| {
+            layout.board_cell(mouse_event.column, mouse_event.row, app.config.board_size)
+        });
+    if let Some(position) = position {
+        editor.paint(position, cell);
+    }
+}
+
+fn game_over_key_update(app: &mut App, key_event: KeyEvent) {
+    // ignore keys held from the last game until the top out animation is over
+    if !matches!(&app.screen, Screen::GameOver(game_over) if game_over.animation_done) {
+        return;
+    }
+
+    match key_event {
+        // back to the menu
+        KeyEvent {
+            code: KeyCode::Esc | KeyCode::Char('q'),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.open_menu(),
+
+        // restart
+        KeyEvent {
+            code: KeyCode::Char('r'),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.reset(),
+
+        // navigate options
+        KeyEvent {
+            code: KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('k'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.select_game_over_option(false),
+        KeyEvent {
+            code: KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('j'),
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        } => app.select_game_over_option(true),
+        KeyEvent {
+            code: KeyCode::Enter,
+            kind: KeyEventKind::Press,
+            ..
+        } => app.confirm_game_over_option(),
+
+        _ => {}
+    }
+}
+
+fn playback_key_update(app: &mut App, key_event: KeyEvent) {
+    match key_event {
+        // skip to the end of the replay
+        KeyEvent {
+            code: KeyCode::Esc | KeyCode::Char('q'),
+            kind: KeyEventKind::Press,
+            ..
+        } => app.stop_playback(),
+
+        // pause
+        KeyEvent {
+            code: KeyCode::Char('p'),
+            kind: KeyEventKind::Press,
+            ..
+        } => {
+            let paused = matches!(&app.screen, Screen::Replay(playback, _) if playback.is_paused());
+            app.pause(!paused)
+        }
+
+        // show every statistic
+        KeyEvent {
+            code: KeyCode::Tab,
+            kind: KeyEventKind::Press,
+            ..
+        } => app.toggle_all_stats(),
+
+        _ => {}
     }
 }